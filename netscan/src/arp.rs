@@ -0,0 +1,29 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+// #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+// pub struct ArpResponse(pub Ipv4Address, pub EthernetAddress);
+
+pub type ArpResponses = BTreeMap<Ipv4Address, EthernetAddress>;
+
+impl super::StringableVec for ArpResponses {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.iter() {
+            ret.push(format!("{} ({})", i.0, i.1));
+        }
+        ret
+    }
+}
+
+impl super::NamedStringableVec for ArpResponses {
+    fn to_named_string_vec(&self, names: &super::HostNames) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.iter() {
+            ret.push(format!("{} ({})", super::host_label(*i.0, names), i.1));
+        }
+        ret
+    }
+}
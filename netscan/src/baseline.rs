@@ -0,0 +1,384 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+use super::arp::ArpResponses;
+use super::HostNames;
+
+/// Time between two scans compared with the baseline
+pub const RESCAN_INTERVAL: Duration = Duration { millis: 60_000 };
+/// Maximum length of a device label entered by the user
+pub const MAX_LABEL_LEN: usize = 24;
+/// Marks a saved baseline, "NSBL"
+const MAGIC: u32 = 0x4e53_424c;
+/// Magic, payload length and checksum
+const HEADER_LEN: usize = 8;
+
+/// A difference between a neighbor table and the baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    /// A hardware address that is not in the baseline
+    NewDevice(Ipv4Address, EthernetAddress),
+    /// A device of the baseline using an address it did not have before
+    NewAddress(Ipv4Address, EthernetAddress),
+    /// A device of the baseline that is gone
+    Missing(Ipv4Address, EthernetAddress),
+}
+
+impl Change {
+    pub fn eth_addr(&self) -> EthernetAddress {
+        match *self {
+            Change::NewDevice(_, x) | Change::NewAddress(_, x) | Change::Missing(_, x) => x,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::NewDevice(addr, eth_addr) => write!(f, "New device {} ({})", addr, eth_addr),
+            Change::NewAddress(addr, eth_addr) => write!(f, "New address {} ({})", addr, eth_addr),
+            Change::Missing(addr, eth_addr) => write!(f, "Missing {} ({})", addr, eth_addr),
+        }
+    }
+}
+
+/// Compare a single binding with the baseline, None if the baseline has it
+pub fn check_host(
+    baseline: &ArpResponses,
+    addr: Ipv4Address,
+    eth_addr: EthernetAddress,
+) -> Option<Change> {
+    if baseline.get(&addr) == Some(&eth_addr) {
+        None
+    } else if baseline.values().any(|x| *x == eth_addr) {
+        Some(Change::NewAddress(addr, eth_addr))
+    } else {
+        Some(Change::NewDevice(addr, eth_addr))
+    }
+}
+
+/// Return all differences of a full neighbor table to the baseline
+/// Devices are identified by their hardware address, a device answering on another address
+/// is not missing
+pub fn diff(baseline: &ArpResponses, current: &ArpResponses) -> Vec<Change> {
+    let mut changes: Vec<Change> = current
+        .iter()
+        .filter_map(|(addr, eth_addr)| check_host(baseline, *addr, *eth_addr))
+        .collect();
+    for (addr, eth_addr) in baseline.iter() {
+        if !current.values().any(|x| x == eth_addr) {
+            changes.push(Change::Missing(*addr, *eth_addr));
+        }
+    }
+    changes
+}
+
+/// The neighbor table at a point in time, devices not in it raise alerts
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    pub hosts: ArpResponses,
+    /// Names of the devices, hostnames known when the baseline was saved or set by the user
+    pub labels: BTreeMap<EthernetAddress, String>,
+    /// When the baseline was saved, or restored after a reset
+    pub saved: Instant,
+}
+
+impl Baseline {
+    /// Save neighbors as baseline, devices are labeled with their hostnames
+    pub fn new(neighbors: &ArpResponses, names: &HostNames, saved: Instant) -> Self {
+        let labels = neighbors
+            .iter()
+            .filter_map(|(addr, eth_addr)| {
+                names
+                    .get(addr)
+                    .map(|name| (*eth_addr, name.chars().take(MAX_LABEL_LEN).collect()))
+            })
+            .collect();
+        Baseline {
+            hosts: neighbors.clone(),
+            labels,
+            saved,
+        }
+    }
+
+    pub fn label(&self, eth_addr: EthernetAddress) -> Option<&String> {
+        self.labels.get(&eth_addr)
+    }
+
+    /// Name a device of the baseline, an empty label removes its name
+    pub fn set_label(&mut self, eth_addr: EthernetAddress, label: &str) {
+        if label.is_empty() {
+            self.labels.remove(&eth_addr);
+        } else if self.hosts.values().any(|x| *x == eth_addr) {
+            self.labels
+                .insert(eth_addr, label.chars().take(MAX_LABEL_LEN).collect());
+        }
+    }
+
+    /// Describe the devices of the baseline with their labels
+    pub fn device_lines(&self) -> Vec<String> {
+        self.hosts
+            .iter()
+            .map(|(addr, eth_addr)| match self.label(*eth_addr) {
+                Some(label) => format!("{} {} {}", addr, eth_addr, label),
+                None => format!("{} {}", addr, eth_addr),
+            })
+            .collect()
+    }
+
+    /// The hardware address of the device described by line index of device_lines
+    pub fn device_at(&self, index: usize) -> Option<EthernetAddress> {
+        self.hosts.values().nth(index).cloned()
+    }
+
+    /// Serialize the baseline to at most max_len bytes, labels that don't fit are left out
+    /// Fails if not even all hosts fit
+    pub fn to_bytes(&self, max_len: usize) -> Result<Vec<u8>, &'static str> {
+        // Header, host count, hosts and label count
+        if HEADER_LEN + 2 + self.hosts.len() * 10 + 2 > max_len {
+            return Err("Too many devices to save the baseline");
+        }
+        let mut payload = vec![0; 2];
+        NetworkEndian::write_u16(&mut payload[0..2], self.hosts.len() as u16);
+        for (addr, eth_addr) in self.hosts.iter() {
+            payload.extend_from_slice(addr.as_bytes());
+            payload.extend_from_slice(eth_addr.as_bytes());
+        }
+        let count_pos = payload.len();
+        payload.extend_from_slice(&[0, 0]);
+        let mut count = 0;
+        for (eth_addr, label) in self.labels.iter() {
+            // Labels are at most MAX_LABEL_LEN characters, their length fits into a byte
+            let label = label.as_bytes();
+            if HEADER_LEN + payload.len() + 7 + label.len() > max_len {
+                break;
+            }
+            payload.extend_from_slice(eth_addr.as_bytes());
+            payload.push(label.len() as u8);
+            payload.extend_from_slice(label);
+            count += 1;
+        }
+        NetworkEndian::write_u16(&mut payload[count_pos..count_pos + 2], count);
+
+        let mut data = vec![0; HEADER_LEN];
+        NetworkEndian::write_u32(&mut data[0..4], MAGIC);
+        NetworkEndian::write_u16(&mut data[4..6], payload.len() as u16);
+        NetworkEndian::write_u16(&mut data[6..8], checksum(&payload));
+        data.extend_from_slice(&payload);
+        Ok(data)
+    }
+
+    /// Restore a baseline written by to_bytes, None if data doesn't hold one
+    pub fn from_bytes(data: &[u8], saved: Instant) -> Option<Self> {
+        if data.len() < HEADER_LEN || NetworkEndian::read_u32(&data[0..4]) != MAGIC {
+            return None;
+        }
+        let len = usize::from(NetworkEndian::read_u16(&data[4..6]));
+        let payload = data.get(HEADER_LEN..HEADER_LEN + len)?;
+        if checksum(payload) != NetworkEndian::read_u16(&data[6..8]) {
+            return None;
+        }
+
+        let mut pos = 0;
+        let mut hosts = ArpResponses::new();
+        for _ in 0..NetworkEndian::read_u16(payload.get(pos..pos + 2)?) {
+            let host = payload.get(pos + 2..pos + 12)?;
+            hosts.insert(
+                Ipv4Address::from_bytes(&host[0..4]),
+                EthernetAddress::from_bytes(&host[4..10]),
+            );
+            pos += 10;
+        }
+        pos += 2;
+        let mut labels = BTreeMap::new();
+        for _ in 0..NetworkEndian::read_u16(payload.get(pos..pos + 2)?) {
+            let eth_addr = EthernetAddress::from_bytes(payload.get(pos + 2..pos + 8)?);
+            let len = usize::from(*payload.get(pos + 8)?);
+            let label = payload.get(pos + 9..pos + 9 + len)?;
+            labels.insert(eth_addr, String::from(core::str::from_utf8(label).ok()?));
+            pos += 7 + len;
+        }
+        Some(Baseline {
+            hosts,
+            labels,
+            saved,
+        })
+    }
+}
+
+/// Sum of all bytes, tells a saved baseline from random memory content
+fn checksum(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(0u16, |sum, x| sum.wrapping_add(u16::from(*x)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRINTER: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 0x01]);
+    const SWITCH: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 0x02]);
+    const LAPTOP: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 0x03]);
+
+    fn addr(host: u8) -> Ipv4Address {
+        Ipv4Address::new(10, 0, 0, host)
+    }
+
+    fn table(hosts: &[(u8, EthernetAddress)]) -> ArpResponses {
+        hosts
+            .iter()
+            .map(|(host, eth_addr)| (addr(*host), *eth_addr))
+            .collect()
+    }
+
+    #[test]
+    fn known_binding_is_no_change() {
+        let baseline = table(&[(1, PRINTER), (2, SWITCH)]);
+        assert_eq!(check_host(&baseline, addr(1), PRINTER), None);
+        assert!(diff(&baseline, &baseline).is_empty());
+    }
+
+    #[test]
+    fn new_device() {
+        let baseline = table(&[(1, PRINTER)]);
+        assert_eq!(
+            check_host(&baseline, addr(3), LAPTOP),
+            Some(Change::NewDevice(addr(3), LAPTOP))
+        );
+        // An unknown device on a known address is new as well
+        assert_eq!(
+            check_host(&baseline, addr(1), LAPTOP),
+            Some(Change::NewDevice(addr(1), LAPTOP))
+        );
+        let current = table(&[(1, PRINTER), (3, LAPTOP)]);
+        assert_eq!(
+            diff(&baseline, &current),
+            vec![Change::NewDevice(addr(3), LAPTOP)]
+        );
+    }
+
+    #[test]
+    fn new_address() {
+        let baseline = table(&[(1, PRINTER)]);
+        assert_eq!(
+            check_host(&baseline, addr(4), PRINTER),
+            Some(Change::NewAddress(addr(4), PRINTER))
+        );
+    }
+
+    #[test]
+    fn missing_device() {
+        let baseline = table(&[(1, PRINTER), (2, SWITCH)]);
+        let current = table(&[(1, PRINTER)]);
+        assert_eq!(
+            diff(&baseline, &current),
+            vec![Change::Missing(addr(2), SWITCH)]
+        );
+        assert_eq!(
+            diff(&baseline, &ArpResponses::new()),
+            vec![
+                Change::Missing(addr(1), PRINTER),
+                Change::Missing(addr(2), SWITCH)
+            ]
+        );
+    }
+
+    #[test]
+    fn moved_device_is_not_missing() {
+        let baseline = table(&[(1, PRINTER), (2, SWITCH)]);
+        let current = table(&[(1, PRINTER), (5, SWITCH)]);
+        assert_eq!(
+            diff(&baseline, &current),
+            vec![Change::NewAddress(addr(5), SWITCH)]
+        );
+    }
+
+    #[test]
+    fn labels_are_taken_from_hostnames() {
+        let neighbors = table(&[(1, PRINTER), (2, SWITCH)]);
+        let mut names = HostNames::new();
+        names.insert(addr(1), String::from("printer"));
+        let baseline = Baseline::new(&neighbors, &names, Instant::from_millis(0));
+        assert_eq!(baseline.label(PRINTER), Some(&String::from("printer")));
+        assert_eq!(baseline.label(SWITCH), None);
+    }
+
+    #[test]
+    fn labels_are_edited() {
+        let neighbors = table(&[(1, PRINTER), (2, SWITCH)]);
+        let mut baseline = Baseline::new(&neighbors, &HostNames::new(), Instant::from_millis(0));
+        assert_eq!(baseline.device_at(1), Some(SWITCH));
+        baseline.set_label(SWITCH, "core switch");
+        assert_eq!(baseline.label(SWITCH), Some(&String::from("core switch")));
+        assert_eq!(
+            baseline.device_lines()[1],
+            format!("{} {} core switch", addr(2), SWITCH)
+        );
+        // Devices that are not part of the baseline are not labeled
+        baseline.set_label(LAPTOP, "laptop");
+        assert_eq!(baseline.label(LAPTOP), None);
+        baseline.set_label(SWITCH, "");
+        assert_eq!(baseline.label(SWITCH), None);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let neighbors = table(&[(1, PRINTER), (2, SWITCH)]);
+        let mut names = HostNames::new();
+        names.insert(addr(1), String::from("printer"));
+        let baseline = Baseline::new(&neighbors, &names, Instant::from_millis(0));
+        let data = baseline.to_bytes(4096).unwrap();
+
+        let restored = Baseline::from_bytes(&data, Instant::from_millis(5)).unwrap();
+        assert_eq!(restored.hosts, baseline.hosts);
+        assert_eq!(restored.labels, baseline.labels);
+        assert_eq!(restored.saved, Instant::from_millis(5));
+    }
+
+    #[test]
+    fn labels_are_dropped_when_full() {
+        let neighbors = table(&[(1, PRINTER)]);
+        let mut names = HostNames::new();
+        names.insert(addr(1), String::from("printer"));
+        let baseline = Baseline::new(&neighbors, &names, Instant::from_millis(0));
+        // Header, host count, one host and the label count
+        let data = baseline.to_bytes(8 + 2 + 10 + 2).unwrap();
+        assert_eq!(data.len(), 22);
+
+        let restored = Baseline::from_bytes(&data, Instant::from_millis(0)).unwrap();
+        assert_eq!(restored.hosts, baseline.hosts);
+        assert!(restored.labels.is_empty());
+    }
+
+    #[test]
+    fn too_many_hosts_are_rejected() {
+        let mut neighbors = ArpResponses::new();
+        for i in 0..500u16 {
+            neighbors.insert(
+                Ipv4Address::new(10, 0, (i >> 8) as u8, i as u8),
+                EthernetAddress([2, 0, 0, 0, (i >> 8) as u8, i as u8]),
+            );
+        }
+        let baseline = Baseline::new(&neighbors, &HostNames::new(), Instant::from_millis(0));
+        assert!(baseline.to_bytes(4096).is_err());
+        // Header, host count, 500 hosts and the label count
+        assert_eq!(baseline.to_bytes(5012).unwrap().len(), 5012);
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        let neighbors = table(&[(1, PRINTER), (2, SWITCH)]);
+        let baseline = Baseline::new(&neighbors, &HostNames::new(), Instant::from_millis(0));
+        let mut data = baseline.to_bytes(4096).unwrap();
+        let len = data.len();
+        assert!(Baseline::from_bytes(&data[..len - 1], Instant::from_millis(0)).is_none());
+        data[10] ^= 0xff;
+        assert!(Baseline::from_bytes(&data, Instant::from_millis(0)).is_none());
+        assert!(Baseline::from_bytes(&[0; 64], Instant::from_millis(0)).is_none());
+    }
+}
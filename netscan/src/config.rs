@@ -0,0 +1,163 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::wire::{IpCidr, Ipv4Address, Ipv4Cidr};
+
+/// Longest input of a configuration field, an address with prefix length
+pub const MAX_FIELD_LEN: usize = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaticConfig {
+    pub cidr: Ipv4Cidr,
+    pub gateway: Option<Ipv4Address>,
+    pub dns_server: Option<Ipv4Address>,
+}
+
+/// Parse an address in x.x.x.x form
+pub fn parse_address(s: &str) -> Result<Ipv4Address, &'static str> {
+    let mut octets = [0; 4];
+    let mut count = 0;
+    for octet in s.split('.') {
+        if count == 4 || octet.is_empty() || octet.len() > 3 {
+            return Err("Ipv4Address Parse Failure");
+        }
+        octets[count] = octet
+            .parse::<u8>()
+            .map_err(|_| "Ipv4Address Parse Failure")?;
+        count += 1;
+    }
+    if count != 4 {
+        return Err("Ipv4Address Parse Failure");
+    }
+    Ok(Ipv4Address::from_bytes(&octets))
+}
+
+/// Parse an optional field, an empty field is not configured
+fn parse_optional(s: &str) -> Result<Option<Ipv4Address>, &'static str> {
+    if s.is_empty() {
+        Ok(None)
+    } else {
+        parse_address(s).map(Some)
+    }
+}
+
+/// Validate the input of the static configuration screen
+/// address is given as x.x.x.x/y, gateway and dns_server may be empty
+pub fn parse_static_config(
+    address: &str,
+    gateway: &str,
+    dns_server: &str,
+) -> Result<StaticConfig, &'static str> {
+    let pos = address
+        .find('/')
+        .ok_or("Address needs a prefix length, e.g. /24")?;
+    let addr = parse_address(&address[..pos])?;
+    let prefix_len = address[pos + 1..]
+        .parse::<u8>()
+        .map_err(|_| "Ipv4 Netmask Parse Failure")?;
+    if prefix_len > 32 {
+        return Err("Ipv4 Netmask too large");
+    }
+    if !addr.is_unicast() {
+        return Err("Address must be a unicast address");
+    }
+    let cidr = Ipv4Cidr::new(addr, prefix_len);
+    // /31 and /32 networks have no network and broadcast address
+    let host = NetworkEndian::read_u32(addr.as_bytes())
+        & !NetworkEndian::read_u32(cidr.netmask().as_bytes());
+    if prefix_len < 31 && (host == 0 || host == !0 >> prefix_len) {
+        return Err("Address is the network or broadcast address");
+    }
+
+    let gateway = parse_optional(gateway)?;
+    if let Some(gw) = gateway {
+        if gw == addr {
+            return Err("Gateway must differ from the address");
+        }
+        if !cidr.contains_addr(&gw) {
+            return Err("Gateway is not in the configured network");
+        }
+    }
+
+    let dns_server = parse_optional(dns_server)?;
+    if let Some(dns) = dns_server {
+        if !dns.is_unicast() {
+            return Err("DNS server must be a unicast address");
+        }
+    }
+
+    Ok(StaticConfig {
+        cidr,
+        gateway,
+        dns_server,
+    })
+}
+
+/// Set the address and default route of a static configuration, replacing the previous route
+pub fn apply<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    config: &StaticConfig,
+) -> Result<(), &'static str>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    super::set_ip4_address(iface, config.cidr.address(), config.cidr.prefix_len());
+    iface.routes_mut().update(|routes| {
+        routes.remove(&IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0));
+    });
+    if let Some(gw) = config.gateway {
+        iface
+            .routes_mut()
+            .add_default_ipv4_route(gw)
+            .map_err(|_| "Route table full")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_with_gateway_and_dns() {
+        let config = parse_static_config("10.0.0.5/24", "10.0.0.1", "9.9.9.9").unwrap();
+        assert_eq!(
+            config.cidr,
+            Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 5), 24)
+        );
+        assert_eq!(config.gateway, Some(Ipv4Address::new(10, 0, 0, 1)));
+        assert_eq!(config.dns_server, Some(Ipv4Address::new(9, 9, 9, 9)));
+        assert_eq!(
+            parse_static_config("10.0.0.5/24", "", "").unwrap().gateway,
+            None
+        );
+    }
+
+    #[test]
+    fn network_and_broadcast_addresses_are_rejected() {
+        assert!(parse_static_config("10.0.0.0/24", "", "").is_err());
+        assert!(parse_static_config("10.0.0.255/24", "", "").is_err());
+        assert!(parse_static_config("10.0.0.4/30", "", "").is_err());
+        assert!(parse_static_config("10.0.0.7/30", "", "").is_err());
+        assert!(parse_static_config("10.0.0.6/30", "10.0.0.5", "").is_ok());
+    }
+
+    #[test]
+    fn point_to_point_and_host_prefixes() {
+        // Both addresses of a /31 are usable (RFC 3021)
+        assert!(parse_static_config("10.0.0.4/31", "10.0.0.5", "").is_ok());
+        assert!(parse_static_config("10.0.0.5/31", "10.0.0.4", "").is_ok());
+        assert!(parse_static_config("10.0.0.5/32", "", "").is_ok());
+        // A /32 has no room for a gateway
+        assert!(parse_static_config("10.0.0.5/32", "10.0.0.1", "").is_err());
+        assert!(parse_static_config("10.0.0.5/33", "", "").is_err());
+    }
+
+    #[test]
+    fn gateway_must_be_in_the_network() {
+        assert!(parse_static_config("10.0.0.5/24", "10.0.1.1", "").is_err());
+        assert!(parse_static_config("10.0.0.5/24", "10.0.0.5", "").is_err());
+        assert!(parse_static_config("10.0.0.5/25", "10.0.0.129", "").is_err());
+        assert!(parse_static_config("10.0.0.5/16", "10.0.1.1", "").is_ok());
+    }
+}
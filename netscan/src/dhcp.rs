@@ -0,0 +1,226 @@
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::wire::*;
+
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+const DHCP_MAGIC_COOKIE: u32 = 0x6382_5363;
+const BOOTP_HEADER_LEN: usize = 236;
+const BOOTP_MIN_LEN: usize = 300;
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DOMAIN_NAME_SERVER: u8 = 6;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_IDENTIFIER: u8 = 54;
+const OPT_PARAMETER_REQUEST_LIST: u8 = 55;
+const OPT_CLIENT_ID: u8 = 61;
+const OPT_END: u8 = 255;
+
+#[derive(Debug)]
+pub struct DhcpOffer {
+    pub server: Ipv4Address,
+    pub mac: EthernetAddress,
+    pub offered: Ipv4Address,
+    pub router: Option<Ipv4Address>,
+    pub dns_servers: Vec<Ipv4Address>,
+    pub lease_time: Option<u32>,
+}
+pub type DhcpOffers = Vec<DhcpOffer>;
+
+/// Offers of the latest audit and the servers that are supposed to answer on the audited network.
+/// Every responding server not on the allowlist is flagged as rogue.
+#[derive(Debug, Default)]
+pub struct DhcpAudit {
+    pub offers: DhcpOffers,
+    pub allowlist: BTreeSet<Ipv4Address>,
+    /// Address leased by our own DHCP client, its server is labeled but not trusted
+    pub lease: Option<Ipv4Address>,
+}
+
+impl DhcpAudit {
+    /// Whether the server of offer is on the allowlist
+    pub fn is_allowed(&self, offer: &DhcpOffer) -> bool {
+        self.allowlist.contains(&offer.server)
+    }
+
+    /// Whether offer is from the server of our lease, servers offer a client its current lease again
+    pub fn is_lease_source(&self, offer: &DhcpOffer) -> bool {
+        self.lease == Some(offer.offered)
+    }
+
+    /// Add server to the allowlist or remove it if it already is on it
+    pub fn toggle(&mut self, server: Ipv4Address) {
+        if !self.allowlist.remove(&server) {
+            self.allowlist.insert(server);
+        }
+    }
+}
+
+impl super::StringableVec for DhcpAudit {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.offers.iter() {
+            let mut line = format!("{} ({})", i.server, i.mac);
+            if !self.is_allowed(i) {
+                line.push_str(" ROGUE");
+            }
+            if self.is_lease_source(i) {
+                line.push_str(" lease source");
+            }
+            ret.push(line);
+            ret.push(format!("    Offer: {}", i.offered));
+            if let Some(router) = i.router {
+                ret.push(format!("    Router: {}", router));
+            }
+            for dns_server in i.dns_servers.iter() {
+                ret.push(format!("    DNS: {}", dns_server));
+            }
+            if let Some(lease_time) = i.lease_time {
+                ret.push(format!("    Lease: {} s", lease_time));
+            }
+            ret.push(String::from(""));
+        }
+        ret.push(String::from("Tap a server to trust it or not"));
+        ret
+    }
+}
+
+/// Build the BOOTP payload of a DHCPDISCOVER with the broadcast flag set
+pub fn emit_discover(xid: u32, eth_addr: EthernetAddress) -> Vec<u8> {
+    let mut buffer = vec![0; BOOTP_HEADER_LEN + 4];
+    buffer[0] = BOOTREQUEST;
+    buffer[1] = 1; // Hardware type: Ethernet
+    buffer[2] = 6; // Hardware address length
+    NetworkEndian::write_u32(&mut buffer[4..8], xid);
+    NetworkEndian::write_u16(&mut buffer[10..12], 0x8000);
+    buffer[28..34].copy_from_slice(eth_addr.as_bytes());
+    NetworkEndian::write_u32(&mut buffer[BOOTP_HEADER_LEN..], DHCP_MAGIC_COOKIE);
+
+    buffer.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, DHCPDISCOVER]);
+    buffer.extend_from_slice(&[OPT_CLIENT_ID, 7, 1]);
+    buffer.extend_from_slice(eth_addr.as_bytes());
+    buffer.extend_from_slice(&[
+        OPT_PARAMETER_REQUEST_LIST,
+        4,
+        OPT_SUBNET_MASK,
+        OPT_ROUTER,
+        OPT_DOMAIN_NAME_SERVER,
+        OPT_LEASE_TIME,
+    ]);
+    buffer.push(OPT_END);
+
+    // Some servers ignore requests shorter than a BOOTP message
+    if buffer.len() < BOOTP_MIN_LEN {
+        buffer.resize(BOOTP_MIN_LEN, OPT_PAD);
+    }
+    buffer
+}
+
+/// Parse the BOOTP payload of a DHCPOFFER for transaction xid
+/// mac and src are the link and network layer source of the offer, src is used as server
+/// identifier if the offer does not contain one
+pub fn parse_offer(
+    payload: &[u8],
+    xid: u32,
+    mac: EthernetAddress,
+    src: Ipv4Address,
+) -> Option<DhcpOffer> {
+    if payload.len() < BOOTP_HEADER_LEN + 4
+        || payload[0] != BOOTREPLY
+        || NetworkEndian::read_u32(&payload[4..8]) != xid
+        || NetworkEndian::read_u32(&payload[BOOTP_HEADER_LEN..BOOTP_HEADER_LEN + 4])
+            != DHCP_MAGIC_COOKIE
+    {
+        return None;
+    }
+
+    let mut offer = DhcpOffer {
+        server: src,
+        mac,
+        offered: Ipv4Address::from_bytes(&payload[16..20]),
+        router: None,
+        dns_servers: Vec::new(),
+        lease_time: None,
+    };
+    let mut message_type = None;
+
+    let mut options = &payload[BOOTP_HEADER_LEN + 4..];
+    while let Some((&kind, rest)) = options.split_first() {
+        match kind {
+            OPT_PAD => {
+                options = rest;
+                continue;
+            }
+            OPT_END => break,
+            _ => {}
+        }
+        let (&len, rest) = rest.split_first()?;
+        if rest.len() < len as usize {
+            return None;
+        }
+        let (data, rest) = rest.split_at(len as usize);
+        match kind {
+            OPT_MESSAGE_TYPE if len == 1 => message_type = Some(data[0]),
+            OPT_SERVER_IDENTIFIER if len == 4 => offer.server = Ipv4Address::from_bytes(data),
+            OPT_ROUTER if len >= 4 => offer.router = Some(Ipv4Address::from_bytes(&data[..4])),
+            OPT_DOMAIN_NAME_SERVER => {
+                for chunk in data.chunks_exact(4) {
+                    offer.dns_servers.push(Ipv4Address::from_bytes(chunk));
+                }
+            }
+            OPT_LEASE_TIME if len == 4 => offer.lease_time = Some(NetworkEndian::read_u32(data)),
+            _ => {}
+        }
+        options = rest;
+    }
+
+    if message_type == Some(DHCPOFFER) {
+        Some(offer)
+    } else {
+        None
+    }
+}
+
+/// Parse a received frame and return the DHCPOFFER it contains
+pub fn process_offer<T: AsRef<[u8]>>(
+    eth_addr: EthernetAddress,
+    xid: u32,
+    frame: &T,
+) -> Result<DhcpOffer, smoltcp::Error> {
+    let eth_frame = EthernetFrame::new_checked(frame)?;
+
+    // Offers are either broadcast or sent to our hardware address
+    if !eth_frame.dst_addr().is_broadcast() && eth_frame.dst_addr() != eth_addr {
+        return Err(smoltcp::Error::Dropped);
+    }
+    if eth_frame.ethertype() != EthernetProtocol::Ipv4 {
+        return Err(smoltcp::Error::Unrecognized);
+    }
+
+    let ipv4_packet = Ipv4Packet::new_checked(eth_frame.payload())?;
+    if ipv4_packet.protocol() != IpProtocol::Udp {
+        return Err(smoltcp::Error::Unrecognized);
+    }
+    let udp_packet = UdpPacket::new_checked(ipv4_packet.payload())?;
+    if udp_packet.src_port() != DHCP_SERVER_PORT || udp_packet.dst_port() != DHCP_CLIENT_PORT {
+        return Err(smoltcp::Error::Unrecognized);
+    }
+
+    parse_offer(
+        udp_packet.payload(),
+        xid,
+        eth_frame.src_addr(),
+        ipv4_packet.src_addr(),
+    )
+    .ok_or(smoltcp::Error::Unrecognized)
+}
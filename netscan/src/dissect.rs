@@ -0,0 +1,716 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt::Display;
+use smoltcp::time::Instant;
+use smoltcp::wire::*;
+use smoltcp::Error;
+
+use super::dns;
+use super::ipv6::{self, NdpMessage};
+use super::Stringable;
+
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const MDNS_PORT: u16 = 5353;
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const NTP_PORT: u16 = 123;
+
+const BOOTP_HEADER_LEN: usize = 236;
+const DHCP_MAGIC_COOKIE: u32 = 0x6382_5363;
+const OPT_PAD: u8 = 0;
+const OPT_HOST_NAME: u8 = 12;
+const OPT_REQUESTED_ADDRESS: u8 = 50;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_IDENTIFIER: u8 = 54;
+const OPT_END: u8 = 255;
+
+const NTP_LEN: usize = 48;
+/// Seconds between the NTP epoch in 1900 and the unix epoch
+const NTP_UNIX_OFFSET: u32 = 2_208_988_800;
+
+/// Number of summary lines kept by the live view
+const LIVE_LINES: usize = 30;
+/// Bytes of raw frames kept to dissect them again when tapped, the oldest are dropped first
+const LIVE_FRAME_BUDGET: usize = 8 * 1024;
+
+/// A decoded field, children are shown indented below their parent
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub label: String,
+    pub children: Vec<Field>,
+}
+
+impl Field {
+    fn new(label: String) -> Self {
+        Field {
+            label,
+            children: Vec::new(),
+        }
+    }
+
+    fn node(label: String, children: Vec<Field>) -> Self {
+        Field { label, children }
+    }
+
+    fn leaf<T: Display>(name: &str, value: T) -> Self {
+        Field::new(format!("{}: {}", name, value))
+    }
+
+    fn push_lines(&self, depth: usize, lines: &mut Vec<String>) {
+        let mut line = String::new();
+        for _ in 0..depth {
+            line.push_str("  ");
+        }
+        line.push_str(&self.label);
+        lines.push(line);
+        for child in self.children.iter() {
+            child.push_lines(depth + 1, lines);
+        }
+    }
+}
+
+/// A frame decoded into a summary line and a field tree with one entry per layer
+#[derive(Debug, Clone)]
+pub struct Dissection {
+    pub timestamp: Instant,
+    pub summary: String,
+    pub layers: Vec<Field>,
+}
+
+impl Dissection {
+    /// Summary line prefixed by the time of reception
+    pub fn summary_line(&self) -> String {
+        summary_line(self.timestamp, &self.summary)
+    }
+
+    /// The field tree as indented lines
+    pub fn tree_lines(&self) -> Vec<String> {
+        let mut lines = vec![self.summary_line()];
+        for layer in self.layers.iter() {
+            layer.push_lines(0, &mut lines);
+        }
+        lines
+    }
+}
+
+fn summary_line(timestamp: Instant, summary: &str) -> String {
+    let millis = timestamp.total_millis();
+    format!("{}.{:03} {}", millis / 1000, millis % 1000, summary)
+}
+
+/// A frame shown by the live view, only its summary is kept once the frame is dropped
+#[derive(Debug)]
+pub struct LiveFrame {
+    pub timestamp: Instant,
+    pub summary: String,
+    pub data: Option<Vec<u8>>,
+}
+
+/// The most recently received frames, oldest first
+#[derive(Debug, Default)]
+pub struct LiveView {
+    pub frames: VecDeque<LiveFrame>,
+    frame_bytes: usize,
+}
+
+impl LiveView {
+    pub fn add(&mut self, frame: &[u8], timestamp: Instant) {
+        if self.frames.len() >= LIVE_LINES {
+            if let Some(data) = self.frames.pop_front().and_then(|x| x.data) {
+                self.frame_bytes -= data.len();
+            }
+        }
+        self.frames.push_back(LiveFrame {
+            timestamp,
+            summary: dissect(frame, timestamp).summary,
+            data: Some(Vec::from(frame)),
+        });
+        self.frame_bytes += frame.len();
+        //The latest frame is always kept, the budget is larger than a frame
+        for old in self.frames.iter_mut() {
+            if self.frame_bytes <= LIVE_FRAME_BUDGET {
+                break;
+            }
+            if let Some(data) = old.data.take() {
+                self.frame_bytes -= data.len();
+            }
+        }
+    }
+
+    /// The field tree of the frame at index, dissected again from the kept frame
+    pub fn tree_lines(&self, index: usize) -> Option<Vec<String>> {
+        let frame = self.frames.get(index)?;
+        Some(match frame.data {
+            Some(ref data) => dissect(data, frame.timestamp).tree_lines(),
+            None => vec![
+                summary_line(frame.timestamp, &frame.summary),
+                String::from("The frame is no longer kept"),
+            ],
+        })
+    }
+
+    pub fn latest_tree_lines(&self) -> Option<Vec<String>> {
+        self.frames
+            .len()
+            .checked_sub(1)
+            .and_then(|x| self.tree_lines(x))
+    }
+}
+
+/// One summary line per frame, in the order of the frames
+impl super::StringableVec for LiveView {
+    fn to_string_vec(&self) -> Vec<String> {
+        self.frames
+            .iter()
+            .map(|x| summary_line(x.timestamp, &x.summary))
+            .collect()
+    }
+}
+
+/// Decode as many layers of an ethernet frame as possible
+pub fn dissect(frame: &[u8], timestamp: Instant) -> Dissection {
+    let mut dissection = Dissection {
+        timestamp,
+        summary: String::new(),
+        layers: Vec::new(),
+    };
+    if let Err(x) = dissect_ethernet(frame, &mut dissection) {
+        dissection
+            .layers
+            .push(Field::new(format!("Malformed: {}", x)));
+        if dissection.summary.is_empty() {
+            dissection.summary = format!("Malformed frame of {} bytes", frame.len());
+        }
+    }
+    dissection
+}
+
+fn dissect_ethernet(frame: &[u8], d: &mut Dissection) -> Result<(), Error> {
+    let eth_frame = EthernetFrame::new_checked(frame)?;
+    let (src, dst) = (eth_frame.src_addr(), eth_frame.dst_addr());
+    let mut ethertype = eth_frame.ethertype();
+    let mut payload = eth_frame.payload();
+
+    d.layers.push(Field::node(
+        format!("Ethernet {} > {}", src, dst),
+        vec![
+            Field::leaf("Source", src),
+            Field::leaf("Destination", dst),
+            Field::leaf("Type", ethertype),
+            Field::leaf("Length", frame.len()),
+        ],
+    ));
+    d.summary = format!("{} > {} {}", src, dst, ethertype);
+
+    if u16::from(ethertype) == ETHERTYPE_VLAN {
+        if payload.len() < 4 {
+            return Err(Error::Truncated);
+        }
+        let tci = NetworkEndian::read_u16(&payload[0..2]);
+        let id = tci & 0x0fff;
+        ethertype = EthernetProtocol::from(NetworkEndian::read_u16(&payload[2..4]));
+        payload = &payload[4..];
+
+        d.layers.push(Field::node(
+            format!("802.1Q VLAN {}", id),
+            vec![
+                Field::leaf("Priority", tci >> 13),
+                Field::leaf("DEI", (tci >> 12) & 1),
+                Field::leaf("VLAN", id),
+                Field::leaf("Type", ethertype),
+            ],
+        ));
+        d.summary = format!("VLAN {} {}", id, ethertype);
+    }
+
+    match ethertype {
+        EthernetProtocol::Arp => dissect_arp(payload, d),
+        EthernetProtocol::Ipv4 => dissect_ipv4(payload, d),
+        EthernetProtocol::Ipv6 => dissect_ipv6(payload, d),
+        _ => Ok(()),
+    }
+}
+
+fn dissect_arp(payload: &[u8], d: &mut Dissection) -> Result<(), Error> {
+    let packet = ArpPacket::new_checked(payload)?;
+    if let ArpRepr::EthernetIpv4 {
+        operation,
+        source_hardware_addr,
+        source_protocol_addr,
+        target_hardware_addr,
+        target_protocol_addr,
+    } = ArpRepr::parse(&packet)?
+    {
+        d.summary = match operation {
+            ArpOperation::Request => format!(
+                "ARP who-has {} tell {}",
+                target_protocol_addr, source_protocol_addr
+            ),
+            ArpOperation::Reply => format!(
+                "ARP {} is-at {}",
+                source_protocol_addr, source_hardware_addr
+            ),
+            x => format!("ARP {:?}", x),
+        };
+        d.layers.push(Field::node(
+            format!("ARP {:?}", operation),
+            vec![
+                Field::leaf("Sender MAC", source_hardware_addr),
+                Field::leaf("Sender IP", source_protocol_addr),
+                Field::leaf("Target MAC", target_hardware_addr),
+                Field::leaf("Target IP", target_protocol_addr),
+            ],
+        ));
+    }
+    Ok(())
+}
+
+fn dissect_ipv4(payload: &[u8], d: &mut Dissection) -> Result<(), Error> {
+    let packet = Ipv4Packet::new_checked(payload)?;
+    let (src, dst) = (packet.src_addr(), packet.dst_addr());
+    let mut flags = Vec::new();
+    if packet.dont_frag() {
+        flags.push("DF");
+    }
+    if packet.more_frags() {
+        flags.push("MF");
+    }
+    let checksum = if packet.verify_checksum() {
+        "valid"
+    } else {
+        "invalid"
+    };
+
+    d.layers.push(Field::node(
+        format!("IPv4 {} > {}", src, dst),
+        vec![
+            Field::leaf("Source", src),
+            Field::leaf("Destination", dst),
+            Field::leaf("Header length", packet.header_len()),
+            Field::leaf("Total length", packet.total_len()),
+            Field::leaf("Identification", format!("0x{:04x}", packet.ident())),
+            Field::leaf("Flags", flags.join(" ")),
+            Field::leaf("Fragment offset", packet.frag_offset()),
+            Field::leaf("TTL", packet.hop_limit()),
+            Field::leaf("Protocol", packet.protocol()),
+            Field::leaf(
+                "Checksum",
+                format!("0x{:04x} ({})", packet.checksum(), checksum),
+            ),
+        ],
+    ));
+    d.summary = format!("{} > {} {}", src, dst, packet.protocol());
+
+    // Only the first fragment carries the transport header
+    if packet.frag_offset() != 0 {
+        return Ok(());
+    }
+    let (src, dst) = (IpAddress::Ipv4(src), IpAddress::Ipv4(dst));
+    match packet.protocol() {
+        IpProtocol::Icmp => dissect_icmpv4(packet.payload(), src, dst, d),
+        IpProtocol::Tcp => dissect_tcp(packet.payload(), src, dst, d),
+        IpProtocol::Udp => dissect_udp(packet.payload(), src, dst, d),
+        _ => Ok(()),
+    }
+}
+
+fn dissect_ipv6(payload: &[u8], d: &mut Dissection) -> Result<(), Error> {
+    let packet = Ipv6Packet::new_checked(payload)?;
+    let (src, dst) = (packet.src_addr(), packet.dst_addr());
+
+    d.layers.push(Field::node(
+        format!("IPv6 {} > {}", src, dst),
+        vec![
+            Field::leaf("Source", src),
+            Field::leaf("Destination", dst),
+            Field::leaf("Traffic class", packet.traffic_class()),
+            Field::leaf("Flow label", packet.flow_label()),
+            Field::leaf("Payload length", packet.payload_len()),
+            Field::leaf("Next header", packet.next_header()),
+            Field::leaf("Hop limit", packet.hop_limit()),
+        ],
+    ));
+    d.summary = format!("{} > {} {}", src, dst, packet.next_header());
+
+    let upper = ipv6::upper_layer(packet.next_header(), packet.payload())?;
+    for extension in upper.extensions.iter() {
+        d.layers
+            .push(Field::new(format!("Extension header {}", extension)));
+    }
+    let payload = match upper.payload {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let (src, dst) = (IpAddress::Ipv6(src), IpAddress::Ipv6(dst));
+    match upper.protocol {
+        IpProtocol::Icmpv6 => dissect_icmpv6(payload, src, dst, d),
+        IpProtocol::Tcp => dissect_tcp(payload, src, dst, d),
+        IpProtocol::Udp => dissect_udp(payload, src, dst, d),
+        _ => Ok(()),
+    }
+}
+
+fn dissect_icmpv4(
+    payload: &[u8],
+    src: IpAddress,
+    dst: IpAddress,
+    d: &mut Dissection,
+) -> Result<(), Error> {
+    let packet = Icmpv4Packet::new_checked(payload)?;
+    let mut children = vec![
+        Field::leaf("Type", packet.msg_type()),
+        Field::leaf("Code", packet.msg_code()),
+    ];
+    d.summary = format!("{} > {} ICMP {}", src, dst, packet.msg_type());
+    match packet.msg_type() {
+        Icmpv4Message::EchoRequest | Icmpv4Message::EchoReply => {
+            children.push(Field::leaf("Identifier", packet.echo_ident()));
+            children.push(Field::leaf("Sequence", packet.echo_seq_no()));
+            d.summary += &format!(" seq {}", packet.echo_seq_no());
+        }
+        _ => {}
+    }
+    d.layers
+        .push(Field::node(format!("ICMP {}", packet.msg_type()), children));
+    Ok(())
+}
+
+fn dissect_icmpv6(
+    payload: &[u8],
+    src: IpAddress,
+    dst: IpAddress,
+    d: &mut Dissection,
+) -> Result<(), Error> {
+    let packet = Icmpv6Packet::new_checked(payload)?;
+    let mut children = vec![
+        Field::leaf("Type", packet.msg_type()),
+        Field::leaf("Code", packet.msg_code()),
+    ];
+    d.summary = format!("{} > {} ICMPv6 {}", src, dst, packet.msg_type());
+    match packet.msg_type() {
+        Icmpv6Message::EchoRequest | Icmpv6Message::EchoReply => {
+            children.push(Field::leaf("Identifier", packet.echo_ident()));
+            children.push(Field::leaf("Sequence", packet.echo_seq_no()));
+            d.summary += &format!(" seq {}", packet.echo_seq_no());
+        }
+        _ => {}
+    }
+    if let Some(message) = ipv6::parse_ndp(payload) {
+        d.summary = format!("{} > {} NDP {}", src, dst, ndp_summary(&message));
+        children.push(Field::leaf("NDP", ndp_summary(&message)));
+    }
+    d.layers.push(Field::node(
+        format!("ICMPv6 {}", packet.msg_type()),
+        children,
+    ));
+    Ok(())
+}
+
+fn ndp_summary(message: &NdpMessage) -> String {
+    let lladdr = |x: Option<EthernetAddress>| match x {
+        Some(x) => format!(" {}", x),
+        None => String::new(),
+    };
+    match *message {
+        NdpMessage::RouterSolicit { lladdr: x } => format!("router solicitation{}", lladdr(x)),
+        NdpMessage::RouterAdvert {
+            lladdr: x,
+            router_lifetime,
+        } => format!(
+            "router advertisement{}, lifetime {}s",
+            lladdr(x),
+            router_lifetime
+        ),
+        NdpMessage::NeighborSolicit { target, lladdr: x } => {
+            format!("who-has {}{}", target, lladdr(x))
+        }
+        NdpMessage::NeighborAdvert {
+            target,
+            lladdr: x,
+            router,
+            ..
+        } => format!(
+            "{} is-at{}{}",
+            target,
+            lladdr(x),
+            if router { " (router)" } else { "" }
+        ),
+        NdpMessage::Redirect {
+            target,
+            destination,
+        } => format!("redirect {} to {}", destination, target),
+    }
+}
+
+/// Format the set TCP flags like tcpdump
+fn tcp_flags<T: AsRef<[u8]>>(packet: &TcpPacket<T>) -> String {
+    let all = [
+        (packet.syn(), 'S'),
+        (packet.fin(), 'F'),
+        (packet.rst(), 'R'),
+        (packet.psh(), 'P'),
+        (packet.ack(), '.'),
+        (packet.urg(), 'U'),
+    ];
+    all.iter().filter(|x| x.0).map(|x| x.1).collect()
+}
+
+fn dissect_tcp(
+    payload: &[u8],
+    src: IpAddress,
+    dst: IpAddress,
+    d: &mut Dissection,
+) -> Result<(), Error> {
+    let packet = TcpPacket::new_checked(payload)?;
+    let (src_port, dst_port) = (packet.src_port(), packet.dst_port());
+    let flags = tcp_flags(&packet);
+    let len = packet.payload().len();
+
+    d.layers.push(Field::node(
+        format!("TCP {} > {} [{}]", src_port, dst_port, flags),
+        vec![
+            Field::leaf("Source port", src_port),
+            Field::leaf("Destination port", dst_port),
+            Field::leaf("Sequence", packet.seq_number().0 as u32),
+            Field::leaf("Acknowledgment", packet.ack_number().0 as u32),
+            Field::leaf("Flags", &flags),
+            Field::leaf("Window", packet.window_len()),
+            Field::leaf("Payload length", len),
+        ],
+    ));
+    d.summary = format!(
+        "{}:{} > {}:{} TCP [{}] len {}",
+        src, src_port, dst, dst_port, flags, len
+    );
+    Ok(())
+}
+
+fn dissect_udp(
+    payload: &[u8],
+    src: IpAddress,
+    dst: IpAddress,
+    d: &mut Dissection,
+) -> Result<(), Error> {
+    let packet = UdpPacket::new_checked(payload)?;
+    let (src_port, dst_port) = (packet.src_port(), packet.dst_port());
+
+    d.layers.push(Field::node(
+        format!("UDP {} > {}", src_port, dst_port),
+        vec![
+            Field::leaf("Source port", src_port),
+            Field::leaf("Destination port", dst_port),
+            Field::leaf("Length", packet.len()),
+        ],
+    ));
+    d.summary = format!(
+        "{}:{} > {}:{} UDP len {}",
+        src,
+        src_port,
+        dst,
+        dst_port,
+        packet.payload().len()
+    );
+
+    let ports = [src_port, dst_port];
+    if ports.contains(&dns::DNS_PORT) || ports.contains(&MDNS_PORT) {
+        dissect_dns(packet.payload(), d);
+    } else if ports.contains(&DHCP_SERVER_PORT) || ports.contains(&DHCP_CLIENT_PORT) {
+        dissect_dhcp(packet.payload(), d);
+    } else if ports.contains(&NTP_PORT) {
+        dissect_ntp(packet.payload(), d);
+    }
+    Ok(())
+}
+
+/// Application layers that fail to parse are only noted in the field tree
+fn dissect_dns(payload: &[u8], d: &mut Dissection) {
+    let msg = match dns::parse_message(payload) {
+        Ok(x) => x,
+        Err(x) => {
+            d.layers.push(Field::new(format!("DNS: {}", x)));
+            return;
+        }
+    };
+    let kind = if msg.is_response() {
+        "response"
+    } else {
+        "query"
+    };
+
+    let mut children = vec![
+        Field::leaf("ID", msg.id),
+        Field::leaf("Flags", format!("0x{:04x}", msg.flags)),
+    ];
+    for q in msg.questions.iter() {
+        let rtype = dns::type_name(q.qtype);
+        children.push(Field::new(format!("Question: {} {}", rtype, q.name)));
+    }
+    for a in msg.answers.iter() {
+        let rtype = dns::type_name(a.rtype);
+        let data = a.data.to_string();
+        children.push(Field::new(format!("Answer: {} {} {}", a.name, rtype, data)));
+    }
+    d.layers
+        .push(Field::node(format!("DNS {}", kind), children));
+
+    d.summary = match msg.questions.first() {
+        Some(q) => {
+            let mut summary = format!("DNS {} {} {}", kind, dns::type_name(q.qtype), q.name);
+            if let Some(a) = msg.answers.first() {
+                summary += &format!(" {}", a.data.to_string());
+            } else if msg.is_response() && msg.rcode() == dns::RCODE_NAME_ERROR {
+                summary += " NXDOMAIN";
+            }
+            summary
+        }
+        None => format!("DNS {} id {}", kind, msg.id),
+    };
+}
+
+fn dhcp_message_name(message_type: u8) -> String {
+    match message_type {
+        1 => String::from("Discover"),
+        2 => String::from("Offer"),
+        3 => String::from("Request"),
+        4 => String::from("Decline"),
+        5 => String::from("Ack"),
+        6 => String::from("Nak"),
+        7 => String::from("Release"),
+        8 => String::from("Inform"),
+        x => format!("type {}", x),
+    }
+}
+
+fn dissect_dhcp(payload: &[u8], d: &mut Dissection) {
+    if payload.len() < BOOTP_HEADER_LEN + 4
+        || NetworkEndian::read_u32(&payload[BOOTP_HEADER_LEN..BOOTP_HEADER_LEN + 4])
+            != DHCP_MAGIC_COOKIE
+    {
+        d.layers.push(Field::new(String::from("DHCP: truncated")));
+        return;
+    }
+    let operation = if payload[0] == 1 { "request" } else { "reply" };
+    let xid = NetworkEndian::read_u32(&payload[4..8]);
+    let client = EthernetAddress::from_bytes(&payload[28..34]);
+    let mut children = vec![
+        Field::leaf("Operation", operation),
+        Field::leaf("Transaction", format!("0x{:08x}", xid)),
+        Field::leaf("Client address", Ipv4Address::from_bytes(&payload[12..16])),
+        Field::leaf("Your address", Ipv4Address::from_bytes(&payload[16..20])),
+        Field::leaf("Client MAC", client),
+    ];
+
+    let mut message_type = None;
+    let mut options = &payload[BOOTP_HEADER_LEN + 4..];
+    while let Some((&kind, rest)) = options.split_first() {
+        match kind {
+            OPT_PAD => {
+                options = rest;
+                continue;
+            }
+            OPT_END => break,
+            _ => {}
+        }
+        let (&len, rest) = match rest.split_first() {
+            Some(x) => x,
+            None => break,
+        };
+        if rest.len() < len as usize {
+            break;
+        }
+        let (data, rest) = rest.split_at(len as usize);
+        let field = match kind {
+            OPT_MESSAGE_TYPE if len == 1 => {
+                message_type = Some(data[0]);
+                Field::leaf("Message type", dhcp_message_name(data[0]))
+            }
+            OPT_HOST_NAME => Field::leaf("Host name", super::printable(data)),
+            OPT_REQUESTED_ADDRESS if len == 4 => {
+                Field::leaf("Requested address", Ipv4Address::from_bytes(data))
+            }
+            OPT_SERVER_IDENTIFIER if len == 4 => {
+                Field::leaf("Server", Ipv4Address::from_bytes(data))
+            }
+            _ => Field::new(format!("Option {}, {} bytes", kind, len)),
+        };
+        children.push(field);
+        options = rest;
+    }
+
+    let name = match message_type {
+        Some(x) => format!("DHCP {}", dhcp_message_name(x)),
+        None => String::from("BOOTP"),
+    };
+    d.summary = format!("{} from {} xid 0x{:08x}", name, client, xid);
+    d.layers.push(Field::node(name, children));
+}
+
+fn dissect_ntp(payload: &[u8], d: &mut Dissection) {
+    if payload.len() < NTP_LEN {
+        d.layers.push(Field::new(String::from("NTP: truncated")));
+        return;
+    }
+    let version = (payload[0] >> 3) & 0x07;
+    let mode = match payload[0] & 0x07 {
+        1 => "symmetric active",
+        2 => "symmetric passive",
+        3 => "client",
+        4 => "server",
+        5 => "broadcast",
+        6 => "control",
+        7 => "private",
+        _ => "reserved",
+    };
+    let transmit = NetworkEndian::read_u32(&payload[40..44]);
+    let unix = transmit.wrapping_sub(NTP_UNIX_OFFSET);
+
+    d.layers.push(Field::node(
+        format!("NTP v{} {}", version, mode),
+        vec![
+            Field::leaf("Leap indicator", payload[0] >> 6),
+            Field::leaf("Version", version),
+            Field::leaf("Mode", mode),
+            Field::leaf("Stratum", payload[1]),
+            Field::leaf("Poll", payload[2] as i8),
+            Field::leaf("Precision", payload[3] as i8),
+            Field::leaf("Transmit time", format!("{} (unix {})", transmit, unix)),
+        ],
+    ));
+    d.summary = format!("NTP v{} {} stratum {}", version, mode, payload[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StringableVec;
+
+    #[test]
+    fn live_view_keeps_summaries_within_budget() {
+        let mut live = LiveView::default();
+        let frame = [0u8; 1514];
+        for i in 0..(LIVE_LINES as i64 + 5) {
+            live.add(&frame, Instant::from_millis(i));
+        }
+        assert_eq!(live.to_string_vec().len(), LIVE_LINES);
+        let kept: usize = live
+            .frames
+            .iter()
+            .filter_map(|x| x.data.as_ref())
+            .map(|x| x.len())
+            .sum();
+        assert!(kept <= LIVE_FRAME_BUDGET);
+        assert!(live.frames.back().unwrap().data.is_some());
+        assert_eq!(
+            live.tree_lines(0).unwrap()[1],
+            "The frame is no longer kept"
+        );
+        let latest = live.latest_tree_lines().unwrap();
+        assert_eq!(
+            latest,
+            dissect(&frame, Instant::from_millis(34)).tree_lines()
+        );
+        assert!(live.tree_lines(LIVE_LINES).is_none());
+    }
+}
@@ -0,0 +1,615 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, Ipv4Address, Ipv6Address};
+
+use super::{HostNames, Stringable};
+
+pub const DNS_PORT: u16 = 53;
+const HEADER_LEN: usize = 12;
+const MAX_NAME_LEN: usize = 255;
+const MAX_POINTERS: usize = 16;
+/// Longest scan target that fits the input field
+pub const MAX_TARGET_LEN: usize = 40;
+
+pub const FLAG_RESPONSE: u16 = 0x8000;
+pub const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+pub const CLASS_IN: u16 = 1;
+
+pub const RCODE_NO_ERROR: u8 = 0;
+pub const RCODE_NAME_ERROR: u8 = 3;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_SOA: u16 = 6;
+pub const TYPE_PTR: u16 = 12;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_SRV: u16 = 33;
+
+/// Time to remember that a lookup failed
+pub const NEGATIVE_TTL: u32 = 300;
+const MIN_TTL: u32 = 60;
+pub const MAX_TTL: u32 = 86400;
+/// Maximum number of CNAME records followed for a single lookup
+pub const MAX_CNAME_DEPTH: usize = 8;
+
+#[derive(Debug)]
+pub struct Question {
+    pub name: String,
+    pub qtype: u16,
+}
+
+#[derive(Debug)]
+pub enum RecordData {
+    A(Ipv4Address),
+    Aaaa(Ipv6Address),
+    /// Target of a CNAME or PTR record
+    Name(String),
+    /// Negative caching ttl of a SOA record
+    Soa(u32),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Txt(Vec<String>),
+    Other,
+}
+
+impl Stringable for RecordData {
+    fn to_string(&self) -> String {
+        match self {
+            RecordData::A(addr) => format!("{}", addr),
+            RecordData::Aaaa(addr) => format!("{}", addr),
+            RecordData::Name(name) => name.clone(),
+            RecordData::Soa(ttl) => format!("negative ttl {}", ttl),
+            RecordData::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => format!("{} {} {} {}", priority, weight, port, target),
+            RecordData::Txt(strings) => strings.join(" "),
+            RecordData::Other => String::new(),
+        }
+    }
+}
+
+/// Return the mnemonic of a record type
+pub fn type_name(rtype: u16) -> String {
+    match rtype {
+        TYPE_A => String::from("A"),
+        TYPE_CNAME => String::from("CNAME"),
+        TYPE_SOA => String::from("SOA"),
+        TYPE_PTR => String::from("PTR"),
+        TYPE_TXT => String::from("TXT"),
+        TYPE_AAAA => String::from("AAAA"),
+        TYPE_SRV => String::from("SRV"),
+        x => format!("TYPE{}", x),
+    }
+}
+
+#[derive(Debug)]
+pub struct Record {
+    pub name: String,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+#[derive(Debug)]
+pub struct Message {
+    pub id: u16,
+    pub flags: u16,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Record>,
+    pub authorities: Vec<Record>,
+    pub additionals: Vec<Record>,
+}
+
+impl Message {
+    pub fn is_response(&self) -> bool {
+        self.flags & FLAG_RESPONSE != 0
+    }
+
+    pub fn rcode(&self) -> u8 {
+        (self.flags & 0x000f) as u8
+    }
+
+    /// Whether the message responds to the query for name built by emit_query with id
+    /// The question has to be echoed as well, the id alone is easily guessed
+    pub fn answers(&self, id: u16, name: &str, qtype: u16) -> bool {
+        self.is_response()
+            && self.id == id
+            && self.questions.len() == 1
+            && self.questions[0].qtype == qtype
+            && self.questions[0]
+                .name
+                .eq_ignore_ascii_case(name.trim_end_matches('.'))
+    }
+}
+
+struct CacheEntry<T> {
+    value: Option<T>,
+    expires: Instant,
+}
+
+/// Cache of resolved names including failed lookups
+#[derive(Default)]
+pub struct DnsCache {
+    ptr: BTreeMap<Ipv4Address, CacheEntry<String>>,
+    hosts: BTreeMap<String, CacheEntry<Vec<IpAddress>>>,
+}
+
+impl DnsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the cached PTR lookup result for addr unless it has expired
+    /// The inner option is None for a cached failure
+    pub fn lookup_ptr(&self, addr: Ipv4Address, timestamp: Instant) -> Option<Option<&String>> {
+        self.ptr
+            .get(&addr)
+            .filter(|x| x.expires > timestamp)
+            .map(|x| x.value.as_ref())
+    }
+
+    pub fn insert_ptr(
+        &mut self,
+        addr: Ipv4Address,
+        name: Option<String>,
+        ttl: u32,
+        timestamp: Instant,
+    ) {
+        self.ptr.insert(
+            addr,
+            CacheEntry {
+                value: name,
+                expires: timestamp + ttl_duration(ttl),
+            },
+        );
+    }
+
+    /// Return the cached addresses of name unless they have expired
+    /// The inner option is None for a cached failure
+    pub fn lookup_host(&self, name: &str, timestamp: Instant) -> Option<Option<&Vec<IpAddress>>> {
+        self.hosts
+            .get(name)
+            .filter(|x| x.expires > timestamp)
+            .map(|x| x.value.as_ref())
+    }
+
+    pub fn insert_host(
+        &mut self,
+        name: String,
+        addrs: Option<Vec<IpAddress>>,
+        ttl: u32,
+        timestamp: Instant,
+    ) {
+        self.hosts.insert(
+            name,
+            CacheEntry {
+                value: addrs,
+                expires: timestamp + ttl_duration(ttl),
+            },
+        );
+    }
+
+    /// Return all known hostnames, expired entries are still used for display
+    pub fn hostnames(&self) -> HostNames {
+        let mut ret = HostNames::new();
+        for (addr, entry) in self.ptr.iter() {
+            if let Some(ref name) = entry.value {
+                ret.insert(*addr, name.clone());
+            }
+        }
+        ret
+    }
+}
+
+fn ttl_duration(ttl: u32) -> Duration {
+    Duration::from_secs(u64::from(ttl.max(MIN_TTL).min(MAX_TTL)))
+}
+
+/// Return the in-addr.arpa name used for reverse lookups of addr
+pub fn ptr_name(addr: Ipv4Address) -> String {
+    let octets = addr.as_bytes();
+    format!(
+        "{}.{}.{}.{}.in-addr.arpa",
+        octets[3], octets[2], octets[1], octets[0]
+    )
+}
+
+/// Build a recursive query for name
+pub fn emit_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, &'static str> {
+    let mut buffer = vec![0; HEADER_LEN];
+    NetworkEndian::write_u16(&mut buffer[0..2], id);
+    NetworkEndian::write_u16(&mut buffer[2..4], FLAG_RECURSION_DESIRED);
+    NetworkEndian::write_u16(&mut buffer[4..6], 1);
+    emit_question(&mut buffer, name, qtype, CLASS_IN)?;
+    Ok(buffer)
+}
+
+/// Append a question for name to buffer
+pub fn emit_question(
+    buffer: &mut Vec<u8>,
+    name: &str,
+    qtype: u16,
+    class: u16,
+) -> Result<(), &'static str> {
+    emit_name(buffer, name)?;
+    let mut fields = [0; 4];
+    NetworkEndian::write_u16(&mut fields[0..2], qtype);
+    NetworkEndian::write_u16(&mut fields[2..4], class);
+    buffer.extend_from_slice(&fields);
+    Ok(())
+}
+
+fn emit_name(buffer: &mut Vec<u8>, name: &str) -> Result<(), &'static str> {
+    let name = name.trim_end_matches('.');
+    if name.len() > MAX_NAME_LEN {
+        return Err("DNS name too long");
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err("Invalid DNS label");
+        }
+        buffer.push(label.len() as u8);
+        buffer.extend_from_slice(label.as_bytes());
+    }
+    buffer.push(0);
+    Ok(())
+}
+
+/// Parse a DNS message
+pub fn parse_message(msg: &[u8]) -> Result<Message, &'static str> {
+    if msg.len() < HEADER_LEN {
+        return Err("DNS message truncated");
+    }
+    let mut ret = Message {
+        id: NetworkEndian::read_u16(&msg[0..2]),
+        flags: NetworkEndian::read_u16(&msg[2..4]),
+        questions: Vec::new(),
+        answers: Vec::new(),
+        authorities: Vec::new(),
+        additionals: Vec::new(),
+    };
+    let qdcount = NetworkEndian::read_u16(&msg[4..6]);
+    let ancount = NetworkEndian::read_u16(&msg[6..8]);
+    let nscount = NetworkEndian::read_u16(&msg[8..10]);
+    let arcount = NetworkEndian::read_u16(&msg[10..12]);
+
+    let mut offset = HEADER_LEN;
+    for _ in 0..qdcount {
+        let (name, next) = parse_name(msg, offset)?;
+        let fields = msg.get(next..next + 4).ok_or("DNS question truncated")?;
+        ret.questions.push(Question {
+            name,
+            qtype: NetworkEndian::read_u16(&fields[0..2]),
+        });
+        offset = next + 4;
+    }
+    offset = parse_records(msg, offset, ancount, &mut ret.answers)?;
+    offset = parse_records(msg, offset, nscount, &mut ret.authorities)?;
+    parse_records(msg, offset, arcount, &mut ret.additionals)?;
+    Ok(ret)
+}
+
+/// Parse count resource records starting at offset into records
+fn parse_records(
+    msg: &[u8],
+    mut offset: usize,
+    count: u16,
+    records: &mut Vec<Record>,
+) -> Result<usize, &'static str> {
+    for _ in 0..count {
+        let (record, next) = parse_record(msg, offset)?;
+        records.push(record);
+        offset = next;
+    }
+    Ok(offset)
+}
+
+/// Parse the resource record at offset, returns the record and the offset following it
+fn parse_record(msg: &[u8], offset: usize) -> Result<(Record, usize), &'static str> {
+    let (name, offset) = parse_name(msg, offset)?;
+    let fields = msg.get(offset..offset + 10).ok_or("DNS record truncated")?;
+    let rtype = NetworkEndian::read_u16(&fields[0..2]);
+    let ttl = NetworkEndian::read_u32(&fields[4..8]);
+    let rdlen = NetworkEndian::read_u16(&fields[8..10]) as usize;
+    let start = offset + 10;
+    let rdata = msg
+        .get(start..start + rdlen)
+        .ok_or("DNS record truncated")?;
+
+    let data = match rtype {
+        TYPE_A if rdlen == 4 => RecordData::A(Ipv4Address::from_bytes(rdata)),
+        TYPE_AAAA if rdlen == 16 => RecordData::Aaaa(Ipv6Address::from_bytes(rdata)),
+        TYPE_CNAME | TYPE_PTR => RecordData::Name(parse_name(msg, start)?.0),
+        TYPE_SOA => {
+            let (_, next) = parse_name(msg, start)?;
+            let (_, next) = parse_name(msg, next)?;
+            let fields = msg.get(next..next + 20).ok_or("DNS record truncated")?;
+            RecordData::Soa(NetworkEndian::read_u32(&fields[16..20]))
+        }
+        TYPE_SRV if rdlen > 6 => RecordData::Srv {
+            priority: NetworkEndian::read_u16(&rdata[0..2]),
+            weight: NetworkEndian::read_u16(&rdata[2..4]),
+            port: NetworkEndian::read_u16(&rdata[4..6]),
+            target: parse_name(msg, start + 6)?.0,
+        },
+        TYPE_TXT => {
+            let mut strings = Vec::new();
+            let mut rest = rdata;
+            while let Some((&len, tail)) = rest.split_first() {
+                let len = (len as usize).min(tail.len());
+                if len > 0 {
+                    strings.push(super::printable(&tail[..len]));
+                }
+                rest = &tail[len..];
+            }
+            RecordData::Txt(strings)
+        }
+        _ => RecordData::Other,
+    };
+    Ok((
+        Record {
+            name,
+            rtype,
+            ttl,
+            data,
+        },
+        start + rdlen,
+    ))
+}
+
+/// Parse the possibly compressed name at offset
+/// Returns the name and the offset following it
+pub fn parse_name(msg: &[u8], mut offset: usize) -> Result<(String, usize), &'static str> {
+    let mut name = String::new();
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = *msg.get(offset).ok_or("DNS name truncated")? as usize;
+        if len & 0xc0 == 0xc0 {
+            let low = *msg.get(offset + 1).ok_or("DNS name truncated")? as usize;
+            if end.is_none() {
+                end = Some(offset + 2);
+            }
+            pointers += 1;
+            if pointers > MAX_POINTERS {
+                return Err("DNS name pointer loop");
+            }
+            offset = ((len & 0x3f) << 8) | low;
+        } else if len & 0xc0 != 0 {
+            return Err("Unsupported DNS label type");
+        } else if len == 0 {
+            return Ok((name, end.unwrap_or(offset + 1)));
+        } else {
+            let label = msg
+                .get(offset + 1..offset + 1 + len)
+                .ok_or("DNS name truncated")?;
+            if !name.is_empty() {
+                name.push('.');
+            }
+            name.push_str(&super::printable(label));
+            if name.len() > MAX_NAME_LEN {
+                return Err("DNS name too long");
+            }
+            offset += 1 + len;
+        }
+    }
+}
+
+/// Follow the CNAME chain starting at name through the answers of msg
+/// Returns the last name of the chain, its addresses and the lowest ttl along the way
+pub fn follow_answers(msg: &Message, name: &str) -> (String, Vec<IpAddress>, u32) {
+    let mut name = String::from(name);
+    let mut ttl = MAX_TTL;
+    for _ in 0..MAX_CNAME_DEPTH {
+        let cname = msg.answers.iter().find_map(|record| match record.data {
+            RecordData::Name(ref target)
+                if record.rtype == TYPE_CNAME && record.name.eq_ignore_ascii_case(&name) =>
+            {
+                Some((target.clone(), record.ttl))
+            }
+            _ => None,
+        });
+        match cname {
+            Some((target, cname_ttl)) => {
+                name = target;
+                ttl = ttl.min(cname_ttl);
+            }
+            None => break,
+        }
+    }
+
+    let mut addrs = Vec::new();
+    for record in msg.answers.iter() {
+        if !record.name.eq_ignore_ascii_case(&name) {
+            continue;
+        }
+        match record.data {
+            RecordData::A(addr) => addrs.push(IpAddress::from(addr)),
+            RecordData::Aaaa(addr) => addrs.push(IpAddress::from(addr)),
+            _ => continue,
+        }
+        ttl = ttl.min(record.ttl);
+    }
+    (name, addrs, ttl)
+}
+
+/// Return how long the absence of an answer in msg may be cached
+pub fn negative_ttl(msg: &Message) -> u32 {
+    msg.authorities
+        .iter()
+        .find_map(|record| match record.data {
+            RecordData::Soa(minimum) => Some(minimum.min(record.ttl)),
+            _ => None,
+        })
+        .unwrap_or(NEGATIVE_TTL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header of a response with the given number of questions and answers
+    fn response_header(id: u16, qdcount: u16, ancount: u16) -> Vec<u8> {
+        let mut buffer = vec![0; HEADER_LEN];
+        NetworkEndian::write_u16(&mut buffer[0..2], id);
+        NetworkEndian::write_u16(&mut buffer[2..4], FLAG_RESPONSE | FLAG_RECURSION_DESIRED);
+        NetworkEndian::write_u16(&mut buffer[4..6], qdcount);
+        NetworkEndian::write_u16(&mut buffer[6..8], ancount);
+        buffer
+    }
+
+    /// Append a record of class IN whose name is a pointer to name_offset
+    fn push_record(buffer: &mut Vec<u8>, name_offset: u16, rtype: u16, ttl: u32, rdata: &[u8]) {
+        let mut fields = [0; 12];
+        NetworkEndian::write_u16(&mut fields[0..2], 0xc000 | name_offset);
+        NetworkEndian::write_u16(&mut fields[2..4], rtype);
+        NetworkEndian::write_u16(&mut fields[4..6], CLASS_IN);
+        NetworkEndian::write_u32(&mut fields[6..10], ttl);
+        NetworkEndian::write_u16(&mut fields[10..12], rdata.len() as u16);
+        buffer.extend_from_slice(&fields);
+        buffer.extend_from_slice(rdata);
+    }
+
+    #[test]
+    fn query_round_trip() {
+        let packet = emit_query(0x1234, "www.example.com.", TYPE_AAAA).unwrap();
+        let msg = parse_message(&packet).unwrap();
+        assert_eq!(msg.id, 0x1234);
+        assert!(!msg.is_response());
+        assert_eq!(msg.flags, FLAG_RECURSION_DESIRED);
+        assert_eq!(msg.questions.len(), 1);
+        assert_eq!(msg.questions[0].name, "www.example.com");
+        assert_eq!(msg.questions[0].qtype, TYPE_AAAA);
+        assert!(msg.answers.is_empty());
+    }
+
+    #[test]
+    fn response_must_echo_question() {
+        let mut msg = response_header(0x1234, 1, 0);
+        emit_question(&mut msg, "WWW.example.com", TYPE_A, CLASS_IN).unwrap();
+        let msg = parse_message(&msg).unwrap();
+        assert!(msg.answers(0x1234, "www.example.com.", TYPE_A));
+        assert!(!msg.answers(0x1235, "www.example.com", TYPE_A));
+        assert!(!msg.answers(0x1234, "mail.example.com", TYPE_A));
+        assert!(!msg.answers(0x1234, "www.example.com", TYPE_AAAA));
+
+        // The query itself is no response
+        let query = parse_message(&emit_query(0x1234, "www.example.com", TYPE_A).unwrap()).unwrap();
+        assert!(!query.answers(0x1234, "www.example.com", TYPE_A));
+    }
+
+    #[test]
+    fn ptr_query_round_trip() {
+        let name = ptr_name(Ipv4Address::new(10, 0, 0, 5));
+        assert_eq!(name, "5.0.0.10.in-addr.arpa");
+        let packet = emit_query(1, &name, TYPE_PTR).unwrap();
+        let (parsed, next) = parse_name(&packet, HEADER_LEN).unwrap();
+        assert_eq!(parsed, name);
+        assert_eq!(next, packet.len() - 4);
+    }
+
+    #[test]
+    fn invalid_names_are_not_emitted() {
+        assert_eq!(emit_query(1, "a..b", TYPE_A), Err("Invalid DNS label"));
+        let label = "x".repeat(64);
+        assert_eq!(emit_query(1, &label, TYPE_A), Err("Invalid DNS label"));
+        let name = vec!["abcdefg"; 40].join(".");
+        assert_eq!(emit_query(1, &name, TYPE_A), Err("DNS name too long"));
+    }
+
+    #[test]
+    fn compressed_answers() {
+        let mut msg = response_header(7, 1, 2);
+        emit_question(&mut msg, "www.example.com", TYPE_A, CLASS_IN).unwrap();
+
+        // www.example.com CNAME web.example.com, the target points into the question
+        let example_offset = HEADER_LEN as u16 + 4;
+        let cname_rdata_offset = msg.len() as u16 + 12;
+        let mut cname = vec![3, b'w', b'e', b'b'];
+        cname.extend_from_slice(&(0xc000 | example_offset).to_be_bytes());
+        push_record(&mut msg, HEADER_LEN as u16, TYPE_CNAME, 600, &cname);
+        // web.example.com A 10.0.0.5, the name points to the CNAME target
+        push_record(&mut msg, cname_rdata_offset, TYPE_A, 300, &[10, 0, 0, 5]);
+
+        let msg = parse_message(&msg).unwrap();
+        assert!(msg.is_response());
+        assert_eq!(msg.rcode(), RCODE_NO_ERROR);
+        assert_eq!(msg.answers.len(), 2);
+        assert_eq!(msg.answers[0].name, "www.example.com");
+        match msg.answers[0].data {
+            RecordData::Name(ref target) => assert_eq!(target, "web.example.com"),
+            ref x => panic!("unexpected record data {:?}", x),
+        }
+        assert_eq!(msg.answers[1].name, "web.example.com");
+
+        let (name, addrs, ttl) = follow_answers(&msg, "www.example.com");
+        assert_eq!(name, "web.example.com");
+        assert_eq!(addrs, vec![IpAddress::from(Ipv4Address::new(10, 0, 0, 5))]);
+        assert_eq!(ttl, 300);
+    }
+
+    #[test]
+    fn pointer_ends_name() {
+        // The name following a pointer ends right after the pointer
+        let mut msg = response_header(1, 0, 0);
+        emit_name(&mut msg, "example.com").unwrap();
+        let offset = msg.len();
+        msg.extend_from_slice(&[3, b'f', b'o', b'o', 0xc0, HEADER_LEN as u8, 0xff]);
+        assert_eq!(
+            parse_name(&msg, offset),
+            Ok((String::from("foo.example.com"), offset + 6))
+        );
+    }
+
+    #[test]
+    fn pointer_loops_are_rejected() {
+        // A pointer to itself
+        let mut msg = response_header(1, 1, 0);
+        msg.extend_from_slice(&[0xc0, HEADER_LEN as u8, 0, 1, 0, 1]);
+        assert_eq!(parse_message(&msg).err(), Some("DNS name pointer loop"));
+
+        // Two pointers to each other
+        let mut msg = response_header(1, 0, 0);
+        msg.extend_from_slice(&[0xc0, HEADER_LEN as u8 + 2, 0xc0, HEADER_LEN as u8]);
+        assert_eq!(parse_name(&msg, HEADER_LEN), Err("DNS name pointer loop"));
+
+        // A label followed by a pointer back to it
+        let mut msg = response_header(1, 0, 0);
+        msg.extend_from_slice(&[1, b'a', 0xc0, HEADER_LEN as u8]);
+        assert_eq!(parse_name(&msg, HEADER_LEN), Err("DNS name pointer loop"));
+    }
+
+    #[test]
+    fn truncated_names_are_rejected() {
+        let mut msg = response_header(1, 0, 0);
+        msg.extend_from_slice(&[0xc0, 0xff]);
+        assert_eq!(parse_name(&msg, HEADER_LEN), Err("DNS name truncated"));
+
+        let mut msg = response_header(1, 0, 0);
+        msg.push(0xc0);
+        assert_eq!(parse_name(&msg, HEADER_LEN), Err("DNS name truncated"));
+
+        let mut msg = response_header(1, 0, 0);
+        msg.extend_from_slice(&[5, b'a', b'b']);
+        assert_eq!(parse_name(&msg, HEADER_LEN), Err("DNS name truncated"));
+
+        let mut msg = response_header(1, 0, 0);
+        msg.extend_from_slice(&[0x40, 0]);
+        assert_eq!(
+            parse_name(&msg, HEADER_LEN),
+            Err("Unsupported DNS label type")
+        );
+    }
+}
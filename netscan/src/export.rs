@@ -0,0 +1,591 @@
+use alloc::string::String;
+use core::fmt::{self, Write};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+use super::inventory::{Host, Inventory, Ports, Source};
+use super::pcapfile::CaptureSink;
+
+/// Port a host-side receiver connects to without a debugger, once per format,
+/// e.g. with `nc <board> 5556 > scan.json`
+pub const EXPORT_PORT: u16 = 5556;
+
+/// Formats the inventory can be exported in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    /// The subset of the nmap XML output describing hosts and their ports
+    NmapXml,
+}
+
+pub const FORMATS: [Format; 3] = [Format::Json, Format::Csv, Format::NmapXml];
+
+impl Format {
+    /// File the export is written to on the debugging host
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Format::Json => "scan.json",
+            Format::Csv => "scan.csv",
+            Format::NmapXml => "scan.xml",
+        }
+    }
+
+    /// Write inventory, finished is the time the results are exported at
+    pub fn write<W: Write>(
+        self,
+        w: &mut W,
+        inventory: &Inventory,
+        finished: Instant,
+    ) -> fmt::Result {
+        match self {
+            Format::Json => write_json(w, inventory),
+            Format::Csv => write_csv(w, inventory),
+            Format::NmapXml => write_nmap_xml(w, inventory, finished),
+        }
+    }
+
+    /// Write inventory to sink in this format
+    pub fn export<S: CaptureSink>(
+        self,
+        sink: &mut S,
+        inventory: &Inventory,
+        finished: Instant,
+    ) -> Result<(), String> {
+        let mut text = TextSink::new(sink);
+        self.write(&mut text, inventory, finished)
+            .and_then(|_| text.flush())
+            .map_err(|_| {
+                text.error
+                    .take()
+                    .unwrap_or_else(|| String::from("Export failed"))
+            })
+    }
+}
+
+/// Text collected before it is written to the sink, every semihosting write traps the debugger
+const TEXT_BUFFER_LEN: usize = 512;
+
+/// Writes text to a capture sink line by line, keeping the error of the sink
+pub struct TextSink<'a, S: CaptureSink> {
+    sink: &'a mut S,
+    buffer: String,
+    pub error: Option<String>,
+}
+
+impl<'a, S: CaptureSink> TextSink<'a, S> {
+    pub fn new(sink: &'a mut S) -> Self {
+        TextSink {
+            sink,
+            buffer: String::with_capacity(TEXT_BUFFER_LEN),
+            error: None,
+        }
+    }
+
+    /// Write the buffered text to the sink
+    pub fn flush(&mut self) -> fmt::Result {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let result = self.sink.write(self.buffer.as_bytes());
+        self.buffer.clear();
+        result.map_err(|x| {
+            self.error = Some(x);
+            fmt::Error
+        })
+    }
+}
+
+impl<'a, S: CaptureSink> Write for TextSink<'a, S> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buffer.push_str(s);
+        // Complete lines are written, long lines once the buffer is full
+        if s.contains('\n') || self.buffer.len() >= TEXT_BUFFER_LEN {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn source_key(source: Source) -> &'static str {
+    match source {
+        Source::Dns => "dns",
+        Source::Netbios => "netbios",
+        Source::Snmp => "snmp",
+        Source::Mdns => "mdns",
+        Source::Ssdp => "ssdp",
+        Source::Lldp => "lldp",
+        Source::Cdp => "cdp",
+        Source::Dhcp => "dhcp",
+    }
+}
+
+/// Write eth_addr colon separated, as the tools reading the exports expect it
+fn mac<W: Write>(w: &mut W, eth_addr: EthernetAddress, upper_case: bool) -> fmt::Result {
+    for (i, byte) in eth_addr.as_bytes().iter().enumerate() {
+        if i > 0 {
+            w.write_char(':')?;
+        }
+        if upper_case {
+            write!(w, "{:02X}", byte)?;
+        } else {
+            write!(w, "{:02x}", byte)?;
+        }
+    }
+    Ok(())
+}
+
+fn json_string<W: Write>(w: &mut W, s: &str) -> fmt::Result {
+    w.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => w.write_char(c)?,
+        }
+    }
+    w.write_char('"')
+}
+
+fn json_ports<W: Write>(w: &mut W, host: &Host, ports: &Ports, banners: bool) -> fmt::Result {
+    write!(w, "{{\"scanned\":{},\"open\":[", ports.scanned.is_some())?;
+    for (i, service) in ports.open.iter().enumerate() {
+        if i > 0 {
+            w.write_char(',')?;
+        }
+        write!(w, "{{\"port\":{},\"service\":", service.0)?;
+        json_string(w, service.1)?;
+        if let Some(banner) = host.banners.get(&service.0).filter(|_| banners) {
+            w.write_str(",\"banner\":")?;
+            json_string(w, banner)?;
+        }
+        w.write_char('}')?;
+    }
+    w.write_str("]}")
+}
+
+fn json_host<W: Write>(w: &mut W, addr: Ipv4Address, host: &Host) -> fmt::Result {
+    write!(w, "{{\"address\":\"{}\",\"mac\":", addr)?;
+    match host.eth_addr {
+        Some(x) => {
+            w.write_char('"')?;
+            mac(w, x, false)?;
+            w.write_char('"')?;
+        }
+        None => w.write_str("null")?,
+    }
+    w.write_str(",\"vendor\":")?;
+    match host.vendor {
+        Some(x) => json_string(w, x)?,
+        None => w.write_str("null")?,
+    }
+    w.write_str(",\"names\":{")?;
+    for (i, (source, name)) in host.names.iter().enumerate() {
+        if i > 0 {
+            w.write_char(',')?;
+        }
+        write!(w, "\"{}\":", source_key(*source))?;
+        json_string(w, name)?;
+    }
+    w.write_str("},\"details\":{")?;
+    for (i, (source, detail)) in host.details.iter().enumerate() {
+        if i > 0 {
+            w.write_char(',')?;
+        }
+        write!(w, "\"{}\":", source_key(*source))?;
+        json_string(w, detail)?;
+    }
+    w.write_str("},\"rtt\":")?;
+    match (host.rtt.min, host.rtt.avg(), host.rtt.max) {
+        (Some(min), Some(avg), Some(max)) => write!(
+            w,
+            "{{\"min_ms\":{},\"avg_ms\":{},\"max_ms\":{},\"answered\":{},\"lost\":{}}}",
+            min.total_millis(),
+            avg.total_millis(),
+            max.total_millis(),
+            host.rtt.count,
+            host.rtt.lost
+        )?,
+        _ => w.write_str("null")?,
+    }
+    w.write_str(",\"tcp\":")?;
+    json_ports(w, host, &host.tcp, true)?;
+    w.write_str(",\"udp\":")?;
+    json_ports(w, host, &host.udp, false)?;
+    write!(
+        w,
+        ",\"first_seen_ms\":{},\"last_seen_ms\":{}}}",
+        host.first_seen.total_millis(),
+        host.last_seen.total_millis()
+    )
+}
+
+/// Write the inventory as a JSON document with one host per line
+pub fn write_json<W: Write>(w: &mut W, inventory: &Inventory) -> fmt::Result {
+    w.write_str("{\"hosts\":[\n")?;
+    for (i, (addr, host)) in inventory.hosts.iter().enumerate() {
+        if i > 0 {
+            w.write_str(",\n")?;
+        }
+        json_host(w, *addr, host)?;
+    }
+    w.write_str("\n]}\n")
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break
+fn csv_field<W: Write>(w: &mut W, s: &str) -> fmt::Result {
+    if !s.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        return w.write_str(s);
+    }
+    w.write_char('"')?;
+    for c in s.chars() {
+        if c == '"' {
+            w.write_char('"')?;
+        }
+        w.write_char(c)?;
+    }
+    w.write_char('"')
+}
+
+fn csv_millis<W: Write>(w: &mut W, duration: Option<Duration>) -> fmt::Result {
+    match duration {
+        Some(x) => write!(w, ",{}", x.total_millis()),
+        None => w.write_char(','),
+    }
+}
+
+/// Write the columns describing a host, shared by all rows of its ports
+fn csv_host<W: Write>(w: &mut W, addr: Ipv4Address, host: &Host) -> fmt::Result {
+    write!(w, "{},", addr)?;
+    if let Some(x) = host.eth_addr {
+        mac(w, x, false)?;
+    }
+    w.write_char(',')?;
+    csv_field(w, host.vendor.unwrap_or(""))?;
+    w.write_char(',')?;
+    csv_field(w, host.name().map_or("", |x| x.as_str()))?;
+    csv_millis(w, host.rtt.min)?;
+    csv_millis(w, host.rtt.avg())?;
+    csv_millis(w, host.rtt.max)
+}
+
+pub const CSV_HEADER: &str =
+    "address,mac,vendor,name,rtt_min_ms,rtt_avg_ms,rtt_max_ms,protocol,port,service,banner";
+
+/// Write the inventory as CSV with one row per open port
+/// Hosts without open ports get a single row with empty port columns
+pub fn write_csv<W: Write>(w: &mut W, inventory: &Inventory) -> fmt::Result {
+    writeln!(w, "{}", CSV_HEADER)?;
+    for (addr, host) in inventory.hosts.iter() {
+        let mut rows = 0;
+        for (protocol, ports) in [("tcp", &host.tcp), ("udp", &host.udp)].iter() {
+            for service in ports.open.iter() {
+                csv_host(w, *addr, host)?;
+                write!(w, ",{},{},", protocol, service.0)?;
+                csv_field(w, service.1)?;
+                w.write_char(',')?;
+                if *protocol == "tcp" {
+                    csv_field(w, host.banners.get(&service.0).map_or("", |x| x.as_str()))?;
+                }
+                w.write_char('\n')?;
+                rows += 1;
+            }
+        }
+        if rows == 0 {
+            csv_host(w, *addr, host)?;
+            w.write_str(",,,,\n")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write s as XML attribute value
+fn xml_attr<W: Write>(w: &mut W, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => w.write_str("&amp;")?,
+            '<' => w.write_str("&lt;")?,
+            '>' => w.write_str("&gt;")?,
+            '"' => w.write_str("&quot;")?,
+            '\'' => w.write_str("&apos;")?,
+            '\n' => w.write_str("&#xa;")?,
+            c => w.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+fn xml_ports<W: Write>(
+    w: &mut W,
+    host: &Host,
+    protocol: &str,
+    ports: &Ports,
+    reason: &str,
+) -> fmt::Result {
+    for service in ports.open.iter() {
+        writeln!(
+            w,
+            "<port protocol=\"{}\" portid=\"{}\"><state state=\"open\" reason=\"{}\"/>",
+            protocol, service.0, reason
+        )?;
+        w.write_str("<service name=\"")?;
+        xml_attr(w, service.1)?;
+        w.write_str("\" method=\"table\" conf=\"3\"/>\n")?;
+        if let Some(banner) = host.banners.get(&service.0).filter(|_| protocol == "tcp") {
+            w.write_str("<script id=\"banner\" output=\"")?;
+            xml_attr(w, banner)?;
+            w.write_str("\"/>\n")?;
+        }
+        w.write_str("</port>\n")?;
+    }
+    Ok(())
+}
+
+/// The nmap reason a host is known to be up, the most direct answer it gave
+fn status_reason(host: &Host) -> &'static str {
+    let udp_answer = |x: &Source| match x {
+        Source::Netbios | Source::Snmp | Source::Mdns | Source::Ssdp | Source::Dhcp => true,
+        Source::Dns | Source::Lldp | Source::Cdp => false,
+    };
+    // Hardware addresses are learned from ARP replies and NetBIOS answers
+    if host.eth_addr.is_some() {
+        "arp-response"
+    } else if host.rtt.count > 0 {
+        "echo-reply"
+    } else if !host.tcp.open.is_empty() {
+        "syn-ack"
+    } else if !host.udp.open.is_empty()
+        || host.names.keys().any(udp_answer)
+        || host.details.keys().any(udp_answer)
+    {
+        "udp-response"
+    } else {
+        // Targets entered by the user and switches announcing themselves
+        "user-set"
+    }
+}
+
+fn xml_host<W: Write>(w: &mut W, addr: Ipv4Address, host: &Host) -> fmt::Result {
+    writeln!(
+        w,
+        "<host>\n<status state=\"up\" reason=\"{}\"/>",
+        status_reason(host)
+    )?;
+    writeln!(w, "<address addr=\"{}\" addrtype=\"ipv4\"/>", addr)?;
+    if let Some(eth_addr) = host.eth_addr {
+        // nmap writes hardware addresses in upper case
+        w.write_str("<address addr=\"")?;
+        mac(w, eth_addr, true)?;
+        w.write_str("\" addrtype=\"mac\"")?;
+        if let Some(vendor) = host.vendor {
+            w.write_str(" vendor=\"")?;
+            xml_attr(w, vendor)?;
+            w.write_char('"')?;
+        }
+        w.write_str("/>\n")?;
+    }
+    w.write_str("<hostnames>\n")?;
+    for (source, name) in host.names.iter() {
+        w.write_str("<hostname name=\"")?;
+        xml_attr(w, name)?;
+        let kind = if *source == Source::Dns {
+            "PTR"
+        } else {
+            "user"
+        };
+        writeln!(w, "\" type=\"{}\"/>", kind)?;
+    }
+    w.write_str("</hostnames>\n")?;
+    if host.tcp.scanned.is_some() || host.udp.scanned.is_some() {
+        w.write_str("<ports>\n")?;
+        xml_ports(w, host, "tcp", &host.tcp, "syn-ack")?;
+        xml_ports(w, host, "udp", &host.udp, "udp-response")?;
+        w.write_str("</ports>\n")?;
+    }
+    if let (Some(min), Some(avg), Some(max)) = (host.rtt.min, host.rtt.avg(), host.rtt.max) {
+        // nmap gives times in microseconds, the spread stands in for the variance
+        let srtt = avg.total_millis() * 1000;
+        let rttvar = (max - min).total_millis() * 1000;
+        writeln!(
+            w,
+            "<times srtt=\"{}\" rttvar=\"{}\" to=\"{}\"/>",
+            srtt,
+            rttvar,
+            srtt + 4 * rttvar
+        )?;
+    }
+    w.write_str("</host>\n")
+}
+
+/// Write the inventory as nmap XML, readable by tools importing nmap scans
+/// Only hosts, addresses, hostnames, open ports and round trip times are written
+/// The board has no clock, times are seconds since it started
+pub fn write_nmap_xml<W: Write>(
+    w: &mut W,
+    inventory: &Inventory,
+    finished: Instant,
+) -> fmt::Result {
+    w.write_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE nmaprun>\n")?;
+    w.write_str("<nmaprun scanner=\"stm32f7-netscan\" xmloutputversion=\"1.04\">\n")?;
+    for (addr, host) in inventory.hosts.iter() {
+        xml_host(w, *addr, host)?;
+    }
+    let hosts = inventory.hosts.len();
+    // The scan started when the first host was found
+    let started = inventory.hosts.values().map(|x| x.first_seen).min();
+    let elapsed = started.map_or(0, |x| (finished - x).total_millis());
+    let elapsed = format!("{}.{:02}", elapsed / 1000, elapsed % 1000 / 10);
+    write!(
+        w,
+        "<runstats><finished time=\"{}\" elapsed=\"{}\"",
+        finished.secs(),
+        elapsed
+    )?;
+    writeln!(
+        w,
+        " summary=\"{} IP addresses ({} hosts up) scanned in {} seconds\" exit=\"success\"/>",
+        hosts, hosts, elapsed
+    )?;
+    writeln!(
+        w,
+        "<hosts up=\"{}\" down=\"0\" total=\"{}\"/></runstats>",
+        hosts, hosts
+    )?;
+    w.write_str("</nmaprun>\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::arp::ArpResponses;
+    use super::super::services::{Service, TCP_SERVICES, UDP_SERVICES};
+    use super::super::PortScan;
+    use super::*;
+    use alloc::vec::Vec;
+
+    const ROUTER: Ipv4Address = Ipv4Address([10, 0, 0, 1]);
+    const TV: Ipv4Address = Ipv4Address([10, 0, 0, 7]);
+    const NAS: Ipv4Address = Ipv4Address([10, 0, 0, 9]);
+    const FINISHED: Instant = Instant { millis: 9_500 };
+
+    fn service(services: &'static [Service], port: u16) -> &'static Service {
+        services.iter().find(|x| x.0 == port).unwrap()
+    }
+
+    /// A router found by ARP, pinged and port scanned, a TV found by mDNS and a target
+    fn inventory() -> Inventory {
+        let mut inventory = Inventory::default();
+        let mut neighbors = ArpResponses::new();
+        neighbors.insert(
+            ROUTER,
+            EthernetAddress([0x00, 0x0c, 0x42, 0x01, 0x02, 0x03]),
+        );
+        inventory.merge_neighbors(&neighbors, Instant::from_millis(1_000));
+        inventory.merge_target(ROUTER, Some("router.lan"), Instant::from_millis(1_000));
+        inventory.merge_ping(
+            ROUTER,
+            Some(Duration::from_millis(2)),
+            Instant::from_millis(2_000),
+        );
+        inventory.merge_ping(
+            ROUTER,
+            Some(Duration::from_millis(4)),
+            Instant::from_millis(3_000),
+        );
+        inventory.merge_ping(ROUTER, None, Instant::from_millis(4_000));
+        let scan = PortScan(
+            ROUTER,
+            vec![service(&TCP_SERVICES, 22), service(&TCP_SERVICES, 80)],
+            vec![(22, String::from("SSH-2.0-dropbear_2019.78"))],
+        );
+        inventory.merge_tcp_scan(&scan, Instant::from_millis(5_000));
+
+        let scan = PortScan(TV, vec![service(&UDP_SERVICES, 5353)], Vec::new());
+        inventory.merge_udp_scan(&scan, Instant::from_millis(6_000));
+        let tv = inventory.hosts.get_mut(&TV).unwrap();
+        tv.names
+            .insert(Source::Mdns, String::from("Living Room \"TV\""));
+        tv.details
+            .insert(Source::Mdns, String::from("_googlecast._tcp"));
+
+        inventory.merge_target(NAS, Some("nas.lan"), Instant::from_millis(7_000));
+        inventory
+    }
+
+    fn export(format: Format) -> String {
+        let mut sink: Vec<u8> = Vec::new();
+        format.export(&mut sink, &inventory(), FINISHED).unwrap();
+        String::from_utf8(sink).unwrap()
+    }
+
+    #[test]
+    fn json_matches_fixture() {
+        let expected = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/scan.json"));
+        assert_eq!(export(Format::Json), expected);
+    }
+
+    #[test]
+    fn csv_matches_fixture() {
+        let expected = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/scan.csv"));
+        assert_eq!(export(Format::Csv), expected);
+    }
+
+    #[test]
+    fn nmap_xml_matches_fixture() {
+        let expected = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/scan.xml"));
+        assert_eq!(export(Format::NmapXml), expected);
+    }
+
+    /// Records every write it gets, fails once limit writes were made
+    struct Writes {
+        writes: Vec<Vec<u8>>,
+        limit: usize,
+    }
+
+    impl CaptureSink for Writes {
+        fn write(&mut self, data: &[u8]) -> Result<(), String> {
+            if self.writes.len() >= self.limit {
+                return Err(String::from("Sink full"));
+            }
+            self.writes.push(data.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn text_is_written_by_line() {
+        let mut sink = Writes {
+            writes: Vec::new(),
+            limit: usize::max_value(),
+        };
+        {
+            let mut text = TextSink::new(&mut sink);
+            write!(text, "{},{}\n{}", 1, 2, 3).unwrap();
+            text.flush().unwrap();
+        }
+        assert_eq!(sink.writes, vec![b"1,2\n".to_vec(), b"3".to_vec()]);
+
+        let lines = export(Format::Csv).lines().count();
+        sink.writes.clear();
+        Format::Csv
+            .export(&mut sink, &inventory(), FINISHED)
+            .unwrap();
+        assert_eq!(sink.writes.len(), lines);
+    }
+
+    #[test]
+    fn sink_errors_are_kept() {
+        let mut sink = Writes {
+            writes: Vec::new(),
+            limit: 1,
+        };
+        assert_eq!(
+            Format::Json.export(&mut sink, &inventory(), FINISHED),
+            Err(String::from("Sink full"))
+        );
+    }
+}
@@ -0,0 +1,628 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::wire::*;
+
+use super::config::parse_address;
+use super::ipv6;
+
+/// Longest filter that can be entered
+pub const MAX_FILTER_LEN: usize = 40;
+/// Shortcuts offered while entering a filter, the empty filter matches every frame
+pub const PRESETS: [&str; 11] = [
+    "",
+    "not broadcast",
+    "not broadcast and not multicast",
+    "arp",
+    "icmp",
+    "tcp",
+    "udp",
+    "tcp port 80 or tcp port 443",
+    "udp port 53",
+    "vlan",
+    "vlan 20",
+];
+
+/// The preset following filter, or the first one if filter is no preset
+pub fn next_preset(filter: &str) -> &'static str {
+    match PRESETS.iter().position(|x| *x == filter) {
+        Some(i) => PRESETS[(i + 1) % PRESETS.len()],
+        None => PRESETS[0],
+    }
+}
+
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// Which addresses or ports of a packet a term is compared with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Src,
+    Dst,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Proto {
+    Arp,
+    Ip,
+    Ip6,
+    Icmp,
+    Tcp,
+    Udp,
+}
+
+/// A compiled filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Proto(Proto),
+    Host(Side, IpAddress),
+    Net(Side, IpCidr),
+    Port(Side, u16),
+    EtherHost(Side, EthernetAddress),
+    Broadcast,
+    Multicast,
+    /// Tagged frames, optionally of a single VLAN
+    Vlan(Option<u16>),
+}
+
+/// The fields of a frame filters are evaluated on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameInfo {
+    pub src_mac: EthernetAddress,
+    pub dst_mac: EthernetAddress,
+    pub vlan: Option<u16>,
+    /// Ethertype of the frame, the inner one for tagged frames
+    pub ethertype: EthernetProtocol,
+    /// IPv4 or IPv6 source and destination, or sender and target of an ARP packet
+    pub addrs: Option<(IpAddress, IpAddress)>,
+    pub ip_protocol: Option<IpProtocol>,
+    pub ports: Option<(u16, u16)>,
+}
+
+impl FrameInfo {
+    pub fn parse(frame: &[u8]) -> Option<FrameInfo> {
+        let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+        let (vlan, ethertype, payload) =
+            if u16::from(eth_frame.ethertype()) == ETHERTYPE_VLAN && frame.len() >= 18 {
+                (
+                    Some(NetworkEndian::read_u16(&frame[14..16]) & 0x0fff),
+                    EthernetProtocol::from(NetworkEndian::read_u16(&frame[16..18])),
+                    &frame[18..],
+                )
+            } else {
+                (None, eth_frame.ethertype(), eth_frame.payload())
+            };
+
+        let mut info = FrameInfo {
+            src_mac: eth_frame.src_addr(),
+            dst_mac: eth_frame.dst_addr(),
+            vlan,
+            ethertype,
+            addrs: None,
+            ip_protocol: None,
+            ports: None,
+        };
+        match ethertype {
+            EthernetProtocol::Ipv4 => {
+                if let Ok(packet) = Ipv4Packet::new_checked(payload) {
+                    info.addrs = Some((packet.src_addr().into(), packet.dst_addr().into()));
+                    info.ip_protocol = Some(packet.protocol());
+                    info.ports = ports(packet.protocol(), packet.payload());
+                }
+            }
+            EthernetProtocol::Ipv6 => {
+                if let Ok(packet) = Ipv6Packet::new_checked(payload) {
+                    info.addrs = Some((packet.src_addr().into(), packet.dst_addr().into()));
+                    if let Ok(upper) = ipv6::upper_layer(packet.next_header(), packet.payload()) {
+                        info.ip_protocol = Some(upper.protocol);
+                        info.ports = upper.payload.and_then(|x| ports(upper.protocol, x));
+                    }
+                }
+            }
+            EthernetProtocol::Arp => {
+                if let Ok(packet) = ArpPacket::new_checked(payload) {
+                    if let Ok(ArpRepr::EthernetIpv4 {
+                        source_protocol_addr,
+                        target_protocol_addr,
+                        ..
+                    }) = ArpRepr::parse(&packet)
+                    {
+                        info.addrs =
+                            Some((source_protocol_addr.into(), target_protocol_addr.into()));
+                    }
+                }
+            }
+            _ => {}
+        }
+        Some(info)
+    }
+}
+
+/// Source and destination port of a TCP or UDP packet
+fn ports(protocol: IpProtocol, payload: &[u8]) -> Option<(u16, u16)> {
+    match protocol {
+        IpProtocol::Tcp => TcpPacket::new_checked(payload)
+            .ok()
+            .map(|x| (x.src_port(), x.dst_port())),
+        IpProtocol::Udp => UdpPacket::new_checked(payload)
+            .ok()
+            .map(|x| (x.src_port(), x.dst_port())),
+        _ => None,
+    }
+}
+
+/// Compare the source, destination or both values of a pair
+fn side_matches<T: Copy>(side: Side, pair: Option<(T, T)>, f: impl Fn(T) -> bool) -> bool {
+    match pair {
+        Some((src, dst)) => match side {
+            Side::Src => f(src),
+            Side::Dst => f(dst),
+            Side::Any => f(src) || f(dst),
+        },
+        None => false,
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, info: &FrameInfo) -> bool {
+        match self {
+            Filter::Not(x) => !x.matches(info),
+            Filter::And(a, b) => a.matches(info) && b.matches(info),
+            Filter::Or(a, b) => a.matches(info) || b.matches(info),
+            Filter::Proto(proto) => match proto {
+                Proto::Arp => info.ethertype == EthernetProtocol::Arp,
+                Proto::Ip => info.ethertype == EthernetProtocol::Ipv4,
+                Proto::Ip6 => info.ethertype == EthernetProtocol::Ipv6,
+                Proto::Icmp => info.ip_protocol == Some(IpProtocol::Icmp),
+                Proto::Tcp => info.ip_protocol == Some(IpProtocol::Tcp),
+                Proto::Udp => info.ip_protocol == Some(IpProtocol::Udp),
+            },
+            Filter::Host(side, addr) => side_matches(*side, info.addrs, |x| x == *addr),
+            Filter::Net(side, cidr) => side_matches(*side, info.addrs, |x| cidr.contains_addr(&x)),
+            Filter::Port(side, port) => side_matches(*side, info.ports, |x| x == *port),
+            Filter::EtherHost(side, mac) => {
+                side_matches(*side, Some((info.src_mac, info.dst_mac)), |x| x == *mac)
+            }
+            Filter::Broadcast => info.dst_mac.is_broadcast(),
+            Filter::Multicast => info.dst_mac.is_multicast(),
+            Filter::Vlan(None) => info.vlan.is_some(),
+            Filter::Vlan(Some(id)) => info.vlan == Some(*id),
+        }
+    }
+
+    /// Evaluate the filter on a raw frame, frames too short to parse never match
+    pub fn matches_frame(&self, frame: &[u8]) -> bool {
+        match FrameInfo::parse(frame) {
+            Some(info) => self.matches(&info),
+            None => false,
+        }
+    }
+}
+
+/// Split an expression into words, parentheses and '!' are words of their own
+fn tokenize(expr: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in expr.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' || c == '!' {
+            if let Some(x) = start.take() {
+                tokens.push(&expr[x..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&expr[i..=i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(x) = start {
+        tokens.push(&expr[x..]);
+    }
+    tokens
+}
+
+fn parse_mac(s: &str) -> Result<EthernetAddress, String> {
+    let mut octets = [0; 6];
+    let mut count = 0;
+    for octet in s.split(':') {
+        if count == 6 {
+            return Err(format!("Invalid hardware address \"{}\"", s));
+        }
+        octets[count] = u8::from_str_radix(octet, 16)
+            .map_err(|_| format!("Invalid hardware address \"{}\"", s))?;
+        count += 1;
+    }
+    if count != 6 {
+        return Err(format!("Invalid hardware address \"{}\"", s));
+    }
+    Ok(EthernetAddress(octets))
+}
+
+/// Parse an IPv4 address or an IPv6 address, which are told apart by their colons
+fn parse_ip(s: &str) -> Result<IpAddress, String> {
+    if s.contains(':') {
+        s.parse::<Ipv6Address>()
+            .map(IpAddress::Ipv6)
+            .map_err(|_| format!("Invalid IPv6 address \"{}\"", s))
+    } else {
+        parse_address(s).map(IpAddress::Ipv4).map_err(String::from)
+    }
+}
+
+fn parse_net(s: &str) -> Result<IpCidr, String> {
+    let pos = s
+        .find('/')
+        .ok_or_else(|| format!("Network \"{}\" needs a prefix length", s))?;
+    let addr = parse_ip(&s[..pos])?;
+    let max_len = match addr {
+        IpAddress::Ipv6(_) => 128,
+        _ => 32,
+    };
+    match s[pos + 1..].parse::<u8>() {
+        Ok(x) if x <= max_len => Ok(IpCidr::new(addr, x)),
+        _ => Err(format!("Invalid prefix length in \"{}\"", s)),
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn next_token(&mut self) -> Result<&'a str, String> {
+        let token = self
+            .peek()
+            .ok_or_else(|| String::from("Unexpected end of filter"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Skip the next token if it is one of words
+    fn eat(&mut self, words: &[&str]) -> bool {
+        match self.peek() {
+            Some(x) if words.contains(&x) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_and()?;
+        while self.eat(&["or", "||"]) {
+            let right = self.parse_and()?;
+            left = Filter::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_not()?;
+        while self.eat(&["and", "&&"]) {
+            let right = self.parse_not()?;
+            left = Filter::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Filter, String> {
+        if self.eat(&["not", "!"]) {
+            Ok(Filter::Not(Box::new(self.parse_not()?)))
+        } else if self.eat(&["("]) {
+            let inner = self.parse_or()?;
+            if !self.eat(&[")"]) {
+                return Err(String::from("Missing \")\""));
+            }
+            Ok(inner)
+        } else {
+            self.parse_term()
+        }
+    }
+
+    fn parse_side(&mut self) -> Side {
+        if self.eat(&["src"]) {
+            Side::Src
+        } else if self.eat(&["dst"]) {
+            Side::Dst
+        } else {
+            Side::Any
+        }
+    }
+
+    /// Parse a term like `src host 10.0.0.5`, `net 10.0.0.0/8` or `dst port 53`
+    fn parse_qualified(&mut self) -> Result<Filter, String> {
+        let side = self.parse_side();
+        match self.next_token()? {
+            "host" => Ok(Filter::Host(side, parse_ip(self.next_token()?)?)),
+            "net" => Ok(Filter::Net(side, parse_net(self.next_token()?)?)),
+            "port" => {
+                let port = self.next_token()?;
+                port.parse::<u16>()
+                    .map(|x| Filter::Port(side, x))
+                    .map_err(|_| format!("Invalid port \"{}\"", port))
+            }
+            x => Err(format!("Unknown filter term \"{}\"", x)),
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Filter, String> {
+        let proto = match self.next_token()? {
+            "arp" => return Ok(Filter::Proto(Proto::Arp)),
+            "ip" => return Ok(Filter::Proto(Proto::Ip)),
+            "ip6" => return Ok(Filter::Proto(Proto::Ip6)),
+            "icmp" => return Ok(Filter::Proto(Proto::Icmp)),
+            "broadcast" => return Ok(Filter::Broadcast),
+            "multicast" => return Ok(Filter::Multicast),
+            "vlan" => {
+                let id = match self.peek().map(|x| x.parse::<u16>()) {
+                    Some(Ok(x)) if x < 4096 => x,
+                    Some(Ok(x)) => return Err(format!("Invalid VLAN id {}", x)),
+                    _ => return Ok(Filter::Vlan(None)),
+                };
+                self.pos += 1;
+                return Ok(Filter::Vlan(Some(id)));
+            }
+            "ether" => {
+                let side = self.parse_side();
+                if !self.eat(&["host"]) {
+                    return Err(String::from("Expected \"host\" after \"ether\""));
+                }
+                return Ok(Filter::EtherHost(side, parse_mac(self.next_token()?)?));
+            }
+            "tcp" => Proto::Tcp,
+            "udp" => Proto::Udp,
+            _ => {
+                self.pos -= 1;
+                return self.parse_qualified();
+            }
+        };
+        // tcp and udp may qualify a port, as in `tcp dst port 443`
+        match self.peek() {
+            Some("port") | Some("src") | Some("dst") => Ok(Filter::And(
+                Box::new(Filter::Proto(proto)),
+                Box::new(self.parse_qualified()?),
+            )),
+            _ => Ok(Filter::Proto(proto)),
+        }
+    }
+}
+
+/// Compile a tcpdump like filter expression, an empty expression matches everything
+pub fn parse(expr: &str) -> Result<Option<Filter>, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expr),
+        pos: 0,
+    };
+    if parser.tokens.is_empty() {
+        return Ok(None);
+    }
+    let filter = parser.parse_or()?;
+    match parser.peek() {
+        Some(x) => Err(format!("Unexpected \"{}\"", x)),
+        None => Ok(Some(filter)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: [u8; 4] = [10, 0, 0, 5];
+    const PEER: [u8; 4] = [192, 168, 1, 2];
+    const UNICAST: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+    const BROADCAST: [u8; 6] = [0xff; 6];
+
+    fn eth(dst: [u8; 6], ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = dst.to_vec();
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x02]);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// Insert a 802.1Q tag for vlan into frame
+    fn tagged(vlan: u16, frame: &[u8]) -> Vec<u8> {
+        let mut tagged = frame[..12].to_vec();
+        tagged.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+        tagged.extend_from_slice(&vlan.to_be_bytes());
+        tagged.extend_from_slice(&frame[12..]);
+        tagged
+    }
+
+    fn tcp_frame(src: [u8; 4], dst: [u8; 4], src_port: u16, dst_port: u16) -> Vec<u8> {
+        let mut tcp = vec![0; 20];
+        NetworkEndian::write_u16(&mut tcp[0..2], src_port);
+        NetworkEndian::write_u16(&mut tcp[2..4], dst_port);
+        // Data offset of 5 words, no options
+        tcp[12] = 0x50;
+
+        let mut ip = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 6, 0, 0];
+        NetworkEndian::write_u16(&mut ip[2..4], 40);
+        ip.extend_from_slice(&src);
+        ip.extend_from_slice(&dst);
+        ip.extend_from_slice(&tcp);
+        eth(UNICAST, 0x0800, &ip)
+    }
+
+    fn udp6_frame(src: &str, dst: &str, src_port: u16, dst_port: u16) -> Vec<u8> {
+        let mut udp = vec![0; 8];
+        NetworkEndian::write_u16(&mut udp[0..2], src_port);
+        NetworkEndian::write_u16(&mut udp[2..4], dst_port);
+        NetworkEndian::write_u16(&mut udp[4..6], 8);
+
+        let mut ip = vec![0x60, 0, 0, 0, 0, 8, 17, 64];
+        ip.extend_from_slice(src.parse::<Ipv6Address>().unwrap().as_bytes());
+        ip.extend_from_slice(dst.parse::<Ipv6Address>().unwrap().as_bytes());
+        ip.extend_from_slice(&udp);
+        eth(UNICAST, 0x86dd, &ip)
+    }
+
+    fn arp_request(sender: [u8; 4], target: [u8; 4]) -> Vec<u8> {
+        let mut arp = vec![0, 1, 0x08, 0x00, 6, 4, 0, 1];
+        arp.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x02]);
+        arp.extend_from_slice(&sender);
+        arp.extend_from_slice(&[0; 6]);
+        arp.extend_from_slice(&target);
+        eth(BROADCAST, 0x0806, &arp)
+    }
+
+    fn compile(expr: &str) -> Filter {
+        parse(expr).unwrap().unwrap()
+    }
+
+    #[test]
+    fn host_and_tcp_port() {
+        let filter = compile("host 10.0.0.5 and tcp port 443");
+        assert!(filter.matches_frame(&tcp_frame(HOST, PEER, 50000, 443)));
+        assert!(filter.matches_frame(&tcp_frame(PEER, HOST, 443, 50000)));
+        assert!(!filter.matches_frame(&tcp_frame(HOST, PEER, 50000, 80)));
+        assert!(!filter.matches_frame(&tcp_frame(PEER, [10, 0, 0, 6], 50000, 443)));
+        assert!(!filter.matches_frame(&arp_request(HOST, PEER)));
+    }
+
+    #[test]
+    fn arp() {
+        let filter = compile("arp");
+        assert!(filter.matches_frame(&arp_request(HOST, PEER)));
+        assert!(!filter.matches_frame(&tcp_frame(HOST, PEER, 50000, 443)));
+        // ARP packets match hosts by sender and target
+        assert!(compile("arp and dst host 192.168.1.2").matches_frame(&arp_request(HOST, PEER)));
+    }
+
+    #[test]
+    fn not_broadcast() {
+        let filter = compile("not broadcast");
+        assert!(!filter.matches_frame(&arp_request(HOST, PEER)));
+        assert!(filter.matches_frame(&tcp_frame(HOST, PEER, 50000, 443)));
+        assert_eq!(filter, Filter::Not(Box::new(Filter::Broadcast)));
+    }
+
+    #[test]
+    fn vlan_id() {
+        let filter = compile("vlan 20");
+        let frame = tcp_frame(HOST, PEER, 50000, 443);
+        assert!(filter.matches_frame(&tagged(20, &frame)));
+        assert!(!filter.matches_frame(&tagged(30, &frame)));
+        assert!(!filter.matches_frame(&frame));
+        assert!(compile("vlan").matches_frame(&tagged(30, &frame)));
+        // The fields behind the tag are still compared
+        assert!(compile("vlan 20 and tcp port 443").matches_frame(&tagged(20, &frame)));
+    }
+
+    #[test]
+    fn ipv6_addresses() {
+        let frame = udp6_frame("2001:db8::5", "fe80::1", 5353, 53);
+        assert!(compile("host 2001:db8::5").matches_frame(&frame));
+        assert!(compile("dst host fe80::1 and udp port 53").matches_frame(&frame));
+        assert!(!compile("src host fe80::1").matches_frame(&frame));
+        assert!(compile("net 2001:db8::/32").matches_frame(&frame));
+        assert!(!compile("net 2001:db9::/32").matches_frame(&frame));
+        // Addresses of one family never match the other
+        assert!(!compile("net 0.0.0.0/0").matches_frame(&frame));
+        assert!(!compile("net ::/0").matches_frame(&tcp_frame(HOST, PEER, 50000, 443)));
+        assert!(compile("ip6 and not ip").matches_frame(&frame));
+    }
+
+    #[test]
+    fn and_binds_closer_than_or() {
+        assert_eq!(
+            compile("arp or tcp and port 443"),
+            Filter::Or(
+                Box::new(Filter::Proto(Proto::Arp)),
+                Box::new(Filter::And(
+                    Box::new(Filter::Proto(Proto::Tcp)),
+                    Box::new(Filter::Port(Side::Any, 443)),
+                )),
+            )
+        );
+        assert_eq!(
+            compile("not arp and udp"),
+            Filter::And(
+                Box::new(Filter::Not(Box::new(Filter::Proto(Proto::Arp)))),
+                Box::new(Filter::Proto(Proto::Udp)),
+            )
+        );
+        assert_eq!(compile("! arp || tcp"), compile("not arp or tcp"));
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(
+            compile("(arp or tcp) and port 443"),
+            Filter::And(
+                Box::new(Filter::Or(
+                    Box::new(Filter::Proto(Proto::Arp)),
+                    Box::new(Filter::Proto(Proto::Tcp)),
+                )),
+                Box::new(Filter::Port(Side::Any, 443)),
+            )
+        );
+        let filter = compile("not (src host 10.0.0.5 or arp)");
+        assert!(!filter.matches_frame(&tcp_frame(HOST, PEER, 50000, 443)));
+        assert!(filter.matches_frame(&tcp_frame(PEER, HOST, 443, 50000)));
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        assert_eq!(parse(""), Ok(None));
+        assert_eq!(parse("   "), Ok(None));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("vlan 5000"),
+            Err(String::from("Invalid VLAN id 5000"))
+        );
+        assert_eq!(parse("(arp or tcp"), Err(String::from("Missing \")\"")));
+        assert_eq!(parse("arp tcp"), Err(String::from("Unexpected \"tcp\"")));
+        assert_eq!(parse("arp )"), Err(String::from("Unexpected \")\"")));
+        assert_eq!(parse("host"), Err(String::from("Unexpected end of filter")));
+        assert_eq!(
+            parse("arp and"),
+            Err(String::from("Unexpected end of filter"))
+        );
+        assert_eq!(
+            parse("port http"),
+            Err(String::from("Invalid port \"http\""))
+        );
+        assert!(parse("net 10.0.0.0").is_err());
+        assert!(parse("net 10.0.0.0/33").is_err());
+        assert!(parse("net 2001:db8::/129").is_err());
+        assert_eq!(
+            parse("host fe80::zz"),
+            Err(String::from("Invalid IPv6 address \"fe80::zz\""))
+        );
+        assert!(parse("ether host 02:00:00:00:00").is_err());
+        assert_eq!(
+            parse("foo"),
+            Err(String::from("Unknown filter term \"foo\""))
+        );
+    }
+
+    #[test]
+    fn presets_compile() {
+        for preset in PRESETS.iter() {
+            assert!(parse(preset).is_ok(), "{}", preset);
+            assert!(preset.len() <= MAX_FILTER_LEN, "{}", preset);
+        }
+    }
+
+    #[test]
+    fn presets_cycle() {
+        assert_eq!(next_preset(""), "not broadcast");
+        assert_eq!(next_preset("vlan 20"), "");
+        // An entered filter starts over with the first preset
+        assert_eq!(next_preset("host 10.0.0.5"), "");
+    }
+}
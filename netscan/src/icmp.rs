@@ -0,0 +1,120 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::time::Duration;
+use smoltcp::wire::*;
+
+/// Length of the ICMP header quoted in error messages, including ident and sequence number
+const QUOTED_ICMP_LEN: usize = 8;
+
+#[derive(Debug)]
+pub struct IcmpResponse(pub Ipv4Address, pub Duration);
+pub type IcmpResponses = Vec<IcmpResponse>;
+
+impl super::StringableVec for IcmpResponses {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.iter() {
+            ret.push(format!("{} ({})", i.0, i.1));
+        }
+        ret
+    }
+}
+
+impl super::NamedStringableVec for IcmpResponses {
+    fn to_named_string_vec(&self, names: &super::HostNames) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.iter() {
+            ret.push(format!("{} ({})", super::host_label(i.0, names), i.1));
+        }
+        ret
+    }
+}
+
+/// How a router or the destination answered a probe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HopReply {
+    TimeExceeded,
+    EchoReply,
+    /// Destination unreachable with its code
+    Unreachable(u8),
+}
+
+#[derive(Debug, Clone)]
+pub struct Hop {
+    pub ttl: u8,
+    /// Address answering the probe, None if the probe timed out
+    pub reply: Option<(Ipv4Address, HopReply, Duration)>,
+}
+pub type Trace = Vec<Hop>;
+
+impl super::StringableVec for Trace {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for hop in self.iter() {
+            match hop.reply {
+                Some((addr, HopReply::Unreachable(code), rtt)) => ret.push(format!(
+                    "{:2} {} ({}) unreachable {}",
+                    hop.ttl, addr, rtt, code
+                )),
+                Some((addr, _, rtt)) => ret.push(format!("{:2} {} ({})", hop.ttl, addr, rtt)),
+                None => ret.push(format!("{:2} *", hop.ttl)),
+            }
+        }
+        ret
+    }
+}
+
+/// Parse a frame as the answer to the echo request with ident and seq_no sent to dst
+/// Returns the address of the sender and the kind of answer
+pub fn parse_hop_reply(
+    frame: &[u8],
+    dst: Ipv4Address,
+    ident: u16,
+    seq_no: u16,
+) -> Option<(Ipv4Address, HopReply)> {
+    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+    if eth_frame.ethertype() != EthernetProtocol::Ipv4 {
+        return None;
+    }
+    let packet = Ipv4Packet::new_checked(eth_frame.payload()).ok()?;
+    if packet.protocol() != IpProtocol::Icmp {
+        return None;
+    }
+    let icmp_packet = Icmpv4Packet::new_checked(packet.payload()).ok()?;
+    let reply = match icmp_packet.msg_type() {
+        Icmpv4Message::EchoReply => {
+            if packet.src_addr() != dst
+                || icmp_packet.echo_ident() != ident
+                || icmp_packet.echo_seq_no() != seq_no
+            {
+                return None;
+            }
+            return Some((packet.src_addr(), HopReply::EchoReply));
+        }
+        Icmpv4Message::TimeExceeded => HopReply::TimeExceeded,
+        Icmpv4Message::DstUnreachable => HopReply::Unreachable(icmp_packet.msg_code()),
+        _ => return None,
+    };
+
+    // Errors quote the header of the probe and the start of its payload
+    let quoted = icmp_packet.data();
+    let quoted_packet = Ipv4Packet::new_unchecked(quoted);
+    if quoted.len() < 20 {
+        return None;
+    }
+    let header_len = quoted_packet.header_len() as usize;
+    if quoted.len() < header_len + QUOTED_ICMP_LEN
+        || quoted_packet.protocol() != IpProtocol::Icmp
+        || quoted_packet.dst_addr() != dst
+    {
+        return None;
+    }
+    let quoted_icmp = &quoted[header_len..];
+    if NetworkEndian::read_u16(&quoted_icmp[4..6]) != ident
+        || NetworkEndian::read_u16(&quoted_icmp[6..8]) != seq_no
+    {
+        return None;
+    }
+    Some((packet.src_addr(), reply))
+}
@@ -0,0 +1,549 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, Ipv4Address, Ipv6Address};
+
+use super::arp::ArpResponses;
+use super::dhcp::DhcpOffers;
+use super::icmp::{IcmpResponses, Trace};
+use super::ipv6::Ipv6Neighbors;
+use super::lldp::{LinkNeighbors, LinkProtocol};
+use super::mdns::MdnsServices;
+use super::netbios::NetbiosInfos;
+use super::oui;
+use super::services::Service;
+use super::snmp::{self, SnmpInfos};
+use super::ssdp::SsdpDevices;
+use super::{HostNames, PortScan, PortScans, StringableVec};
+
+/// Maximum number of hosts kept, the least recently seen one is evicted beyond that
+pub const MAX_HOSTS: usize = 128;
+/// Maximum number of IPv6 addresses kept per host
+const MAX_IPV6_ADDRS: usize = 8;
+
+/// Where a name or detail of a host was learned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Dns,
+    Netbios,
+    Snmp,
+    Mdns,
+    Ssdp,
+    Lldp,
+    Cdp,
+    Dhcp,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Source::Dns => "DNS",
+            Source::Netbios => "NetBIOS",
+            Source::Snmp => "SNMP",
+            Source::Mdns => "mDNS",
+            Source::Ssdp => "SSDP",
+            Source::Lldp => "LLDP",
+            Source::Cdp => "CDP",
+            Source::Dhcp => "DHCP",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Round trip times of the pings a host answered
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RttStats {
+    pub min: Option<Duration>,
+    pub max: Option<Duration>,
+    total: Duration,
+    /// Number of pings answered
+    pub count: u32,
+    /// Number of pings not answered
+    pub lost: u32,
+    pub last: Option<Instant>,
+}
+
+impl RttStats {
+    pub fn add(&mut self, rtt: Duration, timestamp: Instant) {
+        self.min = Some(self.min.map_or(rtt, |x| x.min(rtt)));
+        self.max = Some(self.max.map_or(rtt, |x| x.max(rtt)));
+        self.total += rtt;
+        self.count += 1;
+        self.last = Some(timestamp);
+    }
+
+    pub fn add_lost(&mut self, timestamp: Instant) {
+        self.lost += 1;
+        self.last = Some(timestamp);
+    }
+
+    pub fn avg(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(
+                self.total.total_millis() / u64::from(self.count),
+            ))
+        }
+    }
+}
+
+impl fmt::Display for RttStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.min, self.avg(), self.max) {
+            (Some(min), Some(avg), Some(max)) => write!(
+                f,
+                "{}/{}/{} ({} of {} answered)",
+                min,
+                avg,
+                max,
+                self.count,
+                self.count + self.lost
+            ),
+            _ => write!(f, "no answer to {} pings", self.lost),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortState {
+    Open,
+    /// Scanned without a response, either closed or filtered
+    NoResponse,
+    NotScanned,
+}
+
+/// Result of the latest port scan of one protocol
+#[derive(Debug, Default, Clone)]
+pub struct Ports {
+    pub scanned: Option<Instant>,
+    pub open: Vec<&'static Service>,
+}
+
+impl Ports {
+    pub fn state(&self, port: u16) -> PortState {
+        if self.open.iter().any(|x| x.0 == port) {
+            PortState::Open
+        } else if self.scanned.is_some() {
+            PortState::NoResponse
+        } else {
+            PortState::NotScanned
+        }
+    }
+}
+
+/// Everything the scanners found out about one address
+#[derive(Debug, Clone)]
+pub struct Host {
+    pub eth_addr: Option<EthernetAddress>,
+    pub vendor: Option<&'static str>,
+    /// IPv6 addresses neighbor discovery found for the same hardware address
+    pub ipv6_addrs: BTreeSet<Ipv6Address>,
+    pub names: BTreeMap<Source, String>,
+    /// Descriptions of the device like its model or operating system
+    pub details: BTreeMap<Source, String>,
+    pub rtt: RttStats,
+    pub tcp: Ports,
+    pub udp: Ports,
+    /// First line sent by TCP services by port
+    pub banners: BTreeMap<u16, String>,
+    /// Hops of the latest traceroute
+    pub route: Option<Trace>,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+}
+
+impl Host {
+    fn new(timestamp: Instant) -> Self {
+        Host {
+            eth_addr: None,
+            vendor: None,
+            ipv6_addrs: BTreeSet::new(),
+            names: BTreeMap::new(),
+            details: BTreeMap::new(),
+            rtt: RttStats::default(),
+            tcp: Ports::default(),
+            udp: Ports::default(),
+            banners: BTreeMap::new(),
+            route: None,
+            first_seen: timestamp,
+            last_seen: timestamp,
+        }
+    }
+
+    fn set_eth_addr(&mut self, eth_addr: EthernetAddress) {
+        self.eth_addr = Some(eth_addr);
+        self.vendor = oui::vendor(eth_addr);
+    }
+
+    fn set_name(&mut self, source: Source, name: &str) {
+        if !name.is_empty() {
+            self.names.insert(source, String::from(name));
+        }
+    }
+
+    /// The most descriptive name known, DNS names are preferred
+    pub fn name(&self) -> Option<&String> {
+        self.names.values().next()
+    }
+}
+
+impl StringableVec for Host {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        match (self.eth_addr, self.vendor) {
+            (Some(eth_addr), Some(vendor)) => ret.push(format!("MAC: {} ({})", eth_addr, vendor)),
+            (Some(eth_addr), None) => ret.push(format!("MAC: {}", eth_addr)),
+            (None, _) => ret.push(String::from("MAC: unknown")),
+        }
+        for addr in self.ipv6_addrs.iter() {
+            ret.push(format!("IPv6: {}", addr));
+        }
+        for (source, name) in self.names.iter() {
+            ret.push(format!("Name ({}): {}", source, name));
+        }
+        for (source, detail) in self.details.iter() {
+            ret.push(format!("{}: {}", source, detail));
+        }
+        if self.rtt.last.is_some() {
+            ret.push(format!("RTT: {}", self.rtt));
+        }
+        for (protocol, ports) in [("TCP", &self.tcp), ("UDP", &self.udp)].iter() {
+            match ports.scanned {
+                Some(x) if ports.open.is_empty() => {
+                    ret.push(format!("{} at {}s: no open ports", protocol, x.secs()))
+                }
+                Some(x) => {
+                    ret.push(format!("{} at {}s:", protocol, x.secs()));
+                    for service in ports.open.iter() {
+                        ret.push(format!("    {} ({})", service.0, service.1));
+                        if *protocol == "TCP" {
+                            if let Some(banner) = self.banners.get(&service.0) {
+                                ret.push(format!("        {}", banner));
+                            }
+                        }
+                    }
+                }
+                None => ret.push(format!("{}: not scanned", protocol)),
+            }
+        }
+        if let Some(ref route) = self.route {
+            ret.push(String::from("Route:"));
+            ret.extend(
+                route
+                    .to_string_vec()
+                    .into_iter()
+                    .map(|x| format!("    {}", x)),
+            );
+        }
+        ret.push(format!(
+            "Seen: {}s to {}s",
+            self.first_seen.secs(),
+            self.last_seen.secs()
+        ));
+        ret
+    }
+}
+
+/// Findings of all scanners merged per address
+#[derive(Debug, Default)]
+pub struct Inventory {
+    pub hosts: BTreeMap<Ipv4Address, Host>,
+}
+
+impl Inventory {
+    /// Return the host of addr and mark it as seen, adding it if it is new
+    fn seen(&mut self, addr: Ipv4Address, timestamp: Instant) -> &mut Host {
+        if !self.hosts.contains_key(&addr) && self.hosts.len() >= MAX_HOSTS {
+            let oldest = self
+                .hosts
+                .iter()
+                .min_by_key(|(_, x)| x.last_seen)
+                .map(|(addr, _)| *addr);
+            if let Some(oldest) = oldest {
+                self.hosts.remove(&oldest);
+            }
+        }
+        let host = self
+            .hosts
+            .entry(addr)
+            .or_insert_with(|| Host::new(timestamp));
+        // Findings may be merged again after newer ones
+        host.last_seen = host.last_seen.max(timestamp);
+        host
+    }
+
+    /// Add a target entered by the user, name is the hostname it was resolved from
+    pub fn merge_target(&mut self, addr: Ipv4Address, name: Option<&str>, timestamp: Instant) {
+        let host = self.seen(addr, timestamp);
+        if let Some(name) = name {
+            host.set_name(Source::Dns, name);
+        }
+    }
+
+    /// Merge the neighbors found by an ARP scan
+    pub fn merge_neighbors(&mut self, neighbors: &ArpResponses, timestamp: Instant) {
+        for (addr, eth_addr) in neighbors.iter() {
+            self.seen(*addr, timestamp).set_eth_addr(*eth_addr);
+        }
+    }
+
+    /// Merge the responses to pinging probed, known hosts without a response lost a ping
+    pub fn merge_pings(
+        &mut self,
+        responses: &IcmpResponses,
+        probed: &ArpResponses,
+        timestamp: Instant,
+    ) {
+        for addr in probed.keys() {
+            let rtt = responses.iter().find(|x| x.0 == *addr).map(|x| x.1);
+            self.merge_ping(*addr, rtt, timestamp);
+        }
+    }
+
+    /// Merge a single ping, None if it was not answered
+    pub fn merge_ping(&mut self, addr: Ipv4Address, rtt: Option<Duration>, timestamp: Instant) {
+        match rtt {
+            Some(x) => self.seen(addr, timestamp).rtt.add(x, timestamp),
+            None => {
+                if let Some(host) = self.hosts.get_mut(&addr) {
+                    host.rtt.add_lost(timestamp);
+                }
+            }
+        }
+    }
+
+    /// Merge a TCP port scan, replacing the ports and banners of earlier scans
+    pub fn merge_tcp(&mut self, scans: &PortScans, timestamp: Instant) {
+        for scan in scans.iter() {
+            self.merge_tcp_scan(scan, timestamp);
+        }
+    }
+
+    pub fn merge_tcp_scan(&mut self, scan: &PortScan, timestamp: Instant) {
+        if let Some(host) = self.scanned(scan.0, scan.1.is_empty(), timestamp) {
+            host.tcp = Ports {
+                scanned: Some(timestamp),
+                open: scan.1.clone(),
+            };
+            host.banners = scan.2.iter().cloned().collect();
+        }
+    }
+
+    /// Merge a UDP port scan, replacing the ports of earlier scans
+    pub fn merge_udp(&mut self, scans: &PortScans, timestamp: Instant) {
+        for scan in scans.iter() {
+            self.merge_udp_scan(scan, timestamp);
+        }
+    }
+
+    pub fn merge_udp_scan(&mut self, scan: &PortScan, timestamp: Instant) {
+        if let Some(host) = self.scanned(scan.0, scan.1.is_empty(), timestamp) {
+            host.udp = Ports {
+                scanned: Some(timestamp),
+                open: scan.1.clone(),
+            };
+        }
+    }
+
+    /// Merge a traceroute, the host is seen if it answered
+    pub fn merge_trace(&mut self, addr: Ipv4Address, trace: Trace, timestamp: Instant) {
+        let answered = trace
+            .iter()
+            .any(|hop| hop.reply.map_or(false, |(x, _, _)| x == addr));
+        if let Some(host) = self.scanned(addr, !answered, timestamp) {
+            host.route = Some(trace);
+        }
+    }
+
+    /// A host without open ports gave no sign of life, it is only updated if already known
+    fn scanned(
+        &mut self,
+        addr: Ipv4Address,
+        silent: bool,
+        timestamp: Instant,
+    ) -> Option<&mut Host> {
+        if silent {
+            self.hosts.get_mut(&addr)
+        } else {
+            Some(self.seen(addr, timestamp))
+        }
+    }
+
+    /// Merge names resolved via DNS, hosts not in the inventory are skipped
+    pub fn merge_dns_names(&mut self, names: &HostNames) {
+        for (addr, name) in names.iter() {
+            if let Some(host) = self.hosts.get_mut(addr) {
+                host.set_name(Source::Dns, name);
+            }
+        }
+    }
+
+    pub fn merge_netbios(&mut self, infos: &NetbiosInfos, timestamp: Instant) {
+        for (addr, info) in infos.iter() {
+            let host = self.seen(*addr, timestamp);
+            if let Some(name) = info.computer_name() {
+                host.set_name(Source::Netbios, name);
+            }
+            if let Some(workgroup) = info.workgroup() {
+                host.details
+                    .insert(Source::Netbios, format!("Workgroup {}", workgroup));
+            }
+            if host.eth_addr.is_none() {
+                host.set_eth_addr(info.mac);
+            }
+        }
+    }
+
+    pub fn merge_snmp(&mut self, infos: &SnmpInfos, timestamp: Instant) {
+        for (addr, info) in infos.iter() {
+            let host = self.seen(*addr, timestamp);
+            if let Some(ref name) = info.name {
+                host.set_name(Source::Snmp, name);
+            }
+            match (&info.descr, info.uptime) {
+                (Some(descr), Some(uptime)) => {
+                    let detail = format!("{}, up {}", descr, snmp::format_uptime(uptime));
+                    host.details.insert(Source::Snmp, detail);
+                }
+                (Some(descr), None) => {
+                    host.details.insert(Source::Snmp, descr.clone());
+                }
+                (None, _) => {}
+            }
+        }
+    }
+
+    pub fn merge_ssdp(&mut self, devices: &SsdpDevices, timestamp: Instant) {
+        for (addr, device) in devices.iter() {
+            let host = self.seen(*addr, timestamp);
+            if let Some(ref description) = device.description {
+                if let Some(ref name) = description.friendly_name {
+                    host.set_name(Source::Ssdp, name);
+                }
+                match (&description.manufacturer, &description.model_name) {
+                    (Some(manufacturer), Some(model)) => {
+                        let detail = format!("{} {}", manufacturer, model);
+                        host.details.insert(Source::Ssdp, detail);
+                    }
+                    (Some(x), None) | (None, Some(x)) => {
+                        host.details.insert(Source::Ssdp, x.clone());
+                    }
+                    (None, None) => {}
+                }
+            }
+            if !host.details.contains_key(&Source::Ssdp) {
+                if let Some(server) = device.responses.iter().find_map(|x| x.server.as_ref()) {
+                    host.details.insert(Source::Ssdp, server.clone());
+                }
+            }
+        }
+    }
+
+    pub fn merge_mdns(&mut self, services: &MdnsServices, timestamp: Instant) {
+        for (addr, services) in services.iter() {
+            let host = self.seen(*addr, timestamp);
+            if let Some(service) = services.first() {
+                host.set_name(Source::Mdns, service.instance_label());
+            }
+            let types: Vec<&str> = services.iter().map(|x| x.service_type.as_str()).collect();
+            if !types.is_empty() {
+                host.details.insert(Source::Mdns, types.join(", "));
+            }
+        }
+    }
+
+    /// Attach the IPv6 neighbors to the hosts with the same hardware address
+    /// Neighbors without a known IPv4 host are only listed with the IPv6 neighbors
+    pub fn merge_ipv6_neighbors(&mut self, neighbors: &Ipv6Neighbors) {
+        for host in self.hosts.values_mut() {
+            let eth_addr = match host.eth_addr {
+                Some(x) => x,
+                None => continue,
+            };
+            for (addr, _) in neighbors.iter().filter(|(_, x)| x.eth_addr == eth_addr) {
+                if host.ipv6_addrs.len() < MAX_IPV6_ADDRS {
+                    host.ipv6_addrs.insert(*addr);
+                }
+            }
+        }
+    }
+
+    /// Add the switches announcing a management address by LLDP or CDP
+    pub fn merge_link_neighbors(&mut self, neighbors: &LinkNeighbors) {
+        for neighbor in neighbors.values() {
+            let addr = match neighbor.management_address {
+                Some(x) => x,
+                None => continue,
+            };
+            let source = match neighbor.protocol {
+                LinkProtocol::Lldp => Source::Lldp,
+                LinkProtocol::Cdp => Source::Cdp,
+            };
+            let host = self.seen(addr, neighbor.last_seen);
+            if let Some(ref name) = neighbor.system_name {
+                host.set_name(source, name);
+            }
+            let mut detail: Vec<String> = Vec::new();
+            if let Some(ref port_id) = neighbor.port_id {
+                detail.push(format!("Port {}", port_id));
+            }
+            if let Some(vlan) = neighbor.vlan {
+                detail.push(format!("VLAN {}", vlan));
+            }
+            if !detail.is_empty() {
+                host.details.insert(source, detail.join(", "));
+            }
+        }
+    }
+
+    /// Add the servers that answered a DHCP audit
+    pub fn merge_dhcp_offers(&mut self, offers: &DhcpOffers, timestamp: Instant) {
+        for offer in offers.iter() {
+            let host = self.seen(offer.server, timestamp);
+            let detail = match offer.router {
+                Some(router) => format!("Server offering {}, router {}", offer.offered, router),
+                None => format!("Server offering {}", offer.offered),
+            };
+            // Relayed offers carry the hardware address of the relay agent, it is not taken over
+            host.details.insert(Source::Dhcp, detail);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.hosts.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+}
+
+impl StringableVec for Inventory {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (addr, host) in self.hosts.iter() {
+            match host.name() {
+                Some(name) => ret.push(format!("{} {}", addr, name)),
+                None => ret.push(format!("{}", addr)),
+            }
+            let mut summary: Vec<String> = Vec::new();
+            if let Some(vendor) = host.vendor {
+                summary.push(String::from(vendor));
+            }
+            if let Some(avg) = host.rtt.avg() {
+                summary.push(format!("{}", avg));
+            }
+            let open = host.tcp.open.len() + host.udp.open.len();
+            if open > 0 {
+                summary.push(format!("{} open ports", open));
+            }
+            if !summary.is_empty() {
+                ret.push(format!("    {}", summary.join(", ")));
+            }
+        }
+        ret
+    }
+}
@@ -0,0 +1,300 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::time::Instant;
+use smoltcp::wire::*;
+use smoltcp::Error;
+
+const NEXT_HEADER_HOP_BY_HOP: u8 = 0;
+const NEXT_HEADER_ROUTING: u8 = 43;
+const NEXT_HEADER_FRAGMENT: u8 = 44;
+const NEXT_HEADER_AUTH: u8 = 51;
+const NEXT_HEADER_NONE: u8 = 59;
+const NEXT_HEADER_DEST_OPTS: u8 = 60;
+const FRAGMENT_HEADER_LEN: usize = 8;
+const FRAGMENT_OFFSET_MASK: u16 = 0xfff8;
+/// Longer chains of extension headers are treated as malformed
+const MAX_EXTENSION_HEADERS: usize = 8;
+
+pub const NDP_ROUTER_SOLICIT: u8 = 133;
+pub const NDP_ROUTER_ADVERT: u8 = 134;
+pub const NDP_NEIGHBOR_SOLICIT: u8 = 135;
+pub const NDP_NEIGHBOR_ADVERT: u8 = 136;
+pub const NDP_REDIRECT: u8 = 137;
+const NDP_OPT_SOURCE_LLADDR: u8 = 1;
+const NDP_OPT_TARGET_LLADDR: u8 = 2;
+const NA_FLAG_ROUTER: u8 = 0x80;
+const NA_FLAG_SOLICITED: u8 = 0x40;
+
+/// Maximum number of IPv6 neighbors kept, the least recently seen one is evicted beyond that
+pub const MAX_NEIGHBORS: usize = 32;
+
+/// The upper layer of an IPv6 packet behind its extension headers
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpperLayer<'a> {
+    /// Extension headers in the order they were found
+    pub extensions: Vec<IpProtocol>,
+    pub protocol: IpProtocol,
+    /// None for fragments other than the first and for packets without a next header
+    pub payload: Option<&'a [u8]>,
+}
+
+/// Skip the extension headers following an IPv6 header
+pub fn upper_layer(next_header: IpProtocol, payload: &[u8]) -> Result<UpperLayer, Error> {
+    let mut upper = UpperLayer {
+        extensions: Vec::new(),
+        protocol: next_header,
+        payload: Some(payload),
+    };
+    let mut data = payload;
+    loop {
+        let kind = u8::from(upper.protocol);
+        match kind {
+            NEXT_HEADER_HOP_BY_HOP
+            | NEXT_HEADER_ROUTING
+            | NEXT_HEADER_FRAGMENT
+            | NEXT_HEADER_AUTH
+            | NEXT_HEADER_DEST_OPTS => {}
+            NEXT_HEADER_NONE => {
+                upper.payload = None;
+                return Ok(upper);
+            }
+            _ => {
+                upper.payload = Some(data);
+                return Ok(upper);
+            }
+        }
+        if data.len() < 2 {
+            return Err(Error::Truncated);
+        }
+        let len = match kind {
+            NEXT_HEADER_FRAGMENT => FRAGMENT_HEADER_LEN,
+            // The authentication header counts 4 byte units, minus 2
+            NEXT_HEADER_AUTH => (data[1] as usize + 2) * 4,
+            _ => (data[1] as usize + 1) * 8,
+        };
+        if data.len() < len {
+            return Err(Error::Truncated);
+        }
+        if upper.extensions.len() >= MAX_EXTENSION_HEADERS {
+            return Err(Error::Malformed);
+        }
+        upper.extensions.push(upper.protocol);
+        upper.protocol = IpProtocol::from(data[0]);
+        // Only the first fragment carries the upper layer header
+        if kind == NEXT_HEADER_FRAGMENT
+            && NetworkEndian::read_u16(&data[2..4]) & FRAGMENT_OFFSET_MASK != 0
+        {
+            upper.payload = None;
+            return Ok(upper);
+        }
+        data = &data[len..];
+    }
+}
+
+/// A neighbor discovery message with the fields needed to learn neighbors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NdpMessage {
+    RouterSolicit {
+        lladdr: Option<EthernetAddress>,
+    },
+    RouterAdvert {
+        lladdr: Option<EthernetAddress>,
+        /// Seconds the sender stays a default router, 0 if it is none
+        router_lifetime: u16,
+    },
+    NeighborSolicit {
+        target: Ipv6Address,
+        lladdr: Option<EthernetAddress>,
+    },
+    NeighborAdvert {
+        target: Ipv6Address,
+        lladdr: Option<EthernetAddress>,
+        router: bool,
+        solicited: bool,
+    },
+    Redirect {
+        target: Ipv6Address,
+        destination: Ipv6Address,
+    },
+}
+
+/// Return the link-layer address carried in an option of the given type
+fn ndp_lladdr(mut options: &[u8], option: u8) -> Option<EthernetAddress> {
+    while options.len() >= 8 {
+        // Option lengths are in units of 8 bytes, 0 is invalid
+        let len = options[1] as usize * 8;
+        if len == 0 || options.len() < len {
+            return None;
+        }
+        if options[0] == option {
+            return Some(EthernetAddress::from_bytes(&options[2..8]));
+        }
+        options = &options[len..];
+    }
+    None
+}
+
+/// Parse an ICMPv6 message as neighbor discovery, None for any other ICMPv6 message
+pub fn parse_ndp(icmp: &[u8]) -> Option<NdpMessage> {
+    let msg_type = *icmp.first()?;
+    let (header_len, option) = match msg_type {
+        NDP_ROUTER_SOLICIT => (8, NDP_OPT_SOURCE_LLADDR),
+        NDP_ROUTER_ADVERT => (16, NDP_OPT_SOURCE_LLADDR),
+        NDP_NEIGHBOR_SOLICIT => (24, NDP_OPT_SOURCE_LLADDR),
+        NDP_NEIGHBOR_ADVERT => (24, NDP_OPT_TARGET_LLADDR),
+        NDP_REDIRECT => (40, NDP_OPT_TARGET_LLADDR),
+        _ => return None,
+    };
+    if icmp.len() < header_len {
+        return None;
+    }
+    let lladdr = ndp_lladdr(&icmp[header_len..], option);
+    let message = match msg_type {
+        NDP_ROUTER_SOLICIT => NdpMessage::RouterSolicit { lladdr },
+        NDP_ROUTER_ADVERT => NdpMessage::RouterAdvert {
+            lladdr,
+            router_lifetime: NetworkEndian::read_u16(&icmp[6..8]),
+        },
+        NDP_NEIGHBOR_SOLICIT => NdpMessage::NeighborSolicit {
+            target: Ipv6Address::from_bytes(&icmp[8..24]),
+            lladdr,
+        },
+        NDP_NEIGHBOR_ADVERT => NdpMessage::NeighborAdvert {
+            target: Ipv6Address::from_bytes(&icmp[8..24]),
+            lladdr,
+            router: icmp[4] & NA_FLAG_ROUTER != 0,
+            solicited: icmp[4] & NA_FLAG_SOLICITED != 0,
+        },
+        _ => NdpMessage::Redirect {
+            target: Ipv6Address::from_bytes(&icmp[8..24]),
+            destination: Ipv6Address::from_bytes(&icmp[24..40]),
+        },
+    };
+    Some(message)
+}
+
+/// Return the source and the neighbor discovery message of an untagged frame
+pub fn frame_ndp(frame: &[u8]) -> Option<(Ipv6Address, EthernetAddress, NdpMessage)> {
+    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+    if eth_frame.ethertype() != EthernetProtocol::Ipv6 {
+        return None;
+    }
+    let packet = Ipv6Packet::new_checked(eth_frame.payload()).ok()?;
+    let upper = upper_layer(packet.next_header(), packet.payload()).ok()?;
+    if upper.protocol != IpProtocol::Icmpv6 {
+        return None;
+    }
+    let message = parse_ndp(upper.payload?)?;
+    Some((packet.src_addr(), eth_frame.src_addr(), message))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    pub eth_addr: EthernetAddress,
+    pub router: bool,
+    pub last_seen: Instant,
+}
+
+/// IPv6 neighbors learned from neighbor discovery, bounded to MAX_NEIGHBORS entries
+#[derive(Debug, Default)]
+pub struct Ipv6Neighbors {
+    neighbors: BTreeMap<Ipv6Address, Neighbor>,
+}
+
+impl Ipv6Neighbors {
+    fn insert(&mut self, addr: Ipv6Address, eth_addr: EthernetAddress, timestamp: Instant) {
+        if addr.is_unspecified() || addr.is_multicast() {
+            return;
+        }
+        if !self.neighbors.contains_key(&addr) && self.neighbors.len() >= MAX_NEIGHBORS {
+            let oldest = self
+                .neighbors
+                .iter()
+                .min_by_key(|(_, x)| x.last_seen)
+                .map(|(addr, _)| *addr);
+            if let Some(oldest) = oldest {
+                self.neighbors.remove(&oldest);
+            }
+        }
+        let neighbor = self.neighbors.entry(addr).or_insert(Neighbor {
+            eth_addr,
+            router: false,
+            last_seen: timestamp,
+        });
+        neighbor.eth_addr = eth_addr;
+        neighbor.last_seen = timestamp;
+    }
+
+    fn set_router(&mut self, addr: Ipv6Address, router: bool) {
+        if let Some(x) = self.neighbors.get_mut(&addr) {
+            x.router = router;
+        }
+    }
+
+    /// Learn from a message sent by src, whose frame came from src_mac
+    pub fn learn(
+        &mut self,
+        src: Ipv6Address,
+        src_mac: EthernetAddress,
+        message: &NdpMessage,
+        timestamp: Instant,
+    ) {
+        match *message {
+            NdpMessage::RouterSolicit { lladdr } | NdpMessage::NeighborSolicit { lladdr, .. } => {
+                // Duplicate address detection probes come from the unspecified address
+                if let Some(x) = lladdr {
+                    self.insert(src, x, timestamp);
+                }
+            }
+            NdpMessage::RouterAdvert {
+                lladdr,
+                router_lifetime,
+            } => {
+                self.insert(src, lladdr.unwrap_or(src_mac), timestamp);
+                self.set_router(src, router_lifetime > 0);
+            }
+            NdpMessage::NeighborAdvert {
+                target,
+                lladdr,
+                router,
+                ..
+            } => {
+                self.insert(target, lladdr.unwrap_or(src_mac), timestamp);
+                self.set_router(target, router);
+            }
+            NdpMessage::Redirect { .. } => {}
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Ipv6Address, &Neighbor)> {
+        self.neighbors.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.neighbors.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.neighbors.is_empty()
+    }
+}
+
+impl super::StringableVec for Ipv6Neighbors {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (addr, neighbor) in self.neighbors.iter() {
+            if neighbor.router {
+                ret.push(format!("{}: {} (router)", addr, neighbor.eth_addr));
+            } else {
+                ret.push(format!("{}: {}", addr, neighbor.eth_addr));
+            }
+        }
+        ret
+    }
+}
@@ -0,0 +1,175 @@
+//! Parsers, result types and exports of the network scanner that do not depend on the board
+//!
+//! Run the tests on the host with
+//! `cargo test --manifest-path netscan/Cargo.toml --target x86_64-unknown-linux-gnu`
+#![warn(clippy::all)]
+#![feature(alloc)]
+#![no_std]
+
+#[macro_use]
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use managed::ManagedSlice;
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address};
+
+use services::Service;
+
+pub mod arp;
+pub mod baseline;
+pub mod cidr;
+pub mod config;
+pub mod dhcp;
+pub mod dissect;
+pub mod dns;
+pub mod export;
+pub mod filter;
+pub mod icmp;
+pub mod inventory;
+pub mod ipv6;
+pub mod lldp;
+pub mod mdns;
+pub mod netbios;
+pub mod oui;
+pub mod pcap;
+pub mod pcapfile;
+pub mod services;
+pub mod snmp;
+pub mod ssdp;
+
+pub trait StringableVec {
+    fn to_string_vec(&self) -> Vec<String>;
+}
+
+pub trait Stringable {
+    fn to_string(&self) -> String;
+}
+
+pub type HostNames = BTreeMap<Ipv4Address, String>;
+
+pub trait NamedStringableVec {
+    fn to_named_string_vec(&self, names: &HostNames) -> Vec<String>;
+}
+
+/// Format addr followed by its hostname if it is known
+pub fn host_label(addr: Ipv4Address, names: &HostNames) -> String {
+    match names.get(&addr) {
+        Some(name) => format!("{} {}", addr, name),
+        None => format!("{}", addr),
+    }
+}
+
+/// Return the address a result line starts with, like the lines of host_label
+pub fn line_address(line: &str) -> Option<Ipv4Address> {
+    line.split(|c: char| c == ' ' || c == ':')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Convert bytes received from the network into a string the LCD font can draw
+pub fn printable(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&c| {
+            if c >= b' ' && c <= b'~' {
+                c as char
+            } else {
+                '?'
+            }
+        })
+        .collect()
+}
+
+/// Convert text received from the network like printable, but keep the line breaks
+pub fn printable_lines(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .filter(|&&c| c != b'\r')
+        .map(|&c| {
+            if c == b'\n' || (c >= b' ' && c <= b'~') {
+                c as char
+            } else {
+                '?'
+            }
+        })
+        .collect()
+}
+
+/// Open ports of an address and the first line sent by services greeting on their own
+#[derive(Debug)]
+pub struct PortScan(
+    pub Ipv4Address,
+    pub Vec<&'static Service>,
+    pub Vec<(u16, String)>,
+);
+pub type PortScans = Vec<PortScan>;
+
+impl StringableVec for Vec<&Service> {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.iter() {
+            ret.push(format!("    {} ({})", i.0, i.1));
+        }
+        ret
+    }
+}
+
+impl StringableVec for PortScans {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.iter() {
+            ret.push(format!("{}:", i.0));
+            if i.1.is_empty() {
+                ret.push(String::from("    No open ports found"));
+            } else {
+                ret.extend(i.1.to_string_vec());
+            }
+        }
+        ret
+    }
+}
+
+impl NamedStringableVec for PortScans {
+    fn to_named_string_vec(&self, names: &HostNames) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for i in self.iter() {
+            ret.push(format!("{}:", host_label(i.0, names)));
+            if i.1.is_empty() {
+                ret.push(String::from("    No open ports found"));
+            } else {
+                ret.extend(i.1.to_string_vec());
+            }
+        }
+        ret
+    }
+}
+
+/// Set device IPv4 Address
+pub fn set_ip4_address<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    addr: Ipv4Address,
+    netmask: u8,
+) where
+    DeviceT: for<'d> Device<'d>,
+{
+    iface.update_ip_addrs(|addrs| {
+        let addr = IpAddress::from(addr);
+        *addrs = ManagedSlice::from(vec![IpCidr::new(addr, netmask); 1]);
+    });
+}
+
+/// Stop using the IPv4 address and the default route, e.g. after losing an address conflict
+pub fn clear_ip4_address<'b, 'c, 'e, DeviceT>(iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>)
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    set_ip4_address(iface, Ipv4Address::UNSPECIFIED, 0);
+    iface.routes_mut().update(|routes| {
+        routes.remove(&IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0));
+    });
+}
@@ -0,0 +1,229 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+pub const ETHERTYPE_LLDP: u16 = 0x88cc;
+pub const CDP_ADDR: EthernetAddress = EthernetAddress([0x01, 0x00, 0x0c, 0xcc, 0xcc, 0xcc]);
+/// LLC/SNAP header of CDP frames: DSAP, SSAP, control, Cisco OUI and protocol id
+const CDP_SNAP_HEADER: [u8; 8] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x0c, 0x20, 0x00];
+const ETHERNET_HEADER_LEN: usize = 14;
+
+const LLDP_TLV_END: u8 = 0;
+const LLDP_TLV_CHASSIS_ID: u8 = 1;
+const LLDP_TLV_PORT_ID: u8 = 2;
+const LLDP_TLV_SYSTEM_NAME: u8 = 5;
+const LLDP_TLV_MANAGEMENT_ADDRESS: u8 = 8;
+const LLDP_TLV_ORGANIZATION: u8 = 127;
+const LLDP_SUBTYPE_MAC: u8 = 3;
+const LLDP_CHASSIS_SUBTYPE_MAC: u8 = 4;
+const LLDP_SUBTYPE_NETWORK_ADDRESS: u8 = 5;
+const IANA_FAMILY_IPV4: u8 = 1;
+const IEEE_802_1_OUI: [u8; 3] = [0x00, 0x80, 0xc2];
+const IEEE_802_1_PORT_VLAN_ID: u8 = 1;
+
+const CDP_TLV_DEVICE_ID: u16 = 0x0001;
+const CDP_TLV_ADDRESSES: u16 = 0x0002;
+const CDP_TLV_PORT_ID: u16 = 0x0003;
+const CDP_TLV_NATIVE_VLAN: u16 = 0x000a;
+const CDP_TLV_MANAGEMENT_ADDRESSES: u16 = 0x0016;
+/// Protocol type NLPID with protocol 0xcc identifies an IPv4 address
+const CDP_PROTOCOL_IPV4: [u8; 3] = [0x01, 0x01, 0xcc];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkProtocol {
+    Lldp,
+    Cdp,
+}
+
+#[derive(Debug)]
+pub struct LinkNeighbor {
+    pub protocol: LinkProtocol,
+    pub chassis_id: Option<String>,
+    pub port_id: Option<String>,
+    pub system_name: Option<String>,
+    pub management_address: Option<Ipv4Address>,
+    pub vlan: Option<u16>,
+    pub last_seen: Instant,
+}
+pub type LinkNeighbors = BTreeMap<EthernetAddress, LinkNeighbor>;
+
+impl super::StringableVec for LinkNeighbors {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (mac, neighbor) in self.iter() {
+            match neighbor.protocol {
+                LinkProtocol::Lldp => ret.push(format!("LLDP {}:", mac)),
+                LinkProtocol::Cdp => ret.push(format!("CDP {}:", mac)),
+            }
+            if let Some(ref name) = neighbor.system_name {
+                ret.push(format!("    System: {}", name));
+            }
+            if let Some(ref chassis_id) = neighbor.chassis_id {
+                ret.push(format!("    Chassis: {}", chassis_id));
+            }
+            if let Some(ref port_id) = neighbor.port_id {
+                ret.push(format!("    Port: {}", port_id));
+            }
+            if let Some(addr) = neighbor.management_address {
+                ret.push(format!("    Management: {}", addr));
+            }
+            if let Some(vlan) = neighbor.vlan {
+                ret.push(format!("    VLAN: {}", vlan));
+            }
+            ret.push(String::from(""));
+        }
+        ret
+    }
+}
+
+impl LinkNeighbor {
+    fn new(protocol: LinkProtocol, timestamp: Instant) -> Self {
+        LinkNeighbor {
+            protocol,
+            chassis_id: None,
+            port_id: None,
+            system_name: None,
+            management_address: None,
+            vlan: None,
+            last_seen: timestamp,
+        }
+    }
+}
+
+/// Check whether frame is a LLDP or CDP announcement and parse it
+/// Returns the hardware address of the announcing switch port and its information
+pub fn process_frame(frame: &[u8], timestamp: Instant) -> Option<(EthernetAddress, LinkNeighbor)> {
+    if frame.len() < ETHERNET_HEADER_LEN {
+        return None;
+    }
+    let dst = EthernetAddress::from_bytes(&frame[0..6]);
+    let src = EthernetAddress::from_bytes(&frame[6..12]);
+    let ethertype = NetworkEndian::read_u16(&frame[12..14]);
+    let payload = &frame[ETHERNET_HEADER_LEN..];
+
+    if ethertype == ETHERTYPE_LLDP {
+        parse_lldp(payload, timestamp).map(|x| (src, x))
+    } else if dst == CDP_ADDR && ethertype < 0x0600 && payload.starts_with(&CDP_SNAP_HEADER) {
+        // IEEE 802.3 frames carry the length instead of an ethertype
+        parse_cdp(&payload[CDP_SNAP_HEADER.len()..], timestamp).map(|x| (src, x))
+    } else {
+        None
+    }
+}
+
+/// Parse the TLVs of a LLDP data unit
+pub fn parse_lldp(payload: &[u8], timestamp: Instant) -> Option<LinkNeighbor> {
+    let mut neighbor = LinkNeighbor::new(LinkProtocol::Lldp, timestamp);
+    let mut rest = payload;
+    while rest.len() >= 2 {
+        let header = NetworkEndian::read_u16(&rest[0..2]);
+        let kind = (header >> 9) as u8;
+        let len = (header & 0x01ff) as usize;
+        let value = rest.get(2..2 + len)?;
+        rest = &rest[2 + len..];
+
+        match kind {
+            LLDP_TLV_END => break,
+            LLDP_TLV_CHASSIS_ID if len > 1 => {
+                neighbor.chassis_id = Some(format_id(value, LLDP_CHASSIS_SUBTYPE_MAC))
+            }
+            LLDP_TLV_PORT_ID if len > 1 => {
+                neighbor.port_id = Some(format_id(value, LLDP_SUBTYPE_MAC))
+            }
+            LLDP_TLV_SYSTEM_NAME => neighbor.system_name = Some(super::printable(value)),
+            LLDP_TLV_MANAGEMENT_ADDRESS if len > 2 => {
+                // The address length includes the address family
+                let addr_len = value[0] as usize;
+                if addr_len == 5 && len > 5 && value[1] == IANA_FAMILY_IPV4 {
+                    neighbor.management_address = Some(Ipv4Address::from_bytes(&value[2..6]));
+                }
+            }
+            LLDP_TLV_ORGANIZATION if len >= 6 => {
+                if value[0..3] == IEEE_802_1_OUI[..] && value[3] == IEEE_802_1_PORT_VLAN_ID {
+                    neighbor.vlan = Some(NetworkEndian::read_u16(&value[4..6]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if neighbor.chassis_id.is_some() || neighbor.port_id.is_some() {
+        Some(neighbor)
+    } else {
+        None
+    }
+}
+
+/// Format a chassis or port id, mac_subtype is the subtype used for hardware addresses
+fn format_id(value: &[u8], mac_subtype: u8) -> String {
+    let (subtype, id) = (value[0], &value[1..]);
+    if subtype == mac_subtype && id.len() == 6 {
+        format!("{}", EthernetAddress::from_bytes(id))
+    } else if subtype == LLDP_SUBTYPE_NETWORK_ADDRESS && id.len() == 5 && id[0] == IANA_FAMILY_IPV4
+    {
+        format!("{}", Ipv4Address::from_bytes(&id[1..]))
+    } else {
+        super::printable(id)
+    }
+}
+
+/// Parse a CDP packet following the SNAP header
+pub fn parse_cdp(payload: &[u8], timestamp: Instant) -> Option<LinkNeighbor> {
+    let mut neighbor = LinkNeighbor::new(LinkProtocol::Cdp, timestamp);
+    // Skip version, ttl and checksum
+    let mut rest = payload.get(4..)?;
+    while rest.len() >= 4 {
+        let kind = NetworkEndian::read_u16(&rest[0..2]);
+        let len = NetworkEndian::read_u16(&rest[2..4]) as usize;
+        if len < 4 {
+            break;
+        }
+        let value = rest.get(4..len)?;
+        rest = &rest[len..];
+
+        match kind {
+            CDP_TLV_DEVICE_ID => {
+                neighbor.chassis_id = Some(super::printable(value));
+                neighbor.system_name = neighbor.chassis_id.clone();
+            }
+            CDP_TLV_PORT_ID => neighbor.port_id = Some(super::printable(value)),
+            CDP_TLV_NATIVE_VLAN if len == 6 => {
+                neighbor.vlan = Some(NetworkEndian::read_u16(value));
+            }
+            CDP_TLV_ADDRESSES | CDP_TLV_MANAGEMENT_ADDRESSES => {
+                if let Some(addr) = parse_cdp_address(value) {
+                    neighbor.management_address = Some(addr);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if neighbor.chassis_id.is_some() || neighbor.port_id.is_some() {
+        Some(neighbor)
+    } else {
+        None
+    }
+}
+
+/// Return the first IPv4 address of a CDP address list
+fn parse_cdp_address(value: &[u8]) -> Option<Ipv4Address> {
+    let count = NetworkEndian::read_u32(value.get(0..4)?);
+    let mut rest = &value[4..];
+    for _ in 0..count {
+        let protocol_len = *rest.get(1)? as usize;
+        let protocol = rest.get(0..2 + protocol_len)?;
+        let addr_len =
+            NetworkEndian::read_u16(rest.get(2 + protocol_len..4 + protocol_len)?) as usize;
+        let start = 4 + protocol_len;
+        let addr = rest.get(start..start + addr_len)?;
+        if protocol == &CDP_PROTOCOL_IPV4[..] && addr_len == 4 {
+            return Some(Ipv4Address::from_bytes(addr));
+        }
+        rest = &rest[start + addr_len..];
+    }
+    None
+}
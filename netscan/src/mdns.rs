@@ -0,0 +1,46 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use smoltcp::wire::Ipv4Address;
+
+#[derive(Debug)]
+pub struct MdnsService {
+    pub instance: String,
+    pub service_type: String,
+    pub target: String,
+    pub port: u16,
+    pub txt: Vec<String>,
+}
+pub type MdnsServices = BTreeMap<Ipv4Address, Vec<MdnsService>>;
+
+impl MdnsService {
+    /// Return the instance name without the service type
+    pub fn instance_label(&self) -> &str {
+        let suffix_len = self.service_type.len() + 1;
+        if self.instance.len() > suffix_len && self.instance.ends_with(&self.service_type) {
+            &self.instance[..self.instance.len() - suffix_len]
+        } else {
+            &self.instance
+        }
+    }
+}
+
+impl super::StringableVec for MdnsServices {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (addr, services) in self.iter() {
+            ret.push(format!("{}:", addr));
+            for service in services.iter() {
+                ret.push(format!("    {}", service.instance_label()));
+                ret.push(format!(
+                    "        {} {}:{}",
+                    service.service_type, service.target, service.port
+                ));
+                for txt in service.txt.iter() {
+                    ret.push(format!("        {}", txt));
+                }
+            }
+        }
+        ret
+    }
+}
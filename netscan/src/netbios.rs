@@ -0,0 +1,157 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+
+use super::HostNames;
+
+pub const NETBIOS_NS_PORT: u16 = 137;
+const HEADER_LEN: usize = 12;
+const ENCODED_NAME_LEN: usize = 32;
+const NAME_ENTRY_LEN: usize = 18;
+const TYPE_NBSTAT: u16 = 0x0021;
+const CLASS_IN: u16 = 1;
+const FLAG_RESPONSE: u16 = 0x8000;
+const NAME_FLAG_GROUP: u16 = 0x8000;
+/// Suffix of workstation and domain/workgroup names
+const SUFFIX_WORKSTATION: u8 = 0x00;
+
+#[derive(Debug)]
+pub struct NetbiosName {
+    pub name: String,
+    pub suffix: u8,
+    pub group: bool,
+}
+
+#[derive(Debug)]
+pub struct NetbiosInfo {
+    pub names: Vec<NetbiosName>,
+    pub mac: EthernetAddress,
+}
+pub type NetbiosInfos = BTreeMap<Ipv4Address, NetbiosInfo>;
+
+impl NetbiosInfo {
+    /// Return the computer name of the node
+    pub fn computer_name(&self) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|x| !x.group && x.suffix == SUFFIX_WORKSTATION)
+            .map(|x| x.name.as_str())
+    }
+
+    /// Return the workgroup or domain the node belongs to
+    pub fn workgroup(&self) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|x| x.group && x.suffix == SUFFIX_WORKSTATION)
+            .map(|x| x.name.as_str())
+    }
+}
+
+impl super::StringableVec for NetbiosInfos {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (addr, info) in self.iter() {
+            ret.push(format!("{}:", addr));
+            if let Some(name) = info.computer_name() {
+                ret.push(format!("    Name: {}", name));
+            }
+            if let Some(workgroup) = info.workgroup() {
+                ret.push(format!("    Workgroup: {}", workgroup));
+            }
+            ret.push(format!("    MAC: {}", info.mac));
+            for name in info.names.iter() {
+                ret.push(format!(
+                    "    {:<15}<{:02x}> {}",
+                    name.name,
+                    name.suffix,
+                    if name.group { "G" } else { "U" }
+                ));
+            }
+        }
+        ret
+    }
+}
+
+/// Add the computer names of all nodes without a known hostname to names
+pub fn merge_names(infos: &NetbiosInfos, names: &mut HostNames) {
+    for (addr, info) in infos.iter() {
+        if let Some(name) = info.computer_name() {
+            names.entry(*addr).or_insert_with(|| String::from(name));
+        }
+    }
+}
+
+/// Build a node status request for the wildcard name
+pub fn emit_status_request(id: u16) -> Vec<u8> {
+    let mut buffer = vec![0; HEADER_LEN];
+    NetworkEndian::write_u16(&mut buffer[0..2], id);
+    NetworkEndian::write_u16(&mut buffer[4..6], 1);
+
+    // First level encoding of "*" padded with zeros
+    buffer.push(ENCODED_NAME_LEN as u8);
+    for i in 0..16 {
+        let c = if i == 0 { b'*' } else { 0 };
+        buffer.push(b'A' + (c >> 4));
+        buffer.push(b'A' + (c & 0x0f));
+    }
+    buffer.push(0);
+
+    let mut fields = [0; 4];
+    NetworkEndian::write_u16(&mut fields[0..2], TYPE_NBSTAT);
+    NetworkEndian::write_u16(&mut fields[2..4], CLASS_IN);
+    buffer.extend_from_slice(&fields);
+    buffer
+}
+
+/// Parse a node status response into the name table and MAC address of the node
+pub fn parse_status_response(msg: &[u8]) -> Result<(u16, NetbiosInfo), &'static str> {
+    if msg.len() < HEADER_LEN {
+        return Err("NBSTAT response truncated");
+    }
+    let id = NetworkEndian::read_u16(&msg[0..2]);
+    if NetworkEndian::read_u16(&msg[2..4]) & FLAG_RESPONSE == 0
+        || NetworkEndian::read_u16(&msg[6..8]) == 0
+    {
+        return Err("Not a NBSTAT response");
+    }
+
+    // Skip the resource record name, either encoded in full or as a pointer
+    let mut offset = HEADER_LEN;
+    loop {
+        let len = *msg.get(offset).ok_or("NBSTAT response truncated")? as usize;
+        if len & 0xc0 == 0xc0 {
+            offset += 2;
+            break;
+        }
+        offset += 1 + len;
+        if len == 0 {
+            break;
+        }
+    }
+
+    let fields = msg
+        .get(offset..offset + 10)
+        .ok_or("NBSTAT response truncated")?;
+    if NetworkEndian::read_u16(&fields[0..2]) != TYPE_NBSTAT {
+        return Err("Not a NBSTAT response");
+    }
+    let rdata = &msg[offset + 10..];
+    let (&count, rdata) = rdata.split_first().ok_or("NBSTAT response truncated")?;
+    let names_len = count as usize * NAME_ENTRY_LEN;
+    if rdata.len() < names_len + 6 {
+        return Err("NBSTAT response truncated");
+    }
+
+    let names = rdata[..names_len]
+        .chunks(NAME_ENTRY_LEN)
+        .map(|entry| NetbiosName {
+            name: String::from(super::printable(&entry[..15]).trim_end()),
+            suffix: entry[15],
+            group: NetworkEndian::read_u16(&entry[16..18]) & NAME_FLAG_GROUP != 0,
+        })
+        .collect();
+    let mac = EthernetAddress::from_bytes(&rdata[names_len..names_len + 6]);
+    Ok((id, NetbiosInfo { names, mac }))
+}
@@ -0,0 +1,66 @@
+use smoltcp::wire::EthernetAddress;
+
+/// Organizationally unique identifiers of common vendors, sorted for binary search
+pub const VENDORS: [(u32, &str); 47] = [
+    (0x00000c, "Cisco"),
+    (0x000048, "Epson"),
+    (0x00005e, "IANA"),
+    (0x000085, "Canon"),
+    (0x0000aa, "Xerox"),
+    (0x000393, "Apple"),
+    (0x00040e, "AVM"),
+    (0x000502, "Apple"),
+    (0x000569, "VMware"),
+    (0x00090f, "Fortinet"),
+    (0x00095b, "Netgear"),
+    (0x000a95, "Apple"),
+    (0x000b86, "Aruba"),
+    (0x000c29, "VMware"),
+    (0x000c42, "MikroTik"),
+    (0x000db9, "PC Engines"),
+    (0x000e58, "Sonos"),
+    (0x001132, "Synology"),
+    (0x001422, "Dell"),
+    (0x00155d, "Microsoft"),
+    (0x00163e, "Xen"),
+    (0x001788, "Philips"),
+    (0x001b21, "Intel"),
+    (0x001c14, "VMware"),
+    (0x001c42, "Parallels"),
+    (0x001d0f, "TP-Link"),
+    (0x002722, "Ubiquiti"),
+    (0x00408c, "Axis"),
+    (0x005056, "VMware"),
+    (0x008077, "Brother"),
+    (0x0080e1, "STMicro"),
+    (0x00e018, "ASUSTek"),
+    (0x00e04c, "Realtek"),
+    (0x080009, "HP"),
+    (0x080020, "Sun"),
+    (0x080027, "VirtualBox"),
+    (0x18fe34, "Espressif"),
+    (0x240ac4, "Espressif"),
+    (0x24a43c, "Ubiquiti"),
+    (0x30aea4, "Espressif"),
+    (0x3c5ab4, "Google"),
+    (0x525400, "QEMU"),
+    (0x5caafd, "Sonos"),
+    (0xb827eb, "Raspberry Pi"),
+    (0xdca632, "Raspberry Pi"),
+    (0xe45f01, "Raspberry Pi"),
+    (0xf09fc2, "Ubiquiti"),
+];
+
+/// Set in the first octet of addresses not assigned by a vendor, e.g. randomized ones
+const LOCALLY_ADMINISTERED: u8 = 0x02;
+
+/// Look up the vendor of a hardware address by its OUI
+pub fn vendor(eth_addr: EthernetAddress) -> Option<&'static str> {
+    let bytes = eth_addr.as_bytes();
+    let oui = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+    match VENDORS.binary_search_by_key(&oui, |x| x.0) {
+        Ok(i) => Some(VENDORS[i].1),
+        Err(_) if bytes[0] & LOCALLY_ADMINISTERED != 0 => Some("Locally administered"),
+        Err(_) => None,
+    }
+}
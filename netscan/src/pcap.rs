@@ -0,0 +1,144 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem;
+use smoltcp::time::Instant;
+
+use super::pcapfile::{self, CaptureSink, CapturedFrame};
+
+/// Bytes of a frame that are kept at most
+pub const SNAP_LEN: usize = 256;
+/// Heap bytes the capture holds before the oldest frames are dropped
+/// Counts the frame data and the bookkeeping of every frame
+pub const CAPTURE_BUDGET: usize = 8 * 1024;
+
+/// Heap bytes a frame holding len bytes of data takes, including its slot in the ring buffer
+fn frame_cost(len: usize) -> usize {
+    len + mem::size_of::<CapturedFrame>()
+}
+
+/// Ring buffer of the most recent frames, bounded by the number of bytes it holds
+#[derive(Debug)]
+pub struct Capture {
+    frames: VecDeque<CapturedFrame>,
+    /// Heap bytes of all frames as counted by frame_cost
+    bytes: usize,
+    budget: usize,
+    /// Number of frames that were pushed out of the buffer
+    pub dropped: usize,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Capture::new(CAPTURE_BUDGET)
+    }
+}
+
+impl Capture {
+    pub fn new(budget: usize) -> Self {
+        Capture {
+            frames: VecDeque::new(),
+            bytes: 0,
+            budget,
+            dropped: 0,
+        }
+    }
+
+    /// Store a copy of frame, dropping the oldest frames if the budget is exceeded
+    pub fn record(&mut self, frame: &[u8], timestamp: Instant) {
+        let room = self.budget.saturating_sub(frame_cost(0));
+        let data = &frame[..frame.len().min(SNAP_LEN).min(room)];
+        let cost = frame_cost(data.len());
+        while self.bytes + cost > self.budget {
+            match self.frames.pop_front() {
+                Some(x) => {
+                    self.bytes -= frame_cost(x.data.len());
+                    self.dropped += 1;
+                }
+                None => break,
+            }
+        }
+        self.bytes += cost;
+        self.frames.push_back(CapturedFrame {
+            timestamp,
+            orig_len: frame.len(),
+            data: Vec::from(data),
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.bytes = 0;
+        self.dropped = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Write all frames as a pcap file and return the number of frames written
+    pub fn write_pcap<S: CaptureSink>(&self, sink: &mut S) -> Result<usize, String> {
+        pcapfile::write_pcap(sink, SNAP_LEN as u32, self.frames.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Broadcast ARP frame of len bytes with a counting payload
+    fn frame(len: usize) -> Vec<u8> {
+        let mut frame = vec![0xff; 6];
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01, 0x08, 0x06]);
+        frame.extend((0..len - frame.len()).map(|x| x as u8));
+        frame
+    }
+
+    #[test]
+    fn write_matches_fixture() {
+        let mut capture = Capture::default();
+        capture.record(&frame(42), Instant::from_millis(1500));
+        capture.record(&frame(300), Instant::from_millis(2250));
+
+        let mut file = Vec::new();
+        assert_eq!(capture.write_pcap(&mut file), Ok(2));
+        let expected: &[u8] = include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/capture.pcap"
+        ));
+        assert_eq!(&file[..], expected);
+    }
+
+    #[test]
+    fn empty_capture_writes_header() {
+        let mut file = Vec::new();
+        assert_eq!(Capture::default().write_pcap(&mut file), Ok(0));
+        assert_eq!(file.len(), pcapfile::GLOBAL_HEADER_LEN);
+    }
+
+    #[test]
+    fn budget_counts_frame_overhead() {
+        let budget = 2 * frame_cost(100);
+        let mut capture = Capture::new(budget);
+        capture.record(&frame(100), Instant::from_millis(0));
+        capture.record(&frame(100), Instant::from_millis(1));
+        assert_eq!((capture.len(), capture.dropped), (2, 0));
+
+        // The data alone would still fit, the bookkeeping of the third frame does not
+        capture.record(&frame(14), Instant::from_millis(2));
+        assert_eq!((capture.len(), capture.dropped), (2, 1));
+        assert!(capture.bytes <= budget);
+    }
+
+    #[test]
+    fn frames_are_truncated_to_the_budget() {
+        let mut capture = Capture::new(frame_cost(20));
+        capture.record(&frame(60), Instant::from_millis(0));
+        assert_eq!(capture.frames[0].data.len(), 20);
+        assert_eq!(capture.frames[0].orig_len, 60);
+    }
+}
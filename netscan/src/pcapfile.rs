@@ -0,0 +1,70 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+use smoltcp::time::Instant;
+
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const PCAP_VERSION_MAJOR: u16 = 2;
+const PCAP_VERSION_MINOR: u16 = 4;
+const LINKTYPE_ETHERNET: u32 = 1;
+pub const GLOBAL_HEADER_LEN: usize = 24;
+pub const RECORD_HEADER_LEN: usize = 16;
+
+/// Destination a capture is written to
+pub trait CaptureSink {
+    fn write(&mut self, data: &[u8]) -> Result<(), String>;
+}
+
+impl CaptureSink for Vec<u8> {
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub timestamp: Instant,
+    /// Length of the frame on the wire, data may be truncated to the snap length
+    pub orig_len: usize,
+    pub data: Vec<u8>,
+}
+
+/// Write frames as a pcap file and return the number of frames written
+pub fn write_pcap<'a, S, I>(sink: &mut S, snap_len: u32, frames: I) -> Result<usize, String>
+where
+    S: CaptureSink,
+    I: IntoIterator<Item = &'a CapturedFrame>,
+{
+    sink.write(&global_header(snap_len))?;
+    let mut count = 0;
+    for frame in frames {
+        sink.write(&record_header(frame))?;
+        sink.write(&frame.data)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// The pcap file header for ethernet frames, written in little endian
+pub fn global_header(snap_len: u32) -> [u8; GLOBAL_HEADER_LEN] {
+    let mut header = [0; GLOBAL_HEADER_LEN];
+    LittleEndian::write_u32(&mut header[0..4], PCAP_MAGIC);
+    LittleEndian::write_u16(&mut header[4..6], PCAP_VERSION_MAJOR);
+    LittleEndian::write_u16(&mut header[6..8], PCAP_VERSION_MINOR);
+    // thiszone and sigfigs stay 0
+    LittleEndian::write_u32(&mut header[16..20], snap_len);
+    LittleEndian::write_u32(&mut header[20..24], LINKTYPE_ETHERNET);
+    header
+}
+
+/// The pcap header of a captured frame, timestamps are relative to the system start
+pub fn record_header(frame: &CapturedFrame) -> [u8; RECORD_HEADER_LEN] {
+    let mut header = [0; RECORD_HEADER_LEN];
+    let millis = frame.timestamp.total_millis().max(0);
+    LittleEndian::write_u32(&mut header[0..4], (millis / 1000) as u32);
+    LittleEndian::write_u32(&mut header[4..8], (millis % 1000 * 1000) as u32);
+    LittleEndian::write_u32(&mut header[8..12], frame.data.len() as u32);
+    LittleEndian::write_u32(&mut header[12..16], frame.orig_len as u32);
+    header
+}
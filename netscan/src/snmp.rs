@@ -0,0 +1,345 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use smoltcp::wire::Ipv4Address;
+
+use super::HostNames;
+
+pub const SNMP_PORT: u16 = 161;
+pub const DEFAULT_COMMUNITY: &str = "public";
+/// Longest community that can be entered
+pub const MAX_COMMUNITY_LEN: usize = 32;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+pub const PDU_GET_REQUEST: u8 = 0xa0;
+pub const PDU_GET_NEXT_REQUEST: u8 = 0xa1;
+const PDU_RESPONSE: u8 = 0xa2;
+
+/// Objects of the system group without the instance suffix
+const SYS_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 1];
+const SYS_UPTIME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 3];
+const SYS_CONTACT: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 4];
+const SYS_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 5];
+pub const SYSTEM_OBJECTS: [&[u32]; 4] = [SYS_DESCR, SYS_NAME, SYS_UPTIME, SYS_CONTACT];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+    V1 = 0,
+    V2c = 1,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Version::V1 => write!(f, "v1"),
+            Version::V2c => write!(f, "v2c"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    Oid(Vec<u32>),
+    IpAddress(Ipv4Address),
+    Unsigned(u64),
+    TimeTicks(u32),
+    /// noSuchObject, noSuchInstance or endOfMibView of a SNMPv2c response
+    Exception(u8),
+    Other(u8),
+}
+
+#[derive(Debug)]
+pub struct Pdu {
+    pub request_id: i32,
+    pub error_status: i64,
+    pub error_index: i64,
+    pub bindings: Vec<(Vec<u32>, Value)>,
+}
+
+#[derive(Debug, Default)]
+pub struct SystemInfo {
+    pub descr: Option<String>,
+    pub name: Option<String>,
+    /// Time since the agent was started in hundredths of a second
+    pub uptime: Option<u32>,
+    pub contact: Option<String>,
+}
+pub type SnmpInfos = BTreeMap<Ipv4Address, SystemInfo>;
+
+impl super::StringableVec for SnmpInfos {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (addr, info) in self.iter() {
+            ret.push(format!("{}:", addr));
+            if let Some(ref name) = info.name {
+                ret.push(format!("    Name: {}", name));
+            }
+            if let Some(ref descr) = info.descr {
+                ret.push(format!("    Description: {}", descr));
+            }
+            if let Some(uptime) = info.uptime {
+                ret.push(format!("    Uptime: {}", format_uptime(uptime)));
+            }
+            if let Some(ref contact) = info.contact {
+                ret.push(format!("    Contact: {}", contact));
+            }
+        }
+        ret
+    }
+}
+
+/// Format time ticks as days, hours, minutes and seconds
+pub fn format_uptime(ticks: u32) -> String {
+    let secs = ticks / 100;
+    format!(
+        "{}d {:02}:{:02}:{:02}",
+        secs / 86400,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Add the system names of all agents without a known hostname to names
+pub fn merge_names(infos: &SnmpInfos, names: &mut HostNames) {
+    for (addr, info) in infos.iter() {
+        if let Some(ref name) = info.name {
+            if !name.is_empty() {
+                names.entry(*addr).or_insert_with(|| name.clone());
+            }
+        }
+    }
+}
+
+fn emit_length(buffer: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        buffer.push(len as u8);
+    } else if len <= 0xff {
+        buffer.push(0x81);
+        buffer.push(len as u8);
+    } else {
+        buffer.push(0x82);
+        buffer.push((len >> 8) as u8);
+        buffer.push(len as u8);
+    }
+}
+
+fn emit_tlv(buffer: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    buffer.push(tag);
+    emit_length(buffer, content.len());
+    buffer.extend_from_slice(content);
+}
+
+/// Encode value in the minimal number of two's complement bytes
+fn emit_integer(buffer: &mut Vec<u8>, value: i64) {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    emit_tlv(buffer, TAG_INTEGER, &bytes[start..]);
+}
+
+fn emit_oid(buffer: &mut Vec<u8>, oid: &[u32]) {
+    let mut content = Vec::new();
+    if oid.len() >= 2 {
+        content.push((oid[0] * 40 + oid[1]) as u8);
+    }
+    for &arc in oid.iter().skip(2) {
+        // Base 128 with the high bit set on all but the last byte
+        let mut shift = 28;
+        while shift > 0 && arc >> shift == 0 {
+            shift -= 7;
+        }
+        while shift > 0 {
+            content.push(0x80 | ((arc >> shift) & 0x7f) as u8);
+            shift -= 7;
+        }
+        content.push((arc & 0x7f) as u8);
+    }
+    emit_tlv(buffer, TAG_OID, &content);
+}
+
+/// Build a request of type pdu_type asking for the values of all oids
+pub fn emit_request(
+    version: Version,
+    community: &str,
+    pdu_type: u8,
+    request_id: i32,
+    oids: &[&[u32]],
+) -> Vec<u8> {
+    let mut bindings = Vec::new();
+    for oid in oids.iter() {
+        let mut binding = Vec::new();
+        emit_oid(&mut binding, oid);
+        emit_tlv(&mut binding, TAG_NULL, &[]);
+        emit_tlv(&mut bindings, TAG_SEQUENCE, &binding);
+    }
+
+    let mut pdu = Vec::new();
+    emit_integer(&mut pdu, request_id as i64);
+    emit_integer(&mut pdu, 0);
+    emit_integer(&mut pdu, 0);
+    emit_tlv(&mut pdu, TAG_SEQUENCE, &bindings);
+
+    let mut message = Vec::new();
+    emit_integer(&mut message, version as i64);
+    emit_tlv(&mut message, TAG_OCTET_STRING, community.as_bytes());
+    emit_tlv(&mut message, pdu_type, &pdu);
+
+    let mut buffer = Vec::new();
+    emit_tlv(&mut buffer, TAG_SEQUENCE, &message);
+    buffer
+}
+
+/// Split data into the tag and content of its first element and the remaining data
+fn parse_tlv(data: &[u8]) -> Result<(u8, &[u8], &[u8]), &'static str> {
+    let tag = *data.get(0).ok_or("BER element truncated")?;
+    let first = *data.get(1).ok_or("BER element truncated")? as usize;
+    let (len, offset) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 2 {
+            return Err("Unsupported BER length");
+        }
+        let bytes = data.get(2..2 + count).ok_or("BER element truncated")?;
+        (
+            bytes.iter().fold(0, |len, &x| (len << 8) | x as usize),
+            2 + count,
+        )
+    };
+    let content = data
+        .get(offset..offset + len)
+        .ok_or("BER element truncated")?;
+    Ok((tag, content, &data[offset + len..]))
+}
+
+fn parse_integer(content: &[u8]) -> Result<i64, &'static str> {
+    if content.is_empty() || content.len() > 8 {
+        return Err("Invalid BER integer");
+    }
+    let init = if content[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(content
+        .iter()
+        .fold(init, |value, &x| (value << 8) | x as i64))
+}
+
+fn parse_unsigned(content: &[u8]) -> Result<u64, &'static str> {
+    // Values with the high bit set carry a leading zero byte
+    let content = match content.split_first() {
+        Some((&0, rest)) => rest,
+        _ => content,
+    };
+    if content.len() > 8 {
+        return Err("Invalid BER integer");
+    }
+    Ok(content.iter().fold(0, |value, &x| (value << 8) | x as u64))
+}
+
+fn parse_oid(content: &[u8]) -> Result<Vec<u32>, &'static str> {
+    let (&first, rest) = content.split_first().ok_or("Invalid BER OID")?;
+    let mut oid = vec![first as u32 / 40, first as u32 % 40];
+    let mut arc: u32 = 0;
+    for &x in rest.iter() {
+        arc = (arc << 7) | (x & 0x7f) as u32;
+        if x & 0x80 == 0 {
+            oid.push(arc);
+            arc = 0;
+        }
+    }
+    Ok(oid)
+}
+
+fn parse_value(tag: u8, content: &[u8]) -> Result<Value, &'static str> {
+    Ok(match tag {
+        TAG_INTEGER => Value::Integer(parse_integer(content)?),
+        TAG_OCTET_STRING => Value::OctetString(Vec::from(content)),
+        TAG_NULL => Value::Null,
+        TAG_OID => Value::Oid(parse_oid(content)?),
+        TAG_IP_ADDRESS if content.len() == 4 => Value::IpAddress(Ipv4Address::from_bytes(content)),
+        TAG_COUNTER32 | TAG_GAUGE32 | TAG_COUNTER64 => Value::Unsigned(parse_unsigned(content)?),
+        TAG_TIMETICKS => Value::TimeTicks(parse_unsigned(content)? as u32),
+        TAG_NO_SUCH_OBJECT..=TAG_END_OF_MIB_VIEW => Value::Exception(tag),
+        _ => Value::Other(tag),
+    })
+}
+
+/// Parse a SNMP response message into its PDU
+pub fn parse_response(msg: &[u8]) -> Result<Pdu, &'static str> {
+    let (tag, message, _) = parse_tlv(msg)?;
+    if tag != TAG_SEQUENCE {
+        return Err("Not a SNMP message");
+    }
+    // Skip version and community
+    let (_, _, rest) = parse_tlv(message)?;
+    let (_, _, rest) = parse_tlv(rest)?;
+    let (tag, pdu, _) = parse_tlv(rest)?;
+    if tag != PDU_RESPONSE {
+        return Err("Not a SNMP response");
+    }
+
+    let (_, request_id, rest) = parse_tlv(pdu)?;
+    let (_, error_status, rest) = parse_tlv(rest)?;
+    let (_, error_index, rest) = parse_tlv(rest)?;
+    let (_, mut list, _) = parse_tlv(rest)?;
+    let mut bindings = Vec::new();
+    while !list.is_empty() {
+        let (_, binding, rest) = parse_tlv(list)?;
+        list = rest;
+        let (tag, oid, rest) = parse_tlv(binding)?;
+        if tag != TAG_OID {
+            return Err("Invalid SNMP variable binding");
+        }
+        let (tag, value, _) = parse_tlv(rest)?;
+        bindings.push((parse_oid(oid)?, parse_value(tag, value)?));
+    }
+
+    Ok(Pdu {
+        request_id: parse_integer(request_id)? as i32,
+        error_status: parse_integer(error_status)?,
+        error_index: parse_integer(error_index)?,
+        bindings,
+    })
+}
+
+/// Fill info with all bindings that belong to an object of the system group
+/// Returns whether all objects were present
+pub fn process_bindings(bindings: &[(Vec<u32>, Value)], info: &mut SystemInfo) -> bool {
+    let mut found = 0;
+    for (oid, value) in bindings.iter() {
+        let object = match SYSTEM_OBJECTS.iter().find(|x| oid.starts_with(x)) {
+            Some(x) => *x,
+            None => continue,
+        };
+        match value {
+            Value::OctetString(x) if object == SYS_DESCR => info.descr = Some(super::printable(x)),
+            Value::OctetString(x) if object == SYS_NAME => info.name = Some(super::printable(x)),
+            Value::OctetString(x) if object == SYS_CONTACT => {
+                info.contact = Some(super::printable(x))
+            }
+            Value::TimeTicks(x) if object == SYS_UPTIME => info.uptime = Some(*x),
+            _ => continue,
+        }
+        found += 1;
+    }
+    found == SYSTEM_OBJECTS.len()
+}
@@ -0,0 +1,123 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use smoltcp::wire::Ipv4Address;
+
+#[derive(Debug)]
+pub struct SsdpResponse {
+    pub server: Option<String>,
+    pub st: Option<String>,
+    pub usn: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct DeviceDescription {
+    pub friendly_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model_name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct SsdpDevice {
+    pub responses: Vec<SsdpResponse>,
+    pub description: Option<DeviceDescription>,
+}
+pub type SsdpDevices = BTreeMap<Ipv4Address, SsdpDevice>;
+
+impl super::StringableVec for SsdpDevices {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (addr, device) in self.iter() {
+            ret.push(format!("{}:", addr));
+            if let Some(ref description) = device.description {
+                if let Some(ref name) = description.friendly_name {
+                    ret.push(format!("    {}", name));
+                }
+                match (&description.manufacturer, &description.model_name) {
+                    (Some(manufacturer), Some(model)) => {
+                        ret.push(format!("    {} {}", manufacturer, model))
+                    }
+                    (Some(x), None) | (None, Some(x)) => ret.push(format!("    {}", x)),
+                    (None, None) => {}
+                }
+            }
+            if let Some(server) = device.responses.iter().find_map(|x| x.server.as_ref()) {
+                ret.push(format!("    Server: {}", server));
+            }
+            if let Some(location) = device.responses.iter().find_map(|x| x.location.as_ref()) {
+                ret.push(format!("    Location: {}", location));
+            }
+            for response in device.responses.iter() {
+                if let Some(ref st) = response.st {
+                    ret.push(format!("    ST: {}", st));
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// Parse the HTTP-over-UDP response to a M-SEARCH request
+pub fn parse_response(payload: &[u8]) -> Option<SsdpResponse> {
+    let text = super::printable_lines(payload);
+    let mut lines = text.lines();
+    if !lines.next()?.starts_with("HTTP/1.1 200") {
+        return None;
+    }
+
+    let mut response = SsdpResponse {
+        server: None,
+        st: None,
+        usn: None,
+        location: None,
+    };
+    for line in lines {
+        let pos = match line.find(':') {
+            Some(x) => x,
+            None => continue,
+        };
+        let (name, value) = line.split_at(pos);
+        let value = Some(String::from(value[1..].trim()));
+        if name.eq_ignore_ascii_case("SERVER") {
+            response.server = value;
+        } else if name.eq_ignore_ascii_case("ST") {
+            response.st = value;
+        } else if name.eq_ignore_ascii_case("USN") {
+            response.usn = value;
+        } else if name.eq_ignore_ascii_case("LOCATION") {
+            response.location = value;
+        }
+    }
+    Some(response)
+}
+
+/// Split a http URL with an IPv4 host into address, port and path
+pub fn parse_url(url: &str) -> Option<(Ipv4Address, u16, &str)> {
+    let url = url.trim_start_matches("http://");
+    let (authority, path) = match url.find('/') {
+        Some(pos) => url.split_at(pos),
+        None => (url, "/"),
+    };
+    let (host, port): (&str, u16) = match authority.find(':') {
+        Some(pos) => (&authority[..pos], authority[pos + 1..].parse().ok()?),
+        None => (authority, 80),
+    };
+    Some((host.parse().ok()?, port, path))
+}
+
+/// Extract the root device information from a UPnP device description
+pub fn parse_description(document: &str) -> DeviceDescription {
+    DeviceDescription {
+        friendly_name: xml_value(document, "friendlyName"),
+        manufacturer: xml_value(document, "manufacturer"),
+        model_name: xml_value(document, "modelName"),
+    }
+}
+
+/// Return the text of the first element named tag
+fn xml_value(document: &str, tag: &str) -> Option<String> {
+    let start = document.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let len = document[start..].find("</")?;
+    Some(String::from(document[start..start + len].trim()))
+}
@@ -0,0 +1,34 @@
+use alloc::vec::Vec;
+use core::ptr;
+use stm32f7::stm32f7x6::{PWR, RCC};
+
+/// Start of the backup SRAM, its content survives resets as long as the board is powered
+const BKPSRAM_BASE: usize = 0x4002_4000;
+/// Size of the backup SRAM in bytes
+pub const BKPSRAM_SIZE: usize = 4 * 1024;
+
+/// Enable the backup SRAM and allow writing to it
+pub fn init(rcc: &mut RCC, pwr: &mut PWR) {
+    rcc.apb1enr.modify(|_, w| w.pwren().set_bit());
+    // Writes to the backup domain are ignored until they are enabled
+    pwr.cr1.modify(|_, w| w.dbp().set_bit());
+    rcc.ahb1enr.modify(|_, w| w.bkpsramen().set_bit());
+}
+
+/// Read the whole backup SRAM
+pub fn read() -> Vec<u8> {
+    (0..BKPSRAM_SIZE)
+        .map(|i| unsafe { ptr::read_volatile((BKPSRAM_BASE + i) as *const u8) })
+        .collect()
+}
+
+/// Write data to the start of the backup SRAM
+pub fn write(data: &[u8]) -> Result<(), &'static str> {
+    if data.len() > BKPSRAM_SIZE {
+        return Err("Data exceeds the backup SRAM");
+    }
+    for (i, x) in data.iter().enumerate() {
+        unsafe { ptr::write_volatile((BKPSRAM_BASE + i) as *mut u8, *x) };
+    }
+    Ok(())
+}
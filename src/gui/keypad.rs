@@ -0,0 +1,166 @@
+use super::uielement::UiElement;
+use alloc::string::String;
+use stm32f7_discovery::lcd::Color;
+use stm32f7_discovery::lcd::Framebuffer;
+use stm32f7_discovery::lcd::Layer;
+
+/// Rows of the keypad, '<' deletes the last character and 'C' clears the input
+pub const NUMERIC_KEYS: &[&str] = &["123<", "456.", "789/", "C0"];
+/// Rows of a keyboard for entering hostnames
+pub const HOSTNAME_KEYS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl-", "zxcvbnm.<C"];
+/// Rows of a keyboard for entering capture filters, the blank keys in the last row insert a space
+pub const FILTER_KEYS: &[&str] = &[
+    "1234567890",
+    "qwertyuiop",
+    "asdfghjkl",
+    "zxcvbnm./:",
+    "()      <C",
+];
+const KEY_GAP: usize = 2;
+
+// A keypad for entering addresses or names
+pub struct Keypad {
+    keys: &'static [&'static str],
+    x_pos: usize,
+    y_pos: usize,
+    key_x_size: usize,
+    key_y_size: usize,
+    background_color: Color,
+    text_color: Color,
+}
+
+impl Keypad {
+    pub fn new(x_pos: usize, y_pos: usize, key_x_size: usize, key_y_size: usize) -> Keypad {
+        Keypad::with_keys(x_pos, y_pos, key_x_size, key_y_size, NUMERIC_KEYS)
+    }
+
+    pub fn with_keys(
+        x_pos: usize,
+        y_pos: usize,
+        key_x_size: usize,
+        key_y_size: usize,
+        keys: &'static [&'static str],
+    ) -> Keypad {
+        Keypad {
+            keys: keys,
+            x_pos: x_pos,
+            y_pos: y_pos,
+            key_x_size: key_x_size,
+            key_y_size: key_y_size,
+            background_color: Color {
+                red: 0,
+                green: 0,
+                blue: 255,
+                alpha: 255,
+            },
+            text_color: Color {
+                red: 255,
+                green: 255,
+                blue: 255,
+                alpha: 255,
+            },
+        }
+    }
+}
+
+/// Apply a key of the keypad to text, which holds at most max_len characters
+pub fn apply_key(text: &mut String, key: char, max_len: usize) {
+    match key {
+        '<' => {
+            text.pop();
+        }
+        'C' => text.clear(),
+        _ => {
+            if text.len() < max_len {
+                text.push(key);
+            }
+        }
+    }
+}
+
+impl<T: Framebuffer> UiElement<T> for Keypad {
+    fn get_x_pos(&mut self) -> usize {
+        self.x_pos
+    }
+
+    fn get_y_pos(&mut self) -> usize {
+        self.y_pos
+    }
+
+    fn get_x_size(&mut self) -> usize {
+        let columns = self.keys.iter().map(|x| x.len()).max().unwrap_or(0);
+        columns * (self.key_x_size + KEY_GAP) - KEY_GAP
+    }
+
+    fn get_y_size(&mut self) -> usize {
+        self.keys.len() * (self.key_y_size + KEY_GAP) - KEY_GAP
+    }
+
+    fn get_background_color(&mut self) -> Color {
+        self.background_color
+    }
+
+    fn set_background_color(&mut self, color: Color) {
+        self.background_color = color;
+    }
+
+    fn get_text_color(&mut self) -> Color {
+        self.text_color
+    }
+
+    fn set_text_color(&mut self, color: Color) {
+        self.text_color = color;
+    }
+
+    // Return the key at the given position, touches on the gaps are ignored
+    fn key_at(&mut self, x: usize, y: usize) -> Option<char> {
+        let x = x.checked_sub(self.x_pos)?;
+        let y = y.checked_sub(self.y_pos)?;
+        if x % (self.key_x_size + KEY_GAP) >= self.key_x_size
+            || y % (self.key_y_size + KEY_GAP) >= self.key_y_size
+        {
+            return None;
+        }
+        self.keys
+            .get(y / (self.key_y_size + KEY_GAP))?
+            .chars()
+            .nth(x / (self.key_x_size + KEY_GAP))
+    }
+
+    // Draws the element on the given layer
+    fn draw(&mut self, layer: &mut Layer<T>) {
+        use font8x8::{self, UnicodeFonts};
+
+        for (row, keys) in self.keys.iter().enumerate() {
+            for (column, c) in keys.chars().enumerate() {
+                let key_x_pos = self.x_pos + column * (self.key_x_size + KEY_GAP);
+                let key_y_pos = self.y_pos + row * (self.key_y_size + KEY_GAP);
+
+                // Draw the background of the key
+                for x in key_x_pos..key_x_pos + self.key_x_size {
+                    for y in key_y_pos..key_y_pos + self.key_y_size {
+                        layer.print_point_color_at(x, y, self.background_color);
+                    }
+                }
+
+                // Draw the centered character
+                let rendered = font8x8::BASIC_FONTS
+                    .get(c)
+                    .expect("character not found in basic font");
+                let char_x_pos = key_x_pos + (self.key_x_size - 8) / 2;
+                let char_y_pos = key_y_pos + (self.key_y_size - 8) / 2;
+                for (y, byte) in rendered.iter().enumerate() {
+                    for (x, bit) in (0..8).enumerate() {
+                        if *byte & (1 << bit) != 0 {
+                            layer.print_point_color_at(
+                                char_x_pos + x,
+                                char_y_pos + y,
+                                self.text_color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
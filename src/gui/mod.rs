@@ -1,5 +1,6 @@
 pub mod buttontext;
 pub mod fuielement;
+pub mod keypad;
 pub mod scrollabletext;
 pub mod uielement;
 pub mod uistate;
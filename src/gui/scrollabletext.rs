@@ -50,6 +50,25 @@ impl ScrollableText {
             },
         }
     }
+
+    // Number of rows a line takes when drawn, long lines wrap like in draw
+    fn rows(&self, line: &str) -> usize {
+        let mut rows = 1;
+        let mut x = 0;
+        for c in line.chars() {
+            if c == '\n' {
+                rows += 1;
+                x = 0;
+                continue;
+            }
+            x += 8;
+            if x >= self.x_size - 8 {
+                rows += 1;
+                x = 0;
+            }
+        }
+        rows
+    }
 }
 
 impl<T: Framebuffer> UiElement<T> for ScrollableText {
@@ -135,6 +154,26 @@ impl<T: Framebuffer> UiElement<T> for ScrollableText {
         self.title = title;
     }
 
+    // Return the index of the line drawn at the given position, None for the title
+    fn line_at(&mut self, x: usize, y: usize) -> Option<usize> {
+        if x < self.x_pos
+            || x >= self.x_pos + self.x_size
+            || y < self.y_pos + 8
+            || y >= self.y_pos + self.y_size
+        {
+            return None;
+        }
+        let row = (y - self.y_pos - 8) / 8;
+        let mut first_row = 0;
+        for (i, line) in self.lines.iter().enumerate().skip(self.lines_start) {
+            first_row += self.rows(line);
+            if row < first_row {
+                return Some(i);
+            }
+        }
+        None
+    }
+
     // fn run_touch_func(&mut self){
     // }
 
@@ -57,4 +57,14 @@ pub trait UiElement<T: Framebuffer>: Any {
         println!("get_lines called for unimplemented struct");
         vec![]
     }
+
+    fn key_at(&mut self, _x: usize, _y: usize) -> Option<char> {
+        println!("key_at called for unimplemented struct");
+        None
+    }
+
+    fn line_at(&mut self, _x: usize, _y: usize) -> Option<usize> {
+        println!("line_at called for unimplemented struct");
+        None
+    }
 }
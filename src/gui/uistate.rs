@@ -1,5 +1,6 @@
 use super::buttontext::ButtonText;
 use super::fuielement::FUiElement;
+use super::keypad::{self, Keypad};
 use super::scrollabletext::ScrollableText;
 use super::uistates::UiStates;
 use alloc::boxed::Box;
@@ -12,6 +13,8 @@ use stm32f7_discovery::lcd::Layer;
 
 pub struct UiState {
     current_ui_state: UiStates,
+    /// Screens the back buttons return to, the most recent one last
+    history: Vec<UiStates>,
 }
 
 impl UiState {
@@ -19,6 +22,7 @@ impl UiState {
     pub fn new() -> UiState {
         UiState {
             current_ui_state: UiStates::Initialization,
+            history: Vec::new(),
         }
     }
 
@@ -27,6 +31,33 @@ impl UiState {
         self.current_ui_state
     }
 
+    // Return the UIState the back buttons lead to, the screen the current one was opened from
+    pub fn back_state(&self) -> UiStates {
+        self.history.last().cloned().unwrap_or(UiStates::Start)
+    }
+
+    // Remember the screen to return to when leaving the current UIState for new_ui_state
+    fn update_history(&mut self, new_ui_state: UiStates) {
+        match new_ui_state {
+            // Top level screens start over
+            UiStates::Initialization
+            | UiStates::Address
+            | UiStates::StaticConfig
+            | UiStates::Start => self.history.clear(),
+            x if self.history.last() == Some(&x) => {
+                self.history.pop();
+            }
+            _ => match self.current_ui_state {
+                // Input screens are done once left, nothing returns to them
+                UiStates::Target
+                | UiStates::SnmpCommunity
+                | UiStates::BaselineLabel
+                | UiStates::CaptureFilter => {}
+                x => self.history.push(x),
+            },
+        }
+    }
+
     // Change the current UIState
     pub fn change_ui_state(
         &mut self,
@@ -84,6 +115,11 @@ impl UiState {
             Box::new(ButtonText::new(30, 81, 110, 50, String::from("DHCP"))),
         );
 
+        elements.insert(
+            String::from("INIT_STATIC"),
+            Box::new(ButtonText::new(170, 81, 110, 50, String::from("Static"))),
+        );
+
         elements.insert(
             String::from("INIT_GLOBAL"),
             Box::new(ButtonText::new(310, 81, 110, 50, String::from("Global"))),
@@ -122,6 +158,68 @@ impl UiState {
             )),
         );
 
+        elements.insert(
+            String::from("INIT_LINK_LOCAL"),
+            Box::new(ButtonText::new(
+                30,
+                201,
+                110,
+                50,
+                String::from("Link-Local"),
+            )),
+        );
+
+        //StaticConfig
+        let mut static_hint: FUiElement = Box::new(ScrollableText::new(
+            5,
+            1,
+            290,
+            34,
+            vec![String::from("Enter address/prefix, gateway, DNS")],
+        ));
+        static_hint.set_background_color(Color {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 0,
+        });
+        elements.insert(String::from("StaticHint"), static_hint);
+
+        let mut static_address: FUiElement =
+            Box::new(ButtonText::new(5, 40, 290, 50, String::from("Address:")));
+        static_address.set_background_color(Color {
+            red: 0,
+            green: 0,
+            blue: 255,
+            alpha: 255,
+        });
+        elements.insert(String::from("STATIC_ADDRESS"), static_address);
+
+        elements.insert(
+            String::from("STATIC_GATEWAY"),
+            Box::new(ButtonText::new(5, 95, 290, 50, String::from("Gateway:"))),
+        );
+
+        elements.insert(
+            String::from("STATIC_DNS"),
+            Box::new(ButtonText::new(5, 150, 290, 50, String::from("DNS:"))),
+        );
+
+        elements.insert(
+            String::from("STATIC_APPLY"),
+            Box::new(ButtonText::new(5, 218, 140, 50, String::from("APPLY"))),
+        );
+
+        elements.insert(
+            String::from("STATIC_BACK"),
+            Box::new(ButtonText::new(155, 218, 140, 50, String::from("BACK"))),
+        );
+
+        elements.insert(
+            String::from("Keypad"),
+            Box::new(Keypad::new(300, 1, 42, 66)),
+        );
+
         //Start
         elements.insert(
             String::from("ScrollText"),
@@ -130,26 +228,26 @@ impl UiState {
 
         elements.insert(
             String::from("ButtonScrollUp"),
-            Box::new(ButtonText::new(310, 1, 80, 50, String::from("UP"))),
+            Box::new(ButtonText::new(310, 1, 80, 42, String::from("UP"))),
         );
 
         elements.insert(
             String::from("ButtonScrollDown"),
-            Box::new(ButtonText::new(310, 56, 80, 50, String::from("DOWN"))),
+            Box::new(ButtonText::new(310, 46, 80, 42, String::from("DOWN"))),
         );
 
         elements.insert(
             String::from("TRAFFIC"),
-            Box::new(ButtonText::new(310, 111, 80, 50, String::from("Traffic"))),
+            Box::new(ButtonText::new(310, 91, 80, 42, String::from("Traffic"))),
         );
 
         elements.insert(
             String::from("ButtonInfo"),
-            Box::new(ButtonText::new(310, 165, 80, 50, String::from("INFO"))),
+            Box::new(ButtonText::new(310, 136, 80, 42, String::from("INFO"))),
         );
 
         let mut button_kill_gateway: FUiElement =
-            Box::new(ButtonText::new(310, 220, 80, 50, String::from("KILL GW")));
+            Box::new(ButtonText::new(310, 181, 80, 42, String::from("KILL GW")));
         button_kill_gateway.set_background_color(Color {
             red: 255,
             green: 255,
@@ -158,28 +256,33 @@ impl UiState {
         });
         elements.insert(String::from("ButtonKillGateway"), button_kill_gateway);
 
+        elements.insert(
+            String::from("ButtonTools"),
+            Box::new(ButtonText::new(310, 226, 80, 42, String::from("TOOLS"))),
+        );
+
         elements.insert(
             String::from("ARP_SCAN"),
-            Box::new(ButtonText::new(395, 1, 80, 50, String::from("ARP"))),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("ARP"))),
         );
 
         elements.insert(
             String::from("ICMP"),
-            Box::new(ButtonText::new(395, 56, 80, 50, String::from("ICMP"))),
+            Box::new(ButtonText::new(395, 46, 80, 42, String::from("ICMP"))),
         );
 
         elements.insert(
             String::from("TCP_PROBE"),
-            Box::new(ButtonText::new(395, 110, 80, 50, String::from("TCP PROBE"))),
+            Box::new(ButtonText::new(395, 91, 80, 42, String::from("TCP PROBE"))),
         );
 
         elements.insert(
             String::from("UDP_PROBE"),
-            Box::new(ButtonText::new(395, 165, 80, 50, String::from("UDP PROBE"))),
+            Box::new(ButtonText::new(395, 136, 80, 42, String::from("UDP PROBE"))),
         );
 
         let mut button_kill_network: FUiElement =
-            Box::new(ButtonText::new(395, 220, 80, 50, String::from("KILL NET")));
+            Box::new(ButtonText::new(395, 181, 80, 42, String::from("KILL NET")));
         button_kill_network.set_background_color(Color {
             red: 255,
             green: 255,
@@ -188,6 +291,212 @@ impl UiState {
         });
         elements.insert(String::from("ButtonKillNetwork"), button_kill_network);
 
+        elements.insert(
+            String::from("FLOWS"),
+            Box::new(ButtonText::new(395, 226, 80, 42, String::from("FLOWS"))),
+        );
+
+        //Tools
+        elements.insert(
+            String::from("ButtonBack"),
+            Box::new(ButtonText::new(310, 226, 80, 42, String::from("BACK"))),
+        );
+
+        elements.insert(
+            String::from("DHCP_AUDIT"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("DHCP AUDIT"))),
+        );
+
+        elements.insert(
+            String::from("MDNS"),
+            Box::new(ButtonText::new(395, 46, 80, 42, String::from("MDNS"))),
+        );
+
+        elements.insert(
+            String::from("NETBIOS"),
+            Box::new(ButtonText::new(395, 91, 80, 42, String::from("NETBIOS"))),
+        );
+
+        elements.insert(
+            String::from("SSDP"),
+            Box::new(ButtonText::new(395, 136, 80, 42, String::from("SSDP"))),
+        );
+
+        elements.insert(
+            String::from("SNMP"),
+            Box::new(ButtonText::new(395, 181, 80, 42, String::from("SNMP"))),
+        );
+
+        elements.insert(
+            String::from("TARGET"),
+            Box::new(ButtonText::new(310, 91, 80, 42, String::from("TARGET"))),
+        );
+
+        elements.insert(
+            String::from("LINK"),
+            Box::new(ButtonText::new(310, 136, 80, 42, String::from("LINK"))),
+        );
+
+        elements.insert(
+            String::from("VLAN"),
+            Box::new(ButtonText::new(310, 136, 80, 42, String::from("VLAN"))),
+        );
+
+        elements.insert(
+            String::from("CAPTURE"),
+            Box::new(ButtonText::new(310, 181, 80, 42, String::from("CAPTURE"))),
+        );
+
+        elements.insert(
+            String::from("PCAP_FILE"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("PCAP FILE"))),
+        );
+
+        elements.insert(
+            String::from("PCAP_TCP"),
+            Box::new(ButtonText::new(395, 46, 80, 42, String::from("PCAP TCP"))),
+        );
+
+        elements.insert(
+            String::from("FILTER"),
+            Box::new(ButtonText::new(395, 91, 80, 42, String::from("FILTER"))),
+        );
+
+        elements.insert(
+            String::from("DNS_LOG"),
+            Box::new(ButtonText::new(395, 136, 80, 42, String::from("DNS LOG"))),
+        );
+
+        elements.insert(
+            String::from("ARP_WATCH"),
+            Box::new(ButtonText::new(395, 181, 80, 42, String::from("ARP WATCH"))),
+        );
+
+        elements.insert(
+            String::from("EXPORT"),
+            Box::new(ButtonText::new(310, 136, 80, 42, String::from("EXPORT"))),
+        );
+
+        elements.insert(
+            String::from("SAVE_BASELINE"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("SAVE BASE"))),
+        );
+
+        elements.insert(
+            String::from("RESCAN"),
+            Box::new(ButtonText::new(395, 46, 80, 42, String::from("RESCAN"))),
+        );
+
+        elements.insert(
+            String::from("DNS_NEXT"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("NEXT HOST"))),
+        );
+
+        elements.insert(
+            String::from("LIVE"),
+            Box::new(ButtonText::new(395, 226, 80, 42, String::from("LIVE"))),
+        );
+
+        elements.insert(
+            String::from("HOSTS"),
+            Box::new(ButtonText::new(395, 226, 80, 42, String::from("HOSTS"))),
+        );
+
+        elements.insert(
+            String::from("DETAILS"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("DETAILS"))),
+        );
+
+        elements.insert(
+            String::from("VLAN_NEXT"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("NEXT VLAN"))),
+        );
+
+        elements.insert(
+            String::from("HOST_PING"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("PING"))),
+        );
+
+        elements.insert(
+            String::from("HOST_TCP"),
+            Box::new(ButtonText::new(395, 46, 80, 42, String::from("TCP SCAN"))),
+        );
+
+        elements.insert(
+            String::from("HOST_UDP"),
+            Box::new(ButtonText::new(395, 91, 80, 42, String::from("UDP SCAN"))),
+        );
+
+        elements.insert(
+            String::from("HOST_TRACE"),
+            Box::new(ButtonText::new(395, 136, 80, 42, String::from("TRACE"))),
+        );
+
+        elements.insert(
+            String::from("HOST_RDNS"),
+            Box::new(ButtonText::new(395, 181, 80, 42, String::from("RDNS"))),
+        );
+
+        //Input screens, the prompts are set by the screen using them
+        let mut input_hint: FUiElement = Box::new(ScrollableText::new(5, 1, 400, 34, Vec::new()));
+        input_hint.set_background_color(Color {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 0,
+        });
+        elements.insert(String::from("InputHint"), input_hint);
+
+        let mut input_field: FUiElement = Box::new(ButtonText::new(5, 40, 400, 40, String::new()));
+        input_field.set_background_color(Color {
+            red: 0,
+            green: 0,
+            blue: 255,
+            alpha: 255,
+        });
+        elements.insert(String::from("INPUT_FIELD"), input_field);
+
+        elements.insert(
+            String::from("Keyboard"),
+            Box::new(Keypad::with_keys(5, 86, 38, 44, keypad::HOSTNAME_KEYS)),
+        );
+
+        elements.insert(
+            String::from("FilterKeyboard"),
+            Box::new(Keypad::with_keys(5, 86, 38, 35, keypad::FILTER_KEYS)),
+        );
+
+        elements.insert(
+            String::from("FILTER_PRESET"),
+            Box::new(ButtonText::new(410, 152, 65, 50, String::from("PRESET"))),
+        );
+
+        elements.insert(
+            String::from("INPUT_OK"),
+            Box::new(ButtonText::new(410, 86, 65, 50, String::from("OK"))),
+        );
+
+        elements.insert(
+            String::from("INPUT_BACK"),
+            Box::new(ButtonText::new(410, 218, 65, 50, String::from("BACK"))),
+        );
+
+        //Snmp
+        elements.insert(
+            String::from("SNMP_QUERY"),
+            Box::new(ButtonText::new(395, 1, 80, 42, String::from("QUERY"))),
+        );
+
+        elements.insert(
+            String::from("SNMP_VERSION"),
+            Box::new(ButtonText::new(395, 46, 80, 42, String::from("VERSION"))),
+        );
+
+        elements.insert(
+            String::from("SNMP_COMMUNITY"),
+            Box::new(ButtonText::new(395, 91, 80, 42, String::from("COMMUNITY"))),
+        );
+
         //elements.insert(String::from("ButtonHome"), Box::new(ButtonText::new(400, 222, 80, 50, String::from("HOME"))));
 
         if new_ui_state == UiStates::Initialization {
@@ -196,11 +505,21 @@ impl UiState {
         } else if new_ui_state == UiStates::Address {
             draw_items.push(String::from("AddressHint"));
             draw_items.push(String::from("INIT_DHCP"));
+            draw_items.push(String::from("INIT_STATIC"));
             draw_items.push(String::from("INIT_GLOBAL"));
 
             draw_items.push(String::from("INIT_10_0_0_0"));
             draw_items.push(String::from("INIT_172_16_0_0"));
             draw_items.push(String::from("INIT_192_168_0_0"));
+            draw_items.push(String::from("INIT_LINK_LOCAL"));
+        } else if new_ui_state == UiStates::StaticConfig {
+            draw_items.push(String::from("StaticHint"));
+            draw_items.push(String::from("STATIC_ADDRESS"));
+            draw_items.push(String::from("STATIC_GATEWAY"));
+            draw_items.push(String::from("STATIC_DNS"));
+            draw_items.push(String::from("STATIC_APPLY"));
+            draw_items.push(String::from("STATIC_BACK"));
+            draw_items.push(String::from("Keypad"));
         } else if new_ui_state == UiStates::Start {
             draw_items.push(String::from("ScrollText"));
 
@@ -215,6 +534,115 @@ impl UiState {
             draw_items.push(String::from("TCP_PROBE"));
             draw_items.push(String::from("UDP_PROBE"));
             draw_items.push(String::from("ButtonKillNetwork"));
+            draw_items.push(String::from("FLOWS"));
+            draw_items.push(String::from("ButtonTools"));
+        } else if new_ui_state == UiStates::Tools {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+
+            draw_items.push(String::from("DHCP_AUDIT"));
+            draw_items.push(String::from("MDNS"));
+            draw_items.push(String::from("NETBIOS"));
+            draw_items.push(String::from("SSDP"));
+            draw_items.push(String::from("SNMP"));
+            draw_items.push(String::from("TARGET"));
+            draw_items.push(String::from("EXPORT"));
+            draw_items.push(String::from("CAPTURE"));
+            draw_items.push(String::from("HOSTS"));
+        } else if new_ui_state == UiStates::Link {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+        } else if new_ui_state == UiStates::Vlan {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+            draw_items.push(String::from("VLAN_NEXT"));
+        } else if new_ui_state == UiStates::Capture {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+            draw_items.push(String::from("PCAP_FILE"));
+            draw_items.push(String::from("PCAP_TCP"));
+            draw_items.push(String::from("FILTER"));
+            draw_items.push(String::from("DNS_LOG"));
+            draw_items.push(String::from("ARP_WATCH"));
+            draw_items.push(String::from("VLAN"));
+            draw_items.push(String::from("LIVE"));
+            draw_items.push(String::from("LINK"));
+        } else if new_ui_state == UiStates::Live {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+            draw_items.push(String::from("DETAILS"));
+        } else if new_ui_state == UiStates::DnsLog {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+            draw_items.push(String::from("DNS_NEXT"));
+        } else if new_ui_state == UiStates::ArpWatch {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+            draw_items.push(String::from("SAVE_BASELINE"));
+            draw_items.push(String::from("RESCAN"));
+        } else if new_ui_state == UiStates::Inventory {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+        } else if new_ui_state == UiStates::HostDetail {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+            draw_items.push(String::from("HOST_PING"));
+            draw_items.push(String::from("HOST_TCP"));
+            draw_items.push(String::from("HOST_UDP"));
+            draw_items.push(String::from("HOST_TRACE"));
+            draw_items.push(String::from("HOST_RDNS"));
+        } else if new_ui_state == UiStates::Target
+            || new_ui_state == UiStates::SnmpCommunity
+            || new_ui_state == UiStates::BaselineLabel
+        {
+            draw_items.push(String::from("InputHint"));
+            draw_items.push(String::from("INPUT_FIELD"));
+            draw_items.push(String::from("Keyboard"));
+            draw_items.push(String::from("INPUT_OK"));
+            draw_items.push(String::from("INPUT_BACK"));
+        } else if new_ui_state == UiStates::CaptureFilter {
+            draw_items.push(String::from("InputHint"));
+            draw_items.push(String::from("INPUT_FIELD"));
+            draw_items.push(String::from("FilterKeyboard"));
+            draw_items.push(String::from("FILTER_PRESET"));
+            draw_items.push(String::from("INPUT_OK"));
+            draw_items.push(String::from("INPUT_BACK"));
+        } else if new_ui_state == UiStates::Snmp {
+            draw_items.push(String::from("ScrollText"));
+
+            draw_items.push(String::from("ButtonScrollUp"));
+            draw_items.push(String::from("ButtonScrollDown"));
+            draw_items.push(String::from("ButtonBack"));
+            draw_items.push(String::from("SNMP_QUERY"));
+            draw_items.push(String::from("SNMP_VERSION"));
+            draw_items.push(String::from("SNMP_COMMUNITY"));
         }
 
         //Clear and redraw
@@ -224,6 +652,7 @@ impl UiState {
             elements.get_mut(item).unwrap().draw(layer);
         }
 
+        self.update_history(new_ui_state);
         self.current_ui_state = new_ui_state;
     }
 }
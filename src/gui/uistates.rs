@@ -3,5 +3,20 @@
 pub enum UiStates {
     Initialization,
     Address,
+    StaticConfig,
     Start,
+    Tools,
+    Link,
+    Vlan,
+    Capture,
+    Live,
+    DnsLog,
+    ArpWatch,
+    HostDetail,
+    Inventory,
+    Target,
+    Snmp,
+    SnmpCommunity,
+    BaselineLabel,
+    CaptureFilter,
 }
@@ -4,6 +4,7 @@
 #![no_std]
 #![no_main]
 
+mod backup;
 mod gui;
 mod network;
 
@@ -21,7 +22,7 @@ extern crate smoltcp;
 use gui::fuielement::FUiElement;
 use gui::uistate::UiState;
 use gui::uistates::UiStates;
-use network::StringableVec;
+use network::{NamedStringableVec, StringableVec};
 
 use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
@@ -39,14 +40,14 @@ use smoltcp::{
     dhcp::Dhcpv4Client,
     socket::{SocketSet, UdpPacketMetadata, UdpSocketBuffer},
     time::{Duration, Instant},
-    wire::{EthernetAddress, IpCidr, Ipv4Address},
+    wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr},
 };
 use stm32f7::stm32f7x6::{CorePeripherals, Interrupt, Peripherals};
 use stm32f7_discovery::{
     ethernet,
     gpio::{GpioPort, InputPin, OutputPin},
     init,
-    lcd::{self, Color},
+    lcd::{self, Color, FramebufferArgb8888, Layer},
     random::Rng,
     system_clock::{self, Hz},
     touch,
@@ -78,6 +79,7 @@ fn main() -> ! {
     let mut ethernet_dma = Some(&mut ethernet_dma);
 
     init::init_system_clock_216mhz(&mut rcc, &mut pwr, &mut flash);
+    backup::init(&mut rcc, &mut pwr);
     init::enable_gpio_ports(&mut rcc);
 
     let gpio_a = GpioPort::new(peripherals.GPIOA);
@@ -159,8 +161,24 @@ fn main() -> ! {
     let mut ethernet_interface = None;
     let mut gateway = None;
     let mut neighbors = network::arp::ArpResponses::new();
-    let mut traffic_stats = network::eth::StatsResponses::new();
+    // Findings of all scanners per host
+    let mut inventory = network::inventory::Inventory::default();
+    // Host shown on the host detail screen
+    let mut selected_host: Option<Ipv4Address> = None;
+    let mut monitor = network::eth::Monitor::default();
     let mut got_dhcp = false;
+    // Address leased by DHCP, its server is trusted by the DHCP audit
+    let mut dhcp_lease: Option<Ipv4Address> = None;
+    let mut dhcp_audit = network::dhcp::DhcpAudit::default();
+    // Taps on the tools results toggle the trust of a DHCP server
+    let mut dhcp_audit_shown = false;
+    let mut defender: Option<network::acd::Defender> = None;
+    // Link-local address being claimed, conflicts are retried from the main loop
+    let mut link_local: Option<network::ipv4ll::Claim> = None;
+    let mut static_fields: [String; 3] = Default::default();
+    let mut static_field = 0;
+    // Text entered on the target, SNMP community or baseline label screen
+    let mut text_input = String::new();
 
     let mut previous_button_state = pins.button.get();
 
@@ -171,8 +189,34 @@ fn main() -> ! {
     let mut attack_gateway_v4_active = false;
     let mut attack_network_v4_active = false;
     let mut traffic_stats_active = false;
+    // Show the flows instead of the hosts in the traffic view
+    let mut top_talkers_active = false;
+    // Filter applied to the listeners as entered, the empty filter lets every frame pass
+    let mut filter_text = String::new();
+    // Show the field tree of a single frame instead of the live summaries
+    let mut live_details_active = false;
+    // Client whose DNS lookups are shown, None shows an overview of all clients
+    let mut dns_log_client: Option<IpAddress> = None;
+    let mut arp_watch = network::arpwatch::ArpWatch::default();
+    // A baseline saved before a reset is still in the backup SRAM
+    if let Some(baseline) = network::baseline::Baseline::from_bytes(
+        &backup::read(),
+        Instant::from_millis(system_clock::ms() as i64),
+    ) {
+        arp_watch.set_baseline(baseline);
+    }
+    // Baseline device whose label is being edited
+    let mut labeled_device: Option<EthernetAddress> = None;
+    // Scan regularly and compare the neighbors with the baseline
+    let mut rescan_active = false;
+    let mut last_rescan: Option<Instant> = None;
 
     let mut dns_servers: [Option<Ipv4Address>; 3] = [None; 3];
+    let mut dns_cache = network::dns::DnsCache::new();
+    let mut host_names = network::HostNames::new();
+    let mut snmp_community = String::from(network::snmp::DEFAULT_COMMUNITY);
+    let mut snmp_version = network::snmp::Version::V2c;
+    let mut snmp_infos = network::snmp::SnmpInfos::new();
 
     loop {
         // poll button state
@@ -250,7 +294,7 @@ fn main() -> ! {
                                 Ok(iface) => {
                                     new_ui_state = UiStates::Address;
                                     layer_2.clear();
-                                    Some(iface.into_interface())
+                                    Some(network::eth::into_interface(iface, ETH_ADDR))
                                 }
                                 Err((e, dma)) => {
                                     println!("ethernet init failed: {:?}", e);
@@ -296,9 +340,12 @@ fn main() -> ! {
                                     });
                                 if let Some(x) = config {
                                     match x.address {
-                                        Some(addr) => iface.update_ip_addrs(|addrs| {
-                                            *addrs = ManagedSlice::from(vec![addr.into(); 1]);
-                                        }),
+                                        Some(addr) => {
+                                            iface.update_ip_addrs(|addrs| {
+                                                *addrs = ManagedSlice::from(vec![addr.into(); 1]);
+                                            });
+                                            dhcp_lease = Some(addr.address());
+                                        }
                                         None => println!("DHCP Response without address"),
                                     };
                                     match x.router {
@@ -313,6 +360,7 @@ fn main() -> ! {
 
                                     layer_2.clear();
                                     got_dhcp = true;
+                                    link_local = None;
                                     new_ui_state = UiStates::Start;
                                 }
                                 if !got_dhcp && timestamp - Duration::from_secs(5) > start_timestamp
@@ -321,68 +369,245 @@ fn main() -> ! {
                                     break;
                                 }
                             }
-                        } else if item_ref == "INIT_GLOBAL" {
+                        } else if item_ref == "INIT_GLOBAL"
+                            || item_ref == "INIT_10_0_0_0"
+                            || item_ref == "INIT_172_16_0_0"
+                            || item_ref == "INIT_192_168_0_0"
+                        {
+                            let range = match item_ref.as_str() {
+                                "INIT_10_0_0_0" => Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 0), 8),
+                                "INIT_172_16_0_0" => {
+                                    Ipv4Cidr::new(Ipv4Address::new(172, 16, 0, 0), 12)
+                                }
+                                "INIT_192_168_0_0" => {
+                                    Ipv4Cidr::new(Ipv4Address::new(192, 168, 0, 0), 16)
+                                }
+                                _ => Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0),
+                            };
+                            let address_hint: &mut FUiElement =
+                                element_map.get_mut(&String::from("AddressHint")).unwrap();
+                            address_hint.set_lines(vec![String::from(
+                                "Listening for traffic to choose an address...",
+                            )]);
+                            address_hint.draw(&mut layer_1);
+
                             let iface = &mut ethernet_interface.as_mut().unwrap();
-                            let cidr = network::cidr::Ipv4Cidr::new(0x01_00_00_00, 0);
-                            for addr in cidr {
-                                let s_addr = network::cidr::to_ipv4_address(addr);
-                                match network::arp::request(&mut iface.device, ETH_ADDR, s_addr) {
-                                    Ok(x) => {
-                                        if x {
-                                            new_ui_state = UiStates::Start;
-                                            network::set_ip4_address(iface, s_addr, 0);
-                                            break;
-                                        }
+                            match network::autoconf::choose_address(
+                                &mut iface.device,
+                                ETH_ADDR,
+                                &mut rng,
+                                &range,
+                            ) {
+                                Ok(Some(selection)) => {
+                                    network::set_ip4_address(
+                                        iface,
+                                        selection.cidr.address(),
+                                        selection.cidr.prefix_len(),
+                                    );
+                                    if let Some(gw) = selection.gateway {
+                                        iface.routes_mut().add_default_ipv4_route(gw).unwrap();
+                                        gateway = Some(gw);
                                     }
-                                    Err(e) => println!("Error during ARP request: {}", e),
+                                    defender =
+                                        Some(network::acd::Defender::new(selection.cidr.address()));
+                                    link_local = None;
+                                    new_ui_state = UiStates::Start;
+                                }
+                                Ok(None) => {
+                                    address_hint.set_lines(vec![String::from(
+                                        "No traffic observed, probing for a link-local address...",
+                                    )]);
+                                    address_hint.draw(&mut layer_1);
+                                    link_local = Some(network::ipv4ll::Claim::new(&mut rng, None));
+                                }
+                                Err(x) => {
+                                    address_hint.set_lines(vec![x]);
+                                    address_hint.draw(&mut layer_1);
                                 }
                             }
-                        } else if item_ref == "INIT_10_0_0_0" {
-                            let iface = &mut ethernet_interface.as_mut().unwrap();
-                            let cidr = network::cidr::Ipv4Cidr::from_str("10.0.0.0/8").unwrap();
-                            for addr in cidr {
-                                let s_addr = network::cidr::to_ipv4_address(addr);
-                                match network::arp::request(&mut iface.device, ETH_ADDR, s_addr) {
-                                    Ok(x) => {
-                                        if x {
-                                            new_ui_state = UiStates::Start;
-                                            network::set_ip4_address(iface, s_addr, 8);
-                                            break;
-                                        }
-                                    }
-                                    Err(e) => println!("Error during ARP request: {}", e),
+                        } else if item_ref == "INIT_LINK_LOCAL" {
+                            let address_hint: &mut FUiElement =
+                                element_map.get_mut(&String::from("AddressHint")).unwrap();
+                            address_hint.set_lines(vec![String::from(
+                                "Probing for a free link-local address...",
+                            )]);
+                            address_hint.draw(&mut layer_1);
+                            link_local = Some(network::ipv4ll::Claim::new(&mut rng, None));
+                        } else if item_ref == "INIT_STATIC" {
+                            static_fields = Default::default();
+                            static_field = 0;
+                            new_ui_state = UiStates::StaticConfig;
+                        } else if item_ref == "STATIC_BACK" {
+                            new_ui_state = UiStates::Address;
+                        } else if item_ref == "Keypad" {
+                            if let Some(key) = item.key_at(touch_x, touch_y) {
+                                gui::keypad::apply_key(
+                                    &mut static_fields[static_field],
+                                    key,
+                                    network::config::MAX_FIELD_LEN,
+                                );
+                                draw_static_fields(
+                                    &mut element_map,
+                                    &mut layer_1,
+                                    &static_fields,
+                                    static_field,
+                                );
+                            }
+                        } else if item_ref == "STATIC_ADDRESS" {
+                            static_field = 0;
+                            draw_static_fields(
+                                &mut element_map,
+                                &mut layer_1,
+                                &static_fields,
+                                static_field,
+                            );
+                        } else if item_ref == "STATIC_GATEWAY" {
+                            static_field = 1;
+                            draw_static_fields(
+                                &mut element_map,
+                                &mut layer_1,
+                                &static_fields,
+                                static_field,
+                            );
+                        } else if item_ref == "STATIC_DNS" {
+                            static_field = 2;
+                            draw_static_fields(
+                                &mut element_map,
+                                &mut layer_1,
+                                &static_fields,
+                                static_field,
+                            );
+                        } else if item_ref == "STATIC_APPLY" {
+                            let result = network::config::parse_static_config(
+                                &static_fields[0],
+                                &static_fields[1],
+                                &static_fields[2],
+                            )
+                            .and_then(|config| {
+                                network::config::apply(
+                                    &mut ethernet_interface.as_mut().unwrap(),
+                                    &config,
+                                )
+                                .map(|_| config)
+                            });
+                            match result {
+                                Ok(config) => {
+                                    gateway = config.gateway;
+                                    dns_servers = [config.dns_server, None, None];
+                                    link_local = None;
+                                    new_ui_state = UiStates::Start;
+                                }
+                                Err(x) => {
+                                    let static_hint: &mut FUiElement =
+                                        element_map.get_mut(&String::from("StaticHint")).unwrap();
+                                    static_hint.set_lines(vec![String::from(x)]);
+                                    static_hint.draw(&mut layer_1);
                                 }
                             }
-                        } else if item_ref == "INIT_172_16_0_0" {
-                            let iface = &mut ethernet_interface.as_mut().unwrap();
-                            let cidr = network::cidr::Ipv4Cidr::from_str("172.16.0.0/12").unwrap();
-                            for addr in cidr {
-                                let s_addr = network::cidr::to_ipv4_address(addr);
-                                match network::arp::request(&mut iface.device, ETH_ADDR, s_addr) {
-                                    Ok(x) => {
-                                        if x {
-                                            new_ui_state = UiStates::Start;
-                                            network::set_ip4_address(iface, s_addr, 12);
-                                            break;
-                                        }
-                                    }
-                                    Err(e) => println!("Error during ARP request: {}", e),
+                        } else if item_ref == "TARGET" {
+                            text_input.clear();
+                            new_ui_state = UiStates::Target;
+                        } else if item_ref == "INPUT_BACK" {
+                            new_ui_state = current_ui_state.back_state();
+                        } else if item_ref == "Keyboard" || item_ref == "FilterKeyboard" {
+                            if let Some(key) = item.key_at(touch_x, touch_y) {
+                                let max_len = match new_ui_state {
+                                    UiStates::SnmpCommunity => network::snmp::MAX_COMMUNITY_LEN,
+                                    UiStates::BaselineLabel => network::baseline::MAX_LABEL_LEN,
+                                    UiStates::CaptureFilter => network::filter::MAX_FILTER_LEN,
+                                    _ => network::dns::MAX_TARGET_LEN,
+                                };
+                                gui::keypad::apply_key(&mut text_input, key, max_len);
+                                draw_input(
+                                    &mut element_map,
+                                    &mut layer_1,
+                                    new_ui_state,
+                                    &text_input,
+                                );
+                            }
+                        } else if item_ref == "INPUT_OK" && new_ui_state == UiStates::SnmpCommunity
+                        {
+                            if text_input.is_empty() {
+                                let input_hint: &mut FUiElement =
+                                    element_map.get_mut(&String::from("InputHint")).unwrap();
+                                input_hint.set_lines(vec![String::from(
+                                    "The community must not be empty",
+                                )]);
+                                input_hint.draw(&mut layer_1);
+                            } else {
+                                snmp_community = text_input.clone();
+                                new_ui_state = UiStates::Snmp;
+                            }
+                        } else if item_ref == "FILTER_PRESET" {
+                            text_input = String::from(network::filter::next_preset(&text_input));
+                            draw_input(&mut element_map, &mut layer_1, new_ui_state, &text_input);
+                        } else if item_ref == "INPUT_OK" && new_ui_state == UiStates::CaptureFilter
+                        {
+                            match network::filter::parse(text_input.trim()) {
+                                Ok(x) => {
+                                    monitor.filter = x;
+                                    filter_text = String::from(text_input.trim());
+                                    new_ui_state = current_ui_state.back_state();
+                                }
+                                Err(x) => {
+                                    let input_hint: &mut FUiElement =
+                                        element_map.get_mut(&String::from("InputHint")).unwrap();
+                                    input_hint.set_lines(vec![x]);
+                                    input_hint.draw(&mut layer_1);
                                 }
                             }
-                        } else if item_ref == "INIT_192_168_0_0" {
-                            let iface = &mut ethernet_interface.as_mut().unwrap();
-                            let cidr = network::cidr::Ipv4Cidr::from_str("192.168.0.0/16").unwrap();
-                            for addr in cidr {
-                                let s_addr = network::cidr::to_ipv4_address(addr);
-                                match network::arp::request(&mut iface.device, ETH_ADDR, s_addr) {
-                                    Ok(x) => {
-                                        if x {
-                                            new_ui_state = UiStates::Start;
-                                            network::set_ip4_address(iface, s_addr, 16);
-                                            break;
-                                        }
-                                    }
-                                    Err(e) => println!("Error during ARP request: {}", e),
+                        } else if item_ref == "INPUT_OK" && new_ui_state == UiStates::BaselineLabel
+                        {
+                            let mut stored = Ok(());
+                            if let (Some(baseline), Some(eth_addr)) =
+                                (arp_watch.baseline.as_mut(), labeled_device)
+                            {
+                                baseline.set_label(eth_addr, text_input.trim());
+                                stored = store_baseline(baseline);
+                            }
+                            match stored {
+                                Ok(()) => {
+                                    labeled_device = None;
+                                    new_ui_state = current_ui_state.back_state();
+                                }
+                                Err(x) => {
+                                    let input_hint: &mut FUiElement =
+                                        element_map.get_mut(&String::from("InputHint")).unwrap();
+                                    input_hint.set_lines(vec![String::from(x)]);
+                                    input_hint.draw(&mut layer_1);
+                                }
+                            }
+                        } else if item_ref == "INPUT_OK" {
+                            let input_hint: &mut FUiElement =
+                                element_map.get_mut(&String::from("InputHint")).unwrap();
+                            input_hint.set_lines(vec![format!("Resolving {}...", text_input)]);
+                            input_hint.draw(&mut layer_1);
+
+                            match network::dns::resolve_target(
+                                &mut ethernet_interface.as_mut().unwrap(),
+                                &mut rng,
+                                &mut dns_cache,
+                                &dns_servers,
+                                &text_input,
+                            ) {
+                                Ok(addr) => {
+                                    // Names are kept, addresses are shown as they are
+                                    let name = if text_input.parse::<Ipv4Address>().is_ok() {
+                                        None
+                                    } else {
+                                        Some(text_input.as_str())
+                                    };
+                                    inventory.merge_target(
+                                        addr,
+                                        name,
+                                        Instant::from_millis(system_clock::ms() as i64),
+                                    );
+                                    selected_host = Some(addr);
+                                    new_ui_state = UiStates::HostDetail;
+                                }
+                                Err(x) => {
+                                    input_hint.set_lines(vec![x]);
+                                    input_hint.draw(&mut layer_1);
                                 }
                             }
                         } else if item_ref == "ButtonScrollUp" {
@@ -411,7 +636,11 @@ fn main() -> ! {
                             let scroll_text: &mut FUiElement =
                                 element_map.get_mut(&String::from("ScrollText")).unwrap();
 
-                            scroll_text.set_title(String::from("Traffic"));
+                            if top_talkers_active {
+                                scroll_text.set_title(String::from("Top Talkers"));
+                            } else {
+                                scroll_text.set_title(String::from("Traffic"));
+                            }
 
                             let stats_button: &mut FUiElement =
                                 element_map.get_mut(&String::from("TRAFFIC")).unwrap();
@@ -430,12 +659,27 @@ fn main() -> ! {
                             if !traffic_stats_active {
                                 stats_button.set_background_color(color1);
                                 traffic_stats_active = true;
-                                traffic_stats.clear();
+                                monitor.stats.clear();
+                                monitor.totals = Default::default();
+                                monitor.flows.clear();
                             } else {
                                 traffic_stats_active = false;
                                 stats_button.set_background_color(color2);
                             }
                             stats_button.draw(&mut layer_1);
+                        } else if item_ref == "FLOWS" {
+                            top_talkers_active = !top_talkers_active;
+
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            if top_talkers_active {
+                                scroll_text.set_title(String::from("Top Talkers"));
+                                scroll_text.set_lines(monitor.flows.to_string_vec());
+                            } else {
+                                scroll_text.set_title(String::from("Traffic"));
+                                scroll_text.set_lines(traffic_lines(&monitor));
+                            }
+                            scroll_text.draw(&mut layer_1);
                         } else if item_ref == "ARP_SCAN" {
                             let scroll_text: &mut FUiElement =
                                 element_map.get_mut(&String::from("ScrollText")).unwrap();
@@ -466,6 +710,10 @@ fn main() -> ! {
                                 } else {
                                     scroll_text.set_lines(neighbors.to_string_vec());
                                 }
+                                inventory.merge_neighbors(
+                                    &neighbors,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
 
                                 scroll_text.draw(&mut layer_1);
 
@@ -476,6 +724,22 @@ fn main() -> ! {
                                         Instant::from_millis(system_clock::ms() as i64),
                                     );
                                 }
+
+                                if !neighbors.is_empty() && dns_servers.iter().any(Option::is_some)
+                                {
+                                    network::dns::resolve_ptr(
+                                        iface,
+                                        &mut rng,
+                                        &mut dns_cache,
+                                        &dns_servers,
+                                        &neighbors,
+                                    );
+                                    host_names.extend(dns_cache.hostnames());
+                                    inventory.merge_dns_names(&dns_cache.hostnames());
+                                    scroll_text
+                                        .set_lines(neighbors.to_named_string_vec(&host_names));
+                                    scroll_text.draw(&mut layer_1);
+                                }
                             } else {
                                 scroll_text.add_line(String::from(
                                     "No valid Ipv4 Address found, can't find network to scan.",
@@ -493,11 +757,18 @@ fn main() -> ! {
                                     &mut rng,
                                     &neighbors,
                                 );
+                                inventory.merge_pings(
+                                    &alive_neighbors,
+                                    &neighbors,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
                                 if alive_neighbors.is_empty() {
                                     scroll_text
                                         .add_line(String::from("No neighbors responded to pings"));
                                 } else {
-                                    scroll_text.set_lines(alive_neighbors.to_string_vec());
+                                    scroll_text.set_lines(
+                                        alive_neighbors.to_named_string_vec(&host_names),
+                                    );
                                 }
                             } else {
                                 scroll_text.set_lines(vec![String::from(
@@ -520,7 +791,11 @@ fn main() -> ! {
                                     &mut ethernet_interface.as_mut().unwrap(),
                                     &neighbors,
                                 );
-                                scroll_text.set_lines(ports.to_string_vec());
+                                inventory.merge_tcp(
+                                    &ports,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                scroll_text.set_lines(ports.to_named_string_vec(&host_names));
                             } else {
                                 scroll_text.add_line(String::from(
                                     "No neighbors to probe, perform an ARP scan first",
@@ -541,7 +816,11 @@ fn main() -> ! {
                                     &mut ethernet_interface.as_mut().unwrap(),
                                     &neighbors,
                                 );
-                                scroll_text.set_lines(ports.to_string_vec());
+                                inventory.merge_udp(
+                                    &ports,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                scroll_text.set_lines(ports.to_named_string_vec(&host_names));
                             } else {
                                 scroll_text.add_line(String::from(
                                     "No neighbors to probe, perform an ARP scan first",
@@ -583,6 +862,631 @@ fn main() -> ! {
                                 }
                             }
 
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "ScrollText"
+                            && (new_ui_state == UiStates::Start
+                                || new_ui_state == UiStates::Inventory)
+                        {
+                            // Results listing hosts open the details of the tapped one
+                            let addr = item
+                                .line_at(touch_x, touch_y)
+                                .and_then(|i| network::line_address(&item.get_lines()[i]));
+                            if let Some(addr) = addr.filter(|x| inventory.hosts.contains_key(x)) {
+                                selected_host = Some(addr);
+                                new_ui_state = UiStates::HostDetail;
+                            }
+                        } else if item_ref == "HOST_PING" {
+                            if let Some(addr) = selected_host {
+                                let scroll_text: &mut FUiElement =
+                                    element_map.get_mut(&String::from("ScrollText")).unwrap();
+                                scroll_text.add_line(String::from("Pinging..."));
+                                scroll_text.draw(&mut layer_1);
+
+                                let rtt = network::icmp::probe_v4(
+                                    &mut ethernet_interface.as_mut().unwrap(),
+                                    &mut rng,
+                                    addr,
+                                );
+                                inventory.merge_ping(
+                                    addr,
+                                    rtt,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                draw_host(&mut element_map, &mut layer_1, &inventory, addr);
+                            }
+                        } else if item_ref == "HOST_TCP" {
+                            if let Some(addr) = selected_host {
+                                let scroll_text: &mut FUiElement =
+                                    element_map.get_mut(&String::from("ScrollText")).unwrap();
+                                scroll_text.add_line(String::from("Probing TCP ports..."));
+                                scroll_text.draw(&mut layer_1);
+
+                                let scan = network::tcp::probe_address(
+                                    &mut ethernet_interface.as_mut().unwrap(),
+                                    addr,
+                                );
+                                inventory.merge_tcp_scan(
+                                    &scan,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                draw_host(&mut element_map, &mut layer_1, &inventory, addr);
+                            }
+                        } else if item_ref == "HOST_UDP" {
+                            if let Some(addr) = selected_host {
+                                let scroll_text: &mut FUiElement =
+                                    element_map.get_mut(&String::from("ScrollText")).unwrap();
+                                scroll_text.add_line(String::from("Probing UDP ports..."));
+                                scroll_text.draw(&mut layer_1);
+
+                                let scan = network::udp::probe_address(
+                                    &mut ethernet_interface.as_mut().unwrap(),
+                                    addr,
+                                );
+                                inventory.merge_udp_scan(
+                                    &scan,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                draw_host(&mut element_map, &mut layer_1, &inventory, addr);
+                            }
+                        } else if item_ref == "HOST_TRACE" {
+                            if let Some(addr) = selected_host {
+                                let scroll_text: &mut FUiElement =
+                                    element_map.get_mut(&String::from("ScrollText")).unwrap();
+                                let iface = &mut ethernet_interface.as_mut().unwrap();
+
+                                // Hosts on the link are reached directly, all others via the gateway
+                                let next_hop = match iface.ip_addrs()[0] {
+                                    IpCidr::Ipv4(cidr) if cidr.contains_addr(&addr) => {
+                                        inventory.hosts.get(&addr).and_then(|x| x.eth_addr)
+                                    }
+                                    _ => gateway.and_then(|x| neighbors.get(&x).cloned()),
+                                };
+                                match (iface.ipv4_address(), next_hop) {
+                                    (Some(src), Some(next_hop)) => {
+                                        scroll_text.add_line(String::from("Tracing route..."));
+                                        scroll_text.draw(&mut layer_1);
+
+                                        match network::icmp::traceroute(
+                                            &mut iface.device,
+                                            ETH_ADDR,
+                                            src,
+                                            next_hop,
+                                            addr,
+                                            &mut rng,
+                                        ) {
+                                            Ok(trace) => {
+                                                inventory.merge_trace(
+                                                    addr,
+                                                    trace,
+                                                    Instant::from_millis(system_clock::ms() as i64),
+                                                );
+                                                draw_host(
+                                                    &mut element_map,
+                                                    &mut layer_1,
+                                                    &inventory,
+                                                    addr,
+                                                );
+                                            }
+                                            Err(x) => {
+                                                scroll_text.add_line(x);
+                                                scroll_text.draw(&mut layer_1);
+                                            }
+                                        }
+                                    }
+                                    _ => {
+                                        scroll_text.add_line(String::from(
+                                            "Next hop unknown, perform an ARP scan first",
+                                        ));
+                                        scroll_text.draw(&mut layer_1);
+                                    }
+                                }
+                            }
+                        } else if item_ref == "HOST_RDNS" {
+                            if let Some(addr) = selected_host {
+                                let scroll_text: &mut FUiElement =
+                                    element_map.get_mut(&String::from("ScrollText")).unwrap();
+                                if dns_servers.iter().any(Option::is_some) {
+                                    scroll_text.add_line(String::from("Resolving..."));
+                                    scroll_text.draw(&mut layer_1);
+
+                                    network::dns::resolve_ptr_addrs(
+                                        &mut ethernet_interface.as_mut().unwrap(),
+                                        &mut rng,
+                                        &mut dns_cache,
+                                        &dns_servers,
+                                        &[addr],
+                                    );
+                                    host_names.extend(dns_cache.hostnames());
+                                    inventory.merge_dns_names(&dns_cache.hostnames());
+                                    draw_host(&mut element_map, &mut layer_1, &inventory, addr);
+
+                                    let timestamp = Instant::from_millis(system_clock::ms() as i64);
+                                    if let Some(None) = dns_cache.lookup_ptr(addr, timestamp) {
+                                        let scroll_text: &mut FUiElement = element_map
+                                            .get_mut(&String::from("ScrollText"))
+                                            .unwrap();
+                                        scroll_text.add_line(String::from("No reverse DNS name"));
+                                        scroll_text.draw(&mut layer_1);
+                                    }
+                                } else {
+                                    scroll_text.add_line(String::from("No DNS server configured"));
+                                    scroll_text.draw(&mut layer_1);
+                                }
+                            }
+                        } else if item_ref == "ButtonTools" {
+                            new_ui_state = UiStates::Tools;
+                        } else if item_ref == "ButtonBack" {
+                            new_ui_state = current_ui_state.back_state();
+                        } else if item_ref == "DHCP_AUDIT" {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+
+                            scroll_text.set_title(String::from("DHCP Audit"));
+                            scroll_text.set_lines(vec![String::from("Collecting DHCP offers...")]);
+                            scroll_text.draw(&mut layer_1);
+
+                            let iface = &mut ethernet_interface.as_mut().unwrap();
+                            match network::dhcp::audit(
+                                &mut iface.device,
+                                ETH_ADDR,
+                                &mut rng,
+                                Duration::from_secs(5),
+                            ) {
+                                Ok(offers) => {
+                                    inventory.merge_dhcp_offers(
+                                        &offers,
+                                        Instant::from_millis(system_clock::ms() as i64),
+                                    );
+                                    dhcp_audit.offers = offers;
+                                    dhcp_audit.lease = dhcp_lease;
+                                    if dhcp_audit.offers.is_empty() {
+                                        scroll_text
+                                            .add_line(String::from("No DHCP server responded"));
+                                    } else {
+                                        scroll_text.set_lines(dhcp_audit.to_string_vec());
+                                        dhcp_audit_shown = true;
+                                    }
+                                }
+                                Err(x) => scroll_text.add_line(x),
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "ScrollText"
+                            && new_ui_state == UiStates::Tools
+                            && dhcp_audit_shown
+                        {
+                            let server = item
+                                .line_at(touch_x, touch_y)
+                                .and_then(|i| network::line_address(&item.get_lines()[i]))
+                                .filter(|x| dhcp_audit.offers.iter().any(|o| o.server == *x));
+                            if let Some(server) = server {
+                                dhcp_audit.toggle(server);
+                                item.set_lines_no_scroll(dhcp_audit.to_string_vec());
+                                item.draw(&mut layer_1);
+                            }
+                        } else if item_ref == "MDNS" {
+                            dhcp_audit_shown = false;
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+
+                            scroll_text.set_title(String::from("mDNS Services"));
+                            scroll_text.set_lines(vec![String::from("Browsing services...")]);
+                            scroll_text.draw(&mut layer_1);
+
+                            match network::mdns::discover(&mut ethernet_interface.as_mut().unwrap())
+                            {
+                                Ok(services) => {
+                                    inventory.merge_mdns(
+                                        &services,
+                                        Instant::from_millis(system_clock::ms() as i64),
+                                    );
+                                    if services.is_empty() {
+                                        scroll_text.add_line(String::from("No services announced"));
+                                    } else {
+                                        scroll_text.set_lines(services.to_string_vec());
+                                    }
+                                }
+                                Err(x) => scroll_text.add_line(x),
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "NETBIOS" {
+                            dhcp_audit_shown = false;
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+
+                            scroll_text.set_title(String::from("NetBIOS Names"));
+
+                            if !neighbors.is_empty() {
+                                scroll_text.set_lines(vec![String::from("Querying neighbors...")]);
+                                scroll_text.draw(&mut layer_1);
+
+                                let infos = network::netbios::query_nodes(
+                                    &mut ethernet_interface.as_mut().unwrap(),
+                                    &neighbors,
+                                );
+                                network::netbios::merge_names(&infos, &mut host_names);
+                                inventory.merge_netbios(
+                                    &infos,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                if infos.is_empty() {
+                                    scroll_text.add_line(String::from(
+                                        "No neighbors responded to NetBIOS queries",
+                                    ));
+                                } else {
+                                    scroll_text.set_lines(infos.to_string_vec());
+                                }
+                            } else {
+                                scroll_text.add_line(String::from(
+                                    "No neighbors to query, perform an ARP scan first",
+                                ));
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "SSDP" {
+                            dhcp_audit_shown = false;
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+
+                            scroll_text.set_title(String::from("SSDP Devices"));
+                            scroll_text.set_lines(vec![String::from("Searching devices...")]);
+                            scroll_text.draw(&mut layer_1);
+
+                            match network::ssdp::search(
+                                &mut ethernet_interface.as_mut().unwrap(),
+                                true,
+                            ) {
+                                Ok(devices) => {
+                                    inventory.merge_ssdp(
+                                        &devices,
+                                        Instant::from_millis(system_clock::ms() as i64),
+                                    );
+                                    if devices.is_empty() {
+                                        scroll_text.add_line(String::from("No devices responded"));
+                                    } else {
+                                        scroll_text.set_lines(devices.to_string_vec());
+                                    }
+                                }
+                                Err(x) => scroll_text.add_line(x),
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "SNMP" {
+                            new_ui_state = UiStates::Snmp;
+                        } else if item_ref == "SNMP_QUERY" {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+
+                            scroll_text.set_title(String::from("SNMP System Info"));
+
+                            if !neighbors.is_empty() {
+                                scroll_text.set_lines(vec![format!(
+                                    "Querying neighbors with community \"{}\" ({})...",
+                                    snmp_community, snmp_version
+                                )]);
+                                scroll_text.draw(&mut layer_1);
+
+                                snmp_infos.extend(network::snmp::query_system(
+                                    &mut ethernet_interface.as_mut().unwrap(),
+                                    &neighbors,
+                                    &snmp_community,
+                                    snmp_version,
+                                ));
+                                network::snmp::merge_names(&snmp_infos, &mut host_names);
+                                inventory.merge_snmp(
+                                    &snmp_infos,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                if snmp_infos.is_empty() {
+                                    scroll_text.add_line(String::from(
+                                        "No neighbors responded to SNMP requests",
+                                    ));
+                                } else {
+                                    scroll_text.set_lines(snmp_infos.to_string_vec());
+                                }
+                            } else {
+                                scroll_text.add_line(String::from(
+                                    "No neighbors to query, perform an ARP scan first",
+                                ));
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "SNMP_VERSION" {
+                            snmp_version = match snmp_version {
+                                network::snmp::Version::V1 => network::snmp::Version::V2c,
+                                network::snmp::Version::V2c => network::snmp::Version::V1,
+                            };
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("SNMP System Info"));
+                            scroll_text
+                                .set_lines(snmp_settings_lines(&snmp_community, snmp_version));
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "SNMP_COMMUNITY" {
+                            text_input = snmp_community.clone();
+                            new_ui_state = UiStates::SnmpCommunity;
+                        } else if item_ref == "LINK" {
+                            new_ui_state = UiStates::Link;
+                        } else if item_ref == "CAPTURE" {
+                            new_ui_state = UiStates::Capture;
+                        } else if item_ref == "DNS_LOG" {
+                            new_ui_state = UiStates::DnsLog;
+                            dns_log_client = None;
+                        } else if item_ref == "ARP_WATCH" {
+                            new_ui_state = UiStates::ArpWatch;
+                            // Bindings of earlier scans are trusted
+                            arp_watch.gateway = gateway;
+                            arp_watch.learn_all(&neighbors);
+                            rescan_active = false;
+                        } else if item_ref == "ScrollText" && new_ui_state == UiStates::ArpWatch {
+                            let eth_addr = item
+                                .line_at(touch_x, touch_y)
+                                .and_then(|i| arp_watch.device_at(i));
+                            if let Some(eth_addr) = eth_addr {
+                                text_input = arp_watch
+                                    .baseline
+                                    .as_ref()
+                                    .and_then(|x| x.label(eth_addr))
+                                    .cloned()
+                                    .unwrap_or_default();
+                                labeled_device = Some(eth_addr);
+                                new_ui_state = UiStates::BaselineLabel;
+                            }
+                        } else if item_ref == "SAVE_BASELINE" {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            // All bindings of scans and passively seen ARP packets
+                            if arp_watch.bindings.is_empty() {
+                                scroll_text.add_line(String::from(
+                                    "No neighbors to save, perform an ARP scan first",
+                                ));
+                            } else {
+                                let baseline = network::baseline::Baseline::new(
+                                    &arp_watch.bindings,
+                                    &host_names,
+                                    Instant::from_millis(system_clock::ms() as i64),
+                                );
+                                let stored = store_baseline(&baseline);
+                                arp_watch.set_baseline(baseline);
+                                scroll_text.set_lines(arp_watch.to_string_vec());
+                                if let Err(x) = stored {
+                                    scroll_text.add_line(format!("{}, it is lost on reset", x));
+                                }
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "RESCAN" {
+                            let rescan_button: &mut FUiElement =
+                                element_map.get_mut(&String::from("RESCAN")).unwrap();
+                            if arp_watch.baseline.is_none() {
+                                let scroll_text: &mut FUiElement =
+                                    element_map.get_mut(&String::from("ScrollText")).unwrap();
+                                scroll_text.add_line(String::from("Save a baseline first"));
+                                scroll_text.draw(&mut layer_1);
+                            } else if !rescan_active {
+                                rescan_active = true;
+                                last_rescan = None;
+                                rescan_button.set_background_color(Color {
+                                    red: 0,
+                                    green: 255,
+                                    blue: 255,
+                                    alpha: 255,
+                                });
+                                rescan_button.draw(&mut layer_1);
+                            } else {
+                                rescan_active = false;
+                                rescan_button.set_background_color(Color {
+                                    red: 0,
+                                    green: 255,
+                                    blue: 0,
+                                    alpha: 255,
+                                });
+                                rescan_button.draw(&mut layer_1);
+                            }
+                        } else if item_ref == "DNS_NEXT" {
+                            // Cycle through all clients and the overview
+                            dns_log_client = match dns_log_client {
+                                None => monitor.dns_log.clients.keys().next().cloned(),
+                                Some(x) => {
+                                    monitor.dns_log.clients.keys().find(|a| **a > x).cloned()
+                                }
+                            };
+
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(dns_log_title(dns_log_client));
+                            scroll_text.set_lines(dns_log_lines(&monitor, dns_log_client));
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "HOSTS" {
+                            dhcp_audit_shown = false;
+                            new_ui_state = UiStates::Inventory;
+                        } else if item_ref == "LIVE" {
+                            new_ui_state = UiStates::Live;
+                            monitor.live = Some(network::dissect::LiveView::default());
+                            live_details_active = false;
+                        } else if item_ref == "ScrollText"
+                            && new_ui_state == UiStates::Live
+                            && !live_details_active
+                        {
+                            // The lines are the summaries of the frames kept by the live view
+                            // and the tapped frame is dissected again
+                            let tree = item
+                                .line_at(touch_x, touch_y)
+                                .and_then(|i| monitor.live.as_ref().and_then(|x| x.tree_lines(i)));
+                            if let Some(tree) = tree {
+                                live_details_active = true;
+                                item.set_title(String::from("Details"));
+                                item.set_lines(tree);
+                                item.draw(&mut layer_1);
+                            }
+                        } else if item_ref == "DETAILS" {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            // The tree is a snapshot, it stays put while it is read
+                            let latest = monitor.live.as_ref().and_then(|x| x.latest_tree_lines());
+                            if live_details_active {
+                                live_details_active = false;
+                                scroll_text.set_title(String::from("Live"));
+                                if let Some(ref live) = monitor.live {
+                                    if !live.frames.is_empty() {
+                                        scroll_text.set_lines_no_scroll(live.to_string_vec());
+                                    }
+                                }
+                            } else if let Some(tree) = latest {
+                                live_details_active = true;
+                                scroll_text.set_title(String::from("Details"));
+                                scroll_text.set_lines(tree);
+                            } else {
+                                scroll_text.add_line(String::from("No frame received yet"));
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "FILTER" {
+                            text_input = filter_text.clone();
+                            new_ui_state = UiStates::CaptureFilter;
+                        } else if item_ref == "PCAP_FILE" || item_ref == "PCAP_TCP" {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("Capture"));
+
+                            if monitor.capture.is_empty() {
+                                scroll_text.set_lines(vec![String::from(
+                                    "No frames captured, listen to the traffic first",
+                                )]);
+                            } else if item_ref == "PCAP_FILE" {
+                                scroll_text.set_lines(vec![format!(
+                                    "Writing {} frames to {}...",
+                                    monitor.capture.len(),
+                                    network::capture::CAPTURE_FILE
+                                )]);
+                                scroll_text.draw(&mut layer_1);
+
+                                let result = network::capture::SemihostingSink::create(
+                                    network::capture::CAPTURE_FILE,
+                                )
+                                .and_then(|mut sink| monitor.capture.write_pcap(&mut sink));
+                                match result {
+                                    Ok(x) => scroll_text.add_line(format!(
+                                        "Wrote {} frames, {} older frames were dropped",
+                                        x, monitor.capture.dropped
+                                    )),
+                                    Err(x) => scroll_text.add_line(x),
+                                }
+                            } else {
+                                scroll_text.set_lines(vec![format!(
+                                    "Waiting for a receiver on port {}...",
+                                    network::capture::CAPTURE_PORT
+                                )]);
+                                scroll_text.draw(&mut layer_1);
+
+                                match network::capture::TcpSink::accept(
+                                    ethernet_interface.as_mut().unwrap(),
+                                    network::capture::CAPTURE_PORT,
+                                ) {
+                                    Ok(mut sink) => {
+                                        let result = monitor.capture.write_pcap(&mut sink);
+                                        sink.close();
+                                        match result {
+                                            Ok(x) => scroll_text.add_line(format!(
+                                                "Sent {} frames, {} older frames were dropped",
+                                                x, monitor.capture.dropped
+                                            )),
+                                            Err(x) => scroll_text.add_line(x),
+                                        }
+                                    }
+                                    Err(x) => scroll_text.add_line(x),
+                                }
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "EXPORT" {
+                            dhcp_audit_shown = false;
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("Export"));
+
+                            if inventory.is_empty() {
+                                scroll_text.set_lines(vec![String::from(
+                                    "No hosts to export, perform a scan first",
+                                )]);
+                            } else if network::capture::debugger_attached() {
+                                scroll_text.set_lines(vec![format!(
+                                    "Exporting {} hosts...",
+                                    inventory.hosts.len()
+                                )]);
+                                scroll_text.draw(&mut layer_1);
+
+                                for format in network::export::FORMATS.iter() {
+                                    let result = network::capture::SemihostingSink::create(
+                                        format.file_name(),
+                                    )
+                                    .and_then(|mut sink| {
+                                        format.export(
+                                            &mut sink,
+                                            &inventory,
+                                            Instant::from_millis(system_clock::ms() as i64),
+                                        )
+                                    });
+                                    match result {
+                                        Ok(()) => scroll_text
+                                            .add_line(format!("Wrote {}", format.file_name())),
+                                        Err(x) => scroll_text.add_line(x),
+                                    }
+                                }
+                            } else {
+                                // Without a debugger every format is sent to its own receiver
+                                scroll_text.set_lines(vec![format!(
+                                    "Exporting {} hosts to receivers on port {}",
+                                    inventory.hosts.len(),
+                                    network::export::EXPORT_PORT
+                                )]);
+                                for format in network::export::FORMATS.iter() {
+                                    scroll_text.add_line(format!(
+                                        "Waiting for a receiver of {}...",
+                                        format.file_name()
+                                    ));
+                                    scroll_text.draw(&mut layer_1);
+
+                                    match network::capture::TcpSink::accept(
+                                        ethernet_interface.as_mut().unwrap(),
+                                        network::export::EXPORT_PORT,
+                                    ) {
+                                        Ok(mut sink) => {
+                                            let result = format.export(
+                                                &mut sink,
+                                                &inventory,
+                                                Instant::from_millis(system_clock::ms() as i64),
+                                            );
+                                            sink.close();
+                                            match result {
+                                                Ok(()) => scroll_text.add_line(format!(
+                                                    "Sent {}",
+                                                    format.file_name()
+                                                )),
+                                                Err(x) => scroll_text.add_line(x),
+                                            }
+                                        }
+                                        Err(x) => {
+                                            scroll_text.add_line(x);
+                                            break;
+                                        }
+                                    }
+                                }
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        } else if item_ref == "VLAN" {
+                            new_ui_state = UiStates::Vlan;
+                        } else if item_ref == "VLAN_NEXT" {
+                            // Cycle through untagged and all tags seen so far
+                            let device = &mut ethernet_interface.as_mut().unwrap().device;
+                            let next = match device.vlan {
+                                None => monitor.vlans.keys().next().cloned(),
+                                Some(x) => monitor.vlans.range(x + 1..).next().map(|(id, _)| *id),
+                            };
+                            device.set_vlan(next);
+
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(vlan_title(next));
+                            if !monitor.vlans.is_empty() {
+                                scroll_text.set_lines_no_scroll(network::eth::vlan_lines(
+                                    &monitor.vlans,
+                                    next,
+                                ));
+                            }
                             scroll_text.draw(&mut layer_1);
                         } else if item_ref == "ButtonKillGateway" {
                             if gateway.is_some() {
@@ -657,12 +1561,108 @@ fn main() -> ! {
                 }
 
                 if new_ui_state != current_ui_state.get_ui_state() {
+                    dhcp_audit_shown = false;
                     current_ui_state.change_ui_state(
                         &mut layer_1,
                         &mut draw_items,
                         &mut element_map,
                         new_ui_state,
                     );
+                    match new_ui_state {
+                        UiStates::HostDetail => {
+                            if let Some(addr) = selected_host {
+                                draw_host(&mut element_map, &mut layer_1, &inventory, addr);
+                            }
+                        }
+                        UiStates::Target
+                        | UiStates::SnmpCommunity
+                        | UiStates::BaselineLabel
+                        | UiStates::CaptureFilter => {
+                            draw_input(&mut element_map, &mut layer_1, new_ui_state, &text_input);
+                        }
+                        UiStates::Snmp => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("SNMP System Info"));
+                            scroll_text
+                                .set_lines(snmp_settings_lines(&snmp_community, snmp_version));
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        UiStates::Link => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("Link"));
+                            if monitor.link.is_empty() {
+                                scroll_text
+                                    .set_lines(vec![String::from("Listening for LLDP/CDP...")]);
+                            } else {
+                                scroll_text.set_lines(monitor.link.to_string_vec());
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        UiStates::Vlan => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            let vlan = ethernet_interface.as_ref().unwrap().device.vlan;
+                            scroll_text.set_title(vlan_title(vlan));
+                            if monitor.vlans.is_empty() {
+                                scroll_text.set_lines(vec![String::from(
+                                    "Listening for 802.1Q tagged frames...",
+                                )]);
+                            } else {
+                                scroll_text
+                                    .set_lines(network::eth::vlan_lines(&monitor.vlans, vlan));
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        UiStates::Capture => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("Capture"));
+                            scroll_text.set_lines(capture_status(&monitor.capture, &filter_text));
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        UiStates::Live => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("Live"));
+                            scroll_text.set_lines(vec![String::from("Waiting for frames...")]);
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        UiStates::DnsLog => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(dns_log_title(dns_log_client));
+                            if monitor.dns_log.is_empty() {
+                                scroll_text
+                                    .set_lines(vec![String::from("Listening for DNS lookups...")]);
+                            } else {
+                                scroll_text.set_lines(dns_log_lines(&monitor, dns_log_client));
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        UiStates::Inventory => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(String::from("Hosts"));
+                            if inventory.is_empty() {
+                                scroll_text.set_lines(vec![String::from(
+                                    "No hosts found, run a scan first",
+                                )]);
+                            } else {
+                                scroll_text.set_lines(inventory.to_string_vec());
+                            }
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        UiStates::ArpWatch => {
+                            let scroll_text: &mut FUiElement =
+                                element_map.get_mut(&String::from("ScrollText")).unwrap();
+                            scroll_text.set_title(arp_watch_title(&arp_watch));
+                            scroll_text.set_lines(arp_watch.to_string_vec());
+                            scroll_text.draw(&mut layer_1);
+                        }
+                        _ => {}
+                    }
                 }
             }
 
@@ -674,39 +1674,202 @@ fn main() -> ! {
             previous_touch_state = false;
         }
 
-        if traffic_stats_active {
+        let link_active = current_ui_state.get_ui_state() == UiStates::Link;
+        let vlan_active = current_ui_state.get_ui_state() == UiStates::Vlan;
+        let live_active = current_ui_state.get_ui_state() == UiStates::Live;
+        let dns_log_active = current_ui_state.get_ui_state() == UiStates::DnsLog;
+        let arp_watch_active = current_ui_state.get_ui_state() == UiStates::ArpWatch;
+        if !live_active {
+            monitor.live = None;
+        }
+        // The listeners handle all frames themselves, otherwise defend our address
+        let listening = link_active || vlan_active || live_active || dns_log_active;
+        if !listening && !arp_watch_active && !traffic_stats_active {
+            let lost = match defender {
+                Some(ref mut x) => {
+                    let iface = ethernet_interface.as_mut().unwrap();
+                    !network::acd::defend(x, &mut iface.device, ETH_ADDR).unwrap_or(true)
+                }
+                None => false,
+            };
+            if lost {
+                let addr = defender.take().unwrap().addr;
+                let iface = ethernet_interface.as_mut().unwrap();
+                // The address belongs to the other host now
+                network::clear_ip4_address(iface);
+                gateway = None;
+                // A link-local address is replaced right away, other configurations are up to
+                // the user
+                if addr.is_link_local() {
+                    link_local = Some(network::ipv4ll::Claim::new(&mut rng, Some(addr)));
+                } else {
+                    current_ui_state.change_ui_state(
+                        &mut layer_1,
+                        &mut draw_items,
+                        &mut element_map,
+                        UiStates::Address,
+                    );
+                    let address_hint: &mut FUiElement =
+                        element_map.get_mut(&String::from("AddressHint")).unwrap();
+                    address_hint.set_lines(vec![format!(
+                        "{} is used by another host, please select a new configuration",
+                        addr
+                    )]);
+                    address_hint.draw(&mut layer_1);
+                }
+            }
+        }
+        // Continue claiming a link-local address, probes are rate limited after many conflicts
+        let claim_due = link_local.as_ref().map_or(false, |x| {
+            x.is_due(Instant::from_millis(system_clock::ms() as i64))
+        });
+        if claim_due {
+            let iface = ethernet_interface.as_mut().unwrap();
+            let result = link_local
+                .as_mut()
+                .unwrap()
+                .poll(&mut iface.device, ETH_ADDR, &mut rng);
+            let message = match result {
+                Ok(Some(addr)) => {
+                    network::set_ip4_address(iface, addr, network::ipv4ll::LINK_LOCAL_PREFIX_LEN);
+                    defender = Some(network::acd::Defender::new(addr));
+                    match link_local.take().unwrap().lost {
+                        Some(lost) => Some(format!(
+                            "{} is used by another host, switched to {}",
+                            lost, addr
+                        )),
+                        None => {
+                            current_ui_state.change_ui_state(
+                                &mut layer_1,
+                                &mut draw_items,
+                                &mut element_map,
+                                UiStates::Start,
+                            );
+                            None
+                        }
+                    }
+                }
+                Ok(None) => Some(format!(
+                    "Too many address conflicts, probing again every {} s",
+                    network::ipv4ll::RATE_LIMIT_INTERVAL.secs()
+                )),
+                Err(x) => {
+                    link_local = None;
+                    if current_ui_state.get_ui_state() != UiStates::Address {
+                        current_ui_state.change_ui_state(
+                            &mut layer_1,
+                            &mut draw_items,
+                            &mut element_map,
+                            UiStates::Address,
+                        );
+                    }
+                    Some(x)
+                }
+            };
+            if let Some(message) = message {
+                if current_ui_state.get_ui_state() == UiStates::Address {
+                    let address_hint: &mut FUiElement =
+                        element_map.get_mut(&String::from("AddressHint")).unwrap();
+                    address_hint.set_lines(vec![message]);
+                    address_hint.draw(&mut layer_1);
+                } else {
+                    let scroll_text: &mut FUiElement =
+                        element_map.get_mut(&String::from("ScrollText")).unwrap();
+                    scroll_text.add_line(message);
+                    scroll_text.draw(&mut layer_1);
+                }
+            }
+        }
+        if arp_watch_active {
+            let iface = ethernet_interface.as_mut().unwrap();
+            let mut alerts = network::arp::watch_v4(&mut iface.device, ETH_ADDR, &mut arp_watch);
+            let now = Instant::from_millis(system_clock::ms() as i64);
+            let rescan_due = match last_rescan {
+                Some(x) => now - x >= network::baseline::RESCAN_INTERVAL,
+                None => true,
+            };
+            if rescan_active && rescan_due {
+                last_rescan = Some(now);
+                if let IpCidr::Ipv4(cidr) = iface.ip_addrs()[0] {
+                    if let Ok(found) = network::arp::get_neighbors_v4(
+                        &mut iface.device,
+                        ETH_ADDR,
+                        &mut cidr.into(),
+                    ) {
+                        alerts += arp_watch.check_scan(&found, now);
+                        arp_watch.learn_all(&found);
+                        inventory.merge_neighbors(&found, now);
+                    }
+                }
+            }
+            let scroll_text: &mut FUiElement =
+                element_map.get_mut(&String::from("ScrollText")).unwrap();
+            if alerts > 0 {
+                // Show new alerts right away
+                scroll_text.set_title(arp_watch_title(&arp_watch));
+                scroll_text.set_lines(arp_watch.to_string_vec());
+                scroll_text.draw(&mut layer_1);
+            } else {
+                scroll_text.set_lines_no_scroll(arp_watch.to_string_vec());
+            }
+        } else if listening {
             let iface = ethernet_interface.as_mut().unwrap();
             let scroll_text: &mut FUiElement =
                 element_map.get_mut(&String::from("ScrollText")).unwrap();
             match network::eth::listen(
-                &mut traffic_stats,
+                &mut monitor,
                 &mut iface.device,
                 ETH_ADDR,
                 &neighbors,
                 gateway,
             ) {
                 Ok(_) => {
-                    scroll_text.set_lines_no_scroll(traffic_stats.to_string_vec());
-
-                    let mut overall_stats: (usize, usize, i64) = (0, 0, 0);
-                    let now_s = Instant::from_millis(system_clock::ms() as i64).secs();
-
-                    for traffic_stat in traffic_stats.iter() {
-                        let traffic_stat_single = traffic_stat.1;
-                        overall_stats.0 += traffic_stat_single.0;
-                        overall_stats.1 += traffic_stat_single.1;
-                        if now_s > traffic_stat_single.2.secs() {
-                            overall_stats.2 += (traffic_stat_single.1 as i64)
-                                / (now_s - traffic_stat_single.2.secs());
+                    inventory.merge_ipv6_neighbors(&monitor.ipv6_neighbors);
+                    inventory.merge_link_neighbors(&monitor.link);
+                    if live_active {
+                        if !live_details_active {
+                            if let Some(ref live) = monitor.live {
+                                if !live.frames.is_empty() {
+                                    scroll_text.set_lines_no_scroll(live.to_string_vec());
+                                }
+                            }
+                        }
+                    } else if dns_log_active {
+                        if !monitor.dns_log.is_empty() {
+                            scroll_text
+                                .set_lines_no_scroll(dns_log_lines(&monitor, dns_log_client));
                         }
+                    } else if vlan_active {
+                        if !monitor.vlans.is_empty() {
+                            scroll_text.set_lines_no_scroll(network::eth::vlan_lines(
+                                &monitor.vlans,
+                                iface.device.vlan,
+                            ));
+                        }
+                    } else if !monitor.link.is_empty() {
+                        scroll_text.set_lines_no_scroll(monitor.link.to_string_vec());
+                    }
+                }
+                Err(x) => scroll_text.add_line(format!("Error during processing: {}", x)),
+            }
+        } else if traffic_stats_active {
+            let iface = ethernet_interface.as_mut().unwrap();
+            let scroll_text: &mut FUiElement =
+                element_map.get_mut(&String::from("ScrollText")).unwrap();
+            match network::eth::listen(
+                &mut monitor,
+                &mut iface.device,
+                ETH_ADDR,
+                &neighbors,
+                gateway,
+            ) {
+                Ok(_) => {
+                    inventory.merge_ipv6_neighbors(&monitor.ipv6_neighbors);
+                    if top_talkers_active {
+                        scroll_text.set_lines_no_scroll(monitor.flows.to_string_vec());
+                    } else {
+                        scroll_text.set_lines_no_scroll(traffic_lines(&monitor));
                     }
-
-                    scroll_text.add_line(String::from(""));
-                    scroll_text.add_line(String::from("Overall:"));
-
-                    scroll_text.add_line(format!("    {} packets", overall_stats.0));
-                    scroll_text.add_line(format!("    {} bytes", overall_stats.1));
-                    scroll_text.add_line(format!("    {} bytes / second", overall_stats.2));
                 }
                 Err(x) => scroll_text.add_line(format!("Error during processing: {}", x)),
             }
@@ -744,6 +1907,11 @@ fn main() -> ! {
                     .get_mut(&String::from("ScrollText"))
                     .unwrap()
                     .draw(&mut layer_1);
+            } else if listening || arp_watch_active {
+                element_map
+                    .get_mut(&String::from("ScrollText"))
+                    .unwrap()
+                    .draw(&mut layer_1);
             }
 
             if attack_gateway_v4_active {
@@ -836,6 +2004,163 @@ fn main() -> ! {
     }
 }
 
+/// Show the input of the static configuration fields and highlight the selected one
+fn draw_static_fields(
+    element_map: &mut BTreeMap<String, FUiElement>,
+    layer: &mut Layer<FramebufferArgb8888>,
+    fields: &[String; 3],
+    selected: usize,
+) {
+    let names = ["STATIC_ADDRESS", "STATIC_GATEWAY", "STATIC_DNS"];
+    let labels = ["Address", "Gateway", "DNS"];
+    for (i, name) in names.iter().enumerate() {
+        let field: &mut FUiElement = element_map.get_mut(&String::from(*name)).unwrap();
+        field.set_text(format!("{}: {}", labels[i], fields[i]));
+        field.set_background_color(if i == selected {
+            Color {
+                red: 0,
+                green: 0,
+                blue: 255,
+                alpha: 255,
+            }
+        } else {
+            Color {
+                red: 0,
+                green: 255,
+                blue: 0,
+                alpha: 255,
+            }
+        });
+        field.draw(layer);
+    }
+}
+
+/// Keep baseline in the backup SRAM, it is restored after a reset
+fn store_baseline(baseline: &network::baseline::Baseline) -> Result<(), &'static str> {
+    backup::write(&baseline.to_bytes(backup::BKPSRAM_SIZE)?)
+}
+
+/// Show the prompt of an input screen and the text entered so far
+fn draw_input(
+    element_map: &mut BTreeMap<String, FUiElement>,
+    layer: &mut Layer<FramebufferArgb8888>,
+    state: UiStates,
+    text: &str,
+) {
+    let (hint, label, ok) = match state {
+        UiStates::SnmpCommunity => ("Enter the SNMP community", "Community", "APPLY"),
+        UiStates::BaselineLabel => (
+            "Enter a label for the device, leave it empty to remove the label",
+            "Label",
+            "APPLY",
+        ),
+        UiStates::CaptureFilter => (
+            "Enter a filter, e.g. host 10.0.0.5 and tcp port 443, or tap PRESET",
+            "Filter",
+            "APPLY",
+        ),
+        _ => ("Enter a hostname or address to scan", "Target", "OPEN"),
+    };
+    let input_hint: &mut FUiElement = element_map.get_mut(&String::from("InputHint")).unwrap();
+    input_hint.set_lines(vec![String::from(hint)]);
+    input_hint.draw(layer);
+    let input_field: &mut FUiElement = element_map.get_mut(&String::from("INPUT_FIELD")).unwrap();
+    input_field.set_text(format!("{}: {}", label, text));
+    input_field.draw(layer);
+    let input_ok: &mut FUiElement = element_map.get_mut(&String::from("INPUT_OK")).unwrap();
+    input_ok.set_text(String::from(ok));
+    input_ok.draw(layer);
+}
+
+/// Describe the settings used by SNMP queries
+fn snmp_settings_lines(community: &str, version: network::snmp::Version) -> Vec<String> {
+    vec![
+        format!("Community: {}", community),
+        format!("Version: {}", version),
+        String::from("Press QUERY to query all neighbors"),
+    ]
+}
+
+/// Show everything known about addr on the host detail screen
+fn draw_host(
+    element_map: &mut BTreeMap<String, FUiElement>,
+    layer: &mut Layer<FramebufferArgb8888>,
+    inventory: &network::inventory::Inventory,
+    addr: Ipv4Address,
+) {
+    let scroll_text: &mut FUiElement = element_map.get_mut(&String::from("ScrollText")).unwrap();
+    scroll_text.set_title(format!("Host {}", addr));
+    match inventory.hosts.get(&addr) {
+        Some(host) => scroll_text.set_lines(host.to_string_vec()),
+        None => scroll_text.set_lines(vec![String::from("Host is no longer in the inventory")]),
+    }
+    scroll_text.draw(layer);
+}
+
+/// Describe the captured frames and the filter applied to the listeners
+fn capture_status(capture: &network::pcap::Capture, filter: &str) -> Vec<String> {
+    vec![
+        format!(
+            "{} frames captured, {} dropped",
+            capture.len(),
+            capture.dropped
+        ),
+        if filter.is_empty() {
+            String::from("Filter: none")
+        } else {
+            format!("Filter: {}", filter)
+        },
+        String::from("Frames are captured while listening to the traffic"),
+    ]
+}
+
+/// Per host statistics followed by the protocol mix of the segment and the IPv6 neighbors
+fn traffic_lines(monitor: &network::eth::Monitor) -> Vec<String> {
+    let mut lines = network::stats::host_lines(&monitor.stats);
+    lines.extend(monitor.totals.to_string_vec());
+    if !monitor.ipv6_neighbors.is_empty() {
+        lines.push(String::from("IPv6 neighbors:"));
+        lines.extend(monitor.ipv6_neighbors.to_string_vec());
+    }
+    lines
+}
+
+/// Title of the ARP watch screen, raises attention once an alert was seen
+fn arp_watch_title(watch: &network::arpwatch::ArpWatch) -> String {
+    if watch.events.is_empty() {
+        String::from("ARP Watch")
+    } else {
+        format!("ARP Watch: {} ALERTS", watch.events.len())
+    }
+}
+
+/// Title of the DNS log screen showing the selected client
+fn dns_log_title(client: Option<IpAddress>) -> String {
+    match client {
+        Some(x) => format!("DNS: {}", x),
+        None => String::from("DNS Log"),
+    }
+}
+
+/// Lookups of client, or all clients with their latest lookup
+fn dns_log_lines(monitor: &network::eth::Monitor, client: Option<IpAddress>) -> Vec<String> {
+    match client {
+        Some(x) => match monitor.dns_log.clients.get(&x) {
+            Some(log) => log.to_string_vec(),
+            None => vec![String::from("No lookups of this host are logged anymore")],
+        },
+        None => monitor.dns_log.to_string_vec(),
+    }
+}
+
+/// Title of the VLAN screen showing the selected VLAN
+fn vlan_title(vlan: Option<u16>) -> String {
+    match vlan {
+        Some(x) => format!("VLAN: {}", x),
+        None => String::from("VLAN: untagged"),
+    }
+}
+
 #[exception]
 fn SysTick() {
     system_clock::tick();
@@ -0,0 +1,273 @@
+use alloc::string::{String, ToString};
+use smoltcp::phy::{Device, RxToken};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::*;
+use stm32f7_discovery::{random, system_clock};
+
+use super::eth::{dispatch_ethernet, NetDevice};
+
+// Timing constants of RFC 5227
+const PROBE_WAIT: u64 = 1000;
+const PROBE_NUM: usize = 3;
+const PROBE_MIN: u64 = 1000;
+const PROBE_MAX: u64 = 2000;
+const ANNOUNCE_WAIT: u64 = 2000;
+const ANNOUNCE_NUM: usize = 2;
+const ANNOUNCE_INTERVAL: u64 = 2000;
+/// Number of conflicting addresses after which address selection gives up
+pub const MAX_CONFLICTS: usize = 10;
+const DEFEND_INTERVAL: Duration = Duration { millis: 10_000 };
+
+/// Reaction to an ARP packet claiming our address
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Defense {
+    /// Our address was announced again
+    Defended,
+    /// A second conflict within DEFEND_INTERVAL, the address must not be used any more
+    Lost,
+}
+
+/// Keeps track of the defense of a configured address
+pub struct Defender {
+    pub addr: Ipv4Address,
+    last_defense: Option<Instant>,
+}
+
+impl Defender {
+    pub fn new(addr: Ipv4Address) -> Self {
+        Defender {
+            addr,
+            last_defense: None,
+        }
+    }
+
+    /// Decide how to react to a conflict detected at timestamp
+    pub fn on_conflict(&mut self, timestamp: Instant) -> Defense {
+        match self.last_defense {
+            Some(x) if timestamp - x < DEFEND_INTERVAL => Defense::Lost,
+            _ => {
+                self.last_defense = Some(timestamp);
+                Defense::Defended
+            }
+        }
+    }
+}
+
+/// Check whether an ARP packet from another host claims addr
+/// While probing, probes of other hosts for the same address are conflicts as well
+pub fn is_conflict(
+    repr: &ArpRepr,
+    eth_addr: EthernetAddress,
+    addr: Ipv4Address,
+    probing: bool,
+) -> bool {
+    match *repr {
+        ArpRepr::EthernetIpv4 {
+            operation,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        } => {
+            source_hardware_addr != eth_addr
+                && (source_protocol_addr == addr
+                    || (probing
+                        && operation == ArpOperation::Request
+                        && source_protocol_addr.is_unspecified()
+                        && target_protocol_addr == addr))
+        }
+        _ => false,
+    }
+}
+
+/// Send an ARP packet from sender, requests are broadcast and replies sent to the target
+fn send_arp(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    operation: ArpOperation,
+    sender: Ipv4Address,
+    target: (EthernetAddress, Ipv4Address),
+) -> Result<(), String> {
+    let arp_repr = ArpRepr::EthernetIpv4 {
+        operation,
+        source_hardware_addr: eth_addr,
+        source_protocol_addr: sender,
+        target_hardware_addr: target.0,
+        target_protocol_addr: target.1,
+    };
+    // A reply to a probe is broadcast since its sender has no address yet (RFC 5227)
+    let dst = if operation == ArpOperation::Reply && !target.1.is_unspecified() {
+        target.0
+    } else {
+        EthernetAddress::BROADCAST
+    };
+
+    let tx_token = match iface.transmit() {
+        Some(x) => x,
+        None => return Err(String::from("No tx descriptor available")),
+    };
+    dispatch_ethernet(
+        eth_addr,
+        tx_token,
+        Instant::from_millis(system_clock::ms() as i64),
+        arp_repr.buffer_len(),
+        |mut frame| {
+            frame.set_dst_addr(dst);
+            frame.set_ethertype(EthernetProtocol::Arp);
+
+            let mut packet = ArpPacket::new_unchecked(frame.payload_mut());
+            arp_repr.emit(&mut packet);
+        },
+    )
+    .map_err(|x| x.to_string())
+}
+
+/// Send an ARP probe for addr, the sender address is left unspecified
+pub fn send_probe(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    addr: Ipv4Address,
+) -> Result<(), String> {
+    let target = (EthernetAddress([0; 6]), addr);
+    send_arp(
+        iface,
+        eth_addr,
+        ArpOperation::Request,
+        Ipv4Address::UNSPECIFIED,
+        target,
+    )
+}
+
+/// Send an ARP announcement claiming addr
+pub fn send_announcement(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    addr: Ipv4Address,
+) -> Result<(), String> {
+    let target = (EthernetAddress([0; 6]), addr);
+    send_arp(iface, eth_addr, ArpOperation::Request, addr, target)
+}
+
+/// Parse a raw ethernet frame carrying an ARP packet
+fn parse_arp<T: AsRef<[u8]>>(frame: &T) -> Result<ArpRepr, smoltcp::Error> {
+    let eth_frame = EthernetFrame::new_checked(frame)?;
+    if eth_frame.ethertype() != EthernetProtocol::Arp {
+        return Err(smoltcp::Error::Unrecognized);
+    }
+    let arp_packet = ArpPacket::new_checked(eth_frame.payload())?;
+    ArpRepr::parse(&arp_packet)
+}
+
+/// Receive frames for millis milliseconds and report whether any of them conflicts with addr
+fn watch(iface: &mut NetDevice, eth_addr: EthernetAddress, addr: Ipv4Address, millis: u64) -> bool {
+    let start = Instant::from_millis(system_clock::ms() as i64);
+    let mut conflict = false;
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        if timestamp - start > Duration::from_millis(millis) {
+            break;
+        }
+        let (rx_token, _) = match iface.receive() {
+            None => continue,
+            Some(tokens) => tokens,
+        };
+        if let Ok(repr) = rx_token.consume(timestamp, |frame| parse_arp(&frame)) {
+            conflict |= is_conflict(&repr, eth_addr, addr, true);
+        }
+    }
+    conflict
+}
+
+/// Return a random duration between min and max milliseconds
+fn random_millis(rng: &mut random::Rng, min: u64, max: u64) -> u64 {
+    min + u64::from(rng.poll_and_get().expect("RNG Failed")) % (max - min)
+}
+
+/// Probe whether addr is in use as described in RFC 5227
+/// Returns true if nobody claimed the address
+pub fn probe(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    rng: &mut random::Rng,
+    addr: Ipv4Address,
+) -> Result<bool, String> {
+    let wait = random_millis(rng, 0, PROBE_WAIT);
+    if watch(iface, eth_addr, addr, wait) {
+        return Ok(false);
+    }
+    for i in 0..PROBE_NUM {
+        send_probe(iface, eth_addr, addr)?;
+        let wait = if i + 1 == PROBE_NUM {
+            ANNOUNCE_WAIT
+        } else {
+            random_millis(rng, PROBE_MIN, PROBE_MAX)
+        };
+        if watch(iface, eth_addr, addr, wait) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Announce that we are using addr now
+pub fn announce(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    addr: Ipv4Address,
+) -> Result<(), String> {
+    for i in 0..ANNOUNCE_NUM {
+        send_announcement(iface, eth_addr, addr)?;
+        if i + 1 < ANNOUNCE_NUM {
+            system_clock::wait_ms(ANNOUNCE_INTERVAL as usize);
+        }
+    }
+    Ok(())
+}
+
+/// Process all received frames and defend the address of defender against conflicts
+/// Requests for the address are answered, as the frames never reach the interface
+/// Returns false if the address was lost to another host
+pub fn defend(
+    defender: &mut Defender,
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+) -> Result<bool, String> {
+    let mut tries = 0;
+    loop {
+        let (rx_token, _) = match iface.receive() {
+            None => {
+                if tries > 100 {
+                    break;
+                }
+                tries += 1;
+                continue;
+            }
+            Some(tokens) => tokens,
+        };
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        let repr = match rx_token.consume(timestamp, |frame| parse_arp(&frame)) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        if is_conflict(&repr, eth_addr, defender.addr, false) {
+            match defender.on_conflict(timestamp) {
+                Defense::Defended => send_announcement(iface, eth_addr, defender.addr)?,
+                Defense::Lost => return Ok(false),
+            }
+        } else if let ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        } = repr
+        {
+            // Probes for our address are answered too, so the prober picks another one
+            if target_protocol_addr == defender.addr {
+                let target = (source_hardware_addr, source_protocol_addr);
+                send_arp(iface, eth_addr, ArpOperation::Reply, defender.addr, target)?;
+            }
+        }
+    }
+    Ok(true)
+}
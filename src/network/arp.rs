@@ -1,95 +1,20 @@
 use super::cidr;
-use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use smoltcp::iface::EthernetInterface;
 use smoltcp::phy::{Device, RxToken};
 use smoltcp::time::Instant;
 use smoltcp::wire::*;
-use stm32f7_discovery::{ethernet::EthernetDevice, system_clock};
+use stm32f7_discovery::system_clock;
 
-use super::eth::dispatch_ethernet;
+use super::arpwatch::ArpWatch;
+use super::eth::{dispatch_ethernet, NetDevice};
 
-// #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-// pub struct ArpResponse(pub Ipv4Address, pub EthernetAddress);
-
-pub type ArpResponses = BTreeMap<Ipv4Address, EthernetAddress>;
-
-impl super::StringableVec for ArpResponses {
-    fn to_string_vec(&self) -> Vec<String> {
-        let mut ret: Vec<String> = Vec::new();
-        for i in self.iter() {
-            ret.push(format!("{} ({})", i.0, i.1));
-        }
-        ret
-    }
-}
-
-/// Send an ARP request to check whether addr is currently being used on the current network
-pub fn request(
-    iface: &mut EthernetDevice,
-    eth_addr: EthernetAddress,
-    addr: Ipv4Address,
-) -> Result<bool, String> {
-    let arp_req = ArpRepr::EthernetIpv4 {
-        operation: ArpOperation::Request,
-        source_hardware_addr: eth_addr,
-        source_protocol_addr: Ipv4Address::new(0, 0, 0, 0),
-        target_hardware_addr: EthernetAddress::BROADCAST,
-        target_protocol_addr: addr,
-    };
-
-    let mut buffer = vec![0; arp_req.buffer_len()];
-    let mut packet = ArpPacket::new_unchecked(&mut buffer);
-    arp_req.emit(&mut packet);
-
-    let tx_token = match iface.transmit() {
-        Some(x) => x,
-        None => return Err(String::from("No tx descriptor available")),
-    };
-    match dispatch_ethernet(
-        eth_addr,
-        tx_token,
-        Instant::from_millis(system_clock::ms() as i64),
-        arp_req.buffer_len(),
-        |mut frame| {
-            frame.set_dst_addr(EthernetAddress::BROADCAST);
-            frame.set_ethertype(EthernetProtocol::Arp);
-
-            let mut packet = ArpPacket::new_unchecked(frame.payload_mut());
-            arp_req.emit(&mut packet);
-        },
-    ) {
-        Ok(_) => {}
-        Err(x) => return Err(x.to_string()),
-    };
-    let mut tries = 0;
-    loop {
-        let (rx_token, _) = match iface.receive() {
-            None => {
-                if tries > 100 {
-                    break;
-                }
-                tries += 1;
-                continue;
-            }
-            Some(tokens) => tokens,
-        };
-        match rx_token.consume(Instant::from_millis(system_clock::ms() as i64), |frame| {
-            process_arp(eth_addr, &frame)
-        }) {
-            Ok(ArpRepr::EthernetIpv4 { .. }) => return Ok(false),
-            Ok(_) => {}
-            Err(::smoltcp::Error::Unrecognized) => {}
-            Err(_) => {}
-        };
-    }
-    Ok(true)
-}
+pub use netscan::arp::*;
 
 /// Scan all addrs in cidr and return those for which somebody responded
 pub fn get_neighbors_v4(
-    iface: &mut EthernetDevice,
+    iface: &mut NetDevice,
     eth_addr: EthernetAddress,
     cidr: &mut cidr::Ipv4Cidr,
 ) -> Result<ArpResponses, String> {
@@ -331,9 +256,33 @@ pub fn attack_network_v4_reply<'b, 'c, 'e, DeviceT>(
     }
 }
 
+/// Receive all ARP packets currently buffered and check them for poisoning
+/// Only listens, nothing is sent. Returns the number of alerts raised
+pub fn watch_v4(iface: &mut NetDevice, eth_addr: EthernetAddress, watch: &mut ArpWatch) -> usize {
+    let mut alerts = 0;
+    let mut tries = 0;
+    loop {
+        let (rx_token, _) = match iface.receive() {
+            None => {
+                if tries > 100 {
+                    break;
+                }
+                tries += 1;
+                continue;
+            }
+            Some(tokens) => tokens,
+        };
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        if let Ok(arp_repr) = rx_token.consume(timestamp, |frame| process_arp(eth_addr, &frame)) {
+            alerts += watch.observe(&arp_repr, timestamp);
+        }
+    }
+    alerts
+}
+
 /// Parse ARP frame and check if it is a response to one of our requests
 /// Returns parsed arp representation if it is
-fn process_arp<T: AsRef<[u8]>>(
+pub fn process_arp<T: AsRef<[u8]>>(
     eth_addr: EthernetAddress,
     frame: &T,
 ) -> Result<ArpRepr, smoltcp::Error> {
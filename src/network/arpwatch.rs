@@ -0,0 +1,297 @@
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use smoltcp::time::Instant;
+use smoltcp::wire::*;
+
+use super::arp::ArpResponses;
+use super::baseline::{self, Baseline, Change};
+
+/// A hardware address claiming more addresses than this is reported
+pub const MAX_ADDRS_PER_MAC: usize = 4;
+/// Maximum number of bindings learned, further addresses are ignored
+pub const MAX_BINDINGS: usize = 256;
+/// Number of alerts kept in the event log
+pub const MAX_EVENTS: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// A known address is announced by another hardware address
+    BindingChanged {
+        addr: Ipv4Address,
+        old: EthernetAddress,
+        new: EthernetAddress,
+    },
+    /// A gratuitous ARP contradicts a known binding
+    GratuitousConflict {
+        addr: Ipv4Address,
+        known: EthernetAddress,
+        claimed: EthernetAddress,
+    },
+    /// A hardware address claims many addresses
+    ManyAddresses {
+        eth_addr: EthernetAddress,
+        count: usize,
+    },
+    /// The gateway is announced by another hardware address
+    GatewayChanged {
+        addr: Ipv4Address,
+        old: EthernetAddress,
+        new: EthernetAddress,
+    },
+    /// A device differs from the baseline, label is its name in the baseline
+    Baseline {
+        change: Change,
+        label: Option<String>,
+    },
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Alert::BindingChanged { addr, old, new } => {
+                write!(f, "{} moved from {} to {}", addr, old, new)
+            }
+            Alert::GratuitousConflict {
+                addr,
+                known,
+                claimed,
+            } => write!(
+                f,
+                "Gratuitous ARP of {} claims {}, known as {}",
+                claimed, addr, known
+            ),
+            Alert::ManyAddresses { eth_addr, count } => {
+                write!(f, "{} claims {} addresses", eth_addr, count)
+            }
+            Alert::GatewayChanged { addr, old, new } => {
+                write!(f, "Gateway {} moved from {} to {}", addr, old, new)
+            }
+            Alert::Baseline {
+                change,
+                label: Some(label),
+            } => write!(f, "{} {}", change, label),
+            Alert::Baseline {
+                change,
+                label: None,
+            } => write!(f, "{}", change),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub timestamp: Instant,
+    pub alert: Alert,
+}
+
+/// Watches ARP traffic for poisoning of the learned IP to MAC bindings
+#[derive(Debug, Default)]
+pub struct ArpWatch {
+    pub bindings: ArpResponses,
+    pub gateway: Option<Ipv4Address>,
+    pub events: VecDeque<Event>,
+    /// Hardware addresses already reported for claiming many addresses
+    reported: BTreeSet<EthernetAddress>,
+    /// Devices are compared with the baseline once it is saved
+    pub baseline: Option<Baseline>,
+    /// Bindings and devices already reported as differing from the baseline
+    reported_changes: BTreeSet<(Ipv4Address, EthernetAddress)>,
+    missing: BTreeSet<EthernetAddress>,
+}
+
+impl ArpWatch {
+    /// Take over bindings learned elsewhere, e.g. by an ARP scan, without raising alerts
+    pub fn learn_all(&mut self, neighbors: &ArpResponses) {
+        for (addr, eth_addr) in neighbors.iter() {
+            if self.bindings.len() >= MAX_BINDINGS {
+                break;
+            }
+            self.bindings.entry(*addr).or_insert(*eth_addr);
+        }
+    }
+
+    /// Compare with baseline from now on
+    pub fn set_baseline(&mut self, baseline: Baseline) {
+        self.baseline = Some(baseline);
+        self.reported_changes.clear();
+        self.missing.clear();
+    }
+
+    fn raise(&mut self, alert: Alert, timestamp: Instant) {
+        if self.events.len() >= MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event { timestamp, alert });
+    }
+
+    /// Check an ARP packet against the known bindings and learn from it
+    /// Returns the number of alerts raised
+    pub fn observe(&mut self, repr: &ArpRepr, timestamp: Instant) -> usize {
+        let (addr, eth_addr, target) = match *repr {
+            ArpRepr::EthernetIpv4 {
+                source_protocol_addr,
+                source_hardware_addr,
+                target_protocol_addr,
+                ..
+            } => (
+                source_protocol_addr,
+                source_hardware_addr,
+                target_protocol_addr,
+            ),
+            _ => return 0,
+        };
+        let mut alerts = 0;
+
+        match self.bindings.get(&addr).cloned() {
+            Some(known) if known != eth_addr => {
+                let alert = if self.gateway == Some(addr) {
+                    Alert::GatewayChanged {
+                        addr,
+                        old: known,
+                        new: eth_addr,
+                    }
+                } else if addr == target {
+                    // Announcements and probes for the own address
+                    Alert::GratuitousConflict {
+                        addr,
+                        known,
+                        claimed: eth_addr,
+                    }
+                } else {
+                    Alert::BindingChanged {
+                        addr,
+                        old: known,
+                        new: eth_addr,
+                    }
+                };
+                self.raise(alert, timestamp);
+                alerts += 1;
+                self.bindings.insert(addr, eth_addr);
+            }
+            Some(_) => {}
+            None => {
+                if self.bindings.len() < MAX_BINDINGS {
+                    self.bindings.insert(addr, eth_addr);
+                }
+            }
+        }
+
+        let count = self.bindings.values().filter(|x| **x == eth_addr).count();
+        if count > MAX_ADDRS_PER_MAC && self.reported.insert(eth_addr) {
+            self.raise(Alert::ManyAddresses { eth_addr, count }, timestamp);
+            alerts += 1;
+        }
+
+        // Absence can't be told from passive observation, only scans report missing devices
+        let change = match self.baseline {
+            Some(ref x) => baseline::check_host(&x.hosts, addr, eth_addr),
+            None => None,
+        };
+        if let Some(change) = change {
+            alerts += self.raise_change(change, timestamp);
+        }
+        alerts
+    }
+
+    /// Compare the neighbors found by a scan with the baseline
+    /// Returns the number of alerts raised
+    pub fn check_scan(&mut self, neighbors: &ArpResponses, timestamp: Instant) -> usize {
+        let changes = match self.baseline {
+            Some(ref x) => baseline::diff(&x.hosts, neighbors),
+            None => return 0,
+        };
+        // Devices that are back may be reported missing again
+        let back: Vec<EthernetAddress> = self
+            .missing
+            .iter()
+            .filter(|x| neighbors.values().any(|eth_addr| eth_addr == *x))
+            .cloned()
+            .collect();
+        for eth_addr in back {
+            self.missing.remove(&eth_addr);
+        }
+        changes
+            .into_iter()
+            .map(|change| self.raise_change(change, timestamp))
+            .sum()
+    }
+
+    /// Raise an alert for change unless it was reported before
+    fn raise_change(&mut self, change: Change, timestamp: Instant) -> usize {
+        let new = match change {
+            Change::NewDevice(addr, eth_addr) | Change::NewAddress(addr, eth_addr) => {
+                self.reported_changes.insert((addr, eth_addr))
+            }
+            Change::Missing(_, eth_addr) => self.missing.insert(eth_addr),
+        };
+        if !new {
+            return 0;
+        }
+        let label = self
+            .baseline
+            .as_ref()
+            .and_then(|x| x.label(change.eth_addr()))
+            .cloned();
+        self.raise(Alert::Baseline { change, label }, timestamp);
+        1
+    }
+
+    /// The baseline device shown at line index of to_string_vec
+    pub fn device_at(&self, index: usize) -> Option<EthernetAddress> {
+        // Two summary lines, the alerts and a heading come before the devices
+        let first = 3 + self.events.len();
+        match self.baseline {
+            Some(ref x) if index >= first => x.device_at(index - first),
+            _ => None,
+        }
+    }
+
+    /// The most recent alert
+    pub fn latest(&self) -> Option<&Event> {
+        self.events.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.bindings.clear();
+        self.events.clear();
+        self.reported.clear();
+        self.reported_changes.clear();
+        self.missing.clear();
+    }
+}
+
+impl super::StringableVec for ArpWatch {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        ret.push(format!(
+            "{} bindings known, {} alerts",
+            self.bindings.len(),
+            self.events.len()
+        ));
+        match self.baseline {
+            Some(ref x) => ret.push(format!(
+                "Baseline of {} devices saved at {}s",
+                x.hosts.len(),
+                x.saved.secs()
+            )),
+            None => ret.push(String::from("No baseline saved")),
+        }
+        // Newest alerts first
+        for event in self.events.iter().rev() {
+            let millis = event.timestamp.total_millis();
+            ret.push(format!(
+                "{}.{:03} {}",
+                millis / 1000,
+                millis % 1000,
+                event.alert
+            ));
+        }
+        if let Some(ref x) = self.baseline {
+            ret.push(String::from("Baseline devices, tap one to label it:"));
+            ret.extend(x.device_lines());
+        }
+        ret
+    }
+}
@@ -0,0 +1,223 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::phy::{Device, RxToken};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::*;
+use stm32f7_discovery::{random, system_clock};
+
+use super::acd;
+use super::arp::ArpResponses;
+use super::eth::NetDevice;
+
+/// Time to passively listen to the traffic before choosing an address
+const SURVEY_WINDOW: Duration = Duration { millis: 10_000 };
+/// Longest prefix assumed for a subnet, even if all observed hosts share a longer one
+const MAX_PREFIX_LEN: u8 = 24;
+/// Number of source addresses remembered per hardware address
+const MAX_SOURCES: usize = 8;
+
+/// Traffic observed on the link while no address is configured
+#[derive(Default)]
+pub struct Survey {
+    /// Hosts that used their address as ARP sender
+    pub hosts: ArpResponses,
+    /// Number of ARP requests asking for an address
+    requested: BTreeMap<Ipv4Address, usize>,
+    /// Distinct IPv4 source addresses of the packets sent by a hardware address
+    sources: BTreeMap<EthernetAddress, BTreeSet<Ipv4Address>>,
+}
+
+/// The result of the address selection
+pub struct Selection {
+    pub cidr: Ipv4Cidr,
+    pub gateway: Option<Ipv4Address>,
+    pub hosts: ArpResponses,
+}
+
+fn netmask(prefix_len: u8) -> u32 {
+    (!0u32).checked_shl(32 - u32::from(prefix_len)).unwrap_or(0)
+}
+
+fn to_u32(addr: Ipv4Address) -> u32 {
+    NetworkEndian::read_u32(addr.as_bytes())
+}
+
+fn from_u32(addr: u32) -> Ipv4Address {
+    let mut bytes = [0; 4];
+    NetworkEndian::write_u32(&mut bytes, addr);
+    Ipv4Address::from_bytes(&bytes)
+}
+
+/// Check whether addr lies in range, link-local addresses only count if range is link-local
+fn in_range(addr: Ipv4Address, range: &Ipv4Cidr) -> bool {
+    addr.is_unicast()
+        && range.contains_addr(&addr)
+        && (!addr.is_link_local() || range.address().is_link_local())
+}
+
+impl Survey {
+    /// Record the addresses of an ARP or IPv4 frame
+    pub fn process_frame<T: AsRef<[u8]>>(&mut self, frame: &T, range: &Ipv4Cidr) {
+        let eth_frame = match EthernetFrame::new_checked(frame) {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        match eth_frame.ethertype() {
+            EthernetProtocol::Arp => {
+                let arp_repr = match ArpPacket::new_checked(eth_frame.payload())
+                    .and_then(|x| ArpRepr::parse(&x))
+                {
+                    Ok(x) => x,
+                    Err(_) => return,
+                };
+                if let ArpRepr::EthernetIpv4 {
+                    operation,
+                    source_hardware_addr,
+                    source_protocol_addr,
+                    target_protocol_addr,
+                    ..
+                } = arp_repr
+                {
+                    if in_range(source_protocol_addr, range) {
+                        self.hosts
+                            .insert(source_protocol_addr, source_hardware_addr);
+                    }
+                    if operation == ArpOperation::Request && in_range(target_protocol_addr, range) {
+                        *self.requested.entry(target_protocol_addr).or_insert(0) += 1;
+                    }
+                }
+            }
+            EthernetProtocol::Ipv4 => {
+                if let Ok(packet) = Ipv4Packet::new_checked(eth_frame.payload()) {
+                    let sources = self
+                        .sources
+                        .entry(eth_frame.src_addr())
+                        .or_insert_with(BTreeSet::new);
+                    if sources.len() < MAX_SOURCES && packet.src_addr().is_unicast() {
+                        sources.insert(packet.src_addr());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Guess the subnet in use from the longest prefix shared by all observed hosts
+    pub fn subnet(&self, range: &Ipv4Cidr) -> Option<Ipv4Cidr> {
+        let mut addrs = self.hosts.keys().map(|x| to_u32(*x));
+        let first = addrs.next()?;
+        let common = addrs.fold(32, |len, x| len.min((first ^ x).leading_zeros()));
+        let prefix_len = (common as u8).min(MAX_PREFIX_LEN).max(range.prefix_len());
+        Some(Ipv4Cidr::new(
+            from_u32(first & netmask(prefix_len)),
+            prefix_len,
+        ))
+    }
+
+    /// Guess the gateway of subnet
+    /// A router forwards packets from many source addresses outside of the subnet, if no
+    /// such host was seen the address most hosts ask for is assumed to be the gateway
+    pub fn gateway(&self, subnet: &Ipv4Cidr) -> Option<Ipv4Address> {
+        let router = self
+            .sources
+            .iter()
+            .map(|(mac, addrs)| {
+                (
+                    mac,
+                    addrs.iter().filter(|x| !subnet.contains_addr(x)).count(),
+                )
+            })
+            .filter(|(_, count)| *count > 1)
+            .max_by_key(|(_, count)| *count)
+            .and_then(|(mac, _)| {
+                self.hosts
+                    .iter()
+                    .find(|(addr, x)| *x == mac && subnet.contains_addr(addr))
+                    .map(|(addr, _)| *addr)
+            });
+        router.or_else(|| {
+            self.requested
+                .iter()
+                .filter(|(addr, count)| **count > 1 && subnet.contains_addr(addr))
+                .max_by_key(|(_, count)| **count)
+                .map(|(addr, _)| *addr)
+        })
+    }
+}
+
+/// Pick a host address of subnet that is not in taken, starting at a random offset
+pub fn candidate(
+    subnet: &Ipv4Cidr,
+    taken: &BTreeSet<Ipv4Address>,
+    random: u32,
+) -> Option<Ipv4Address> {
+    if subnet.prefix_len() >= 31 {
+        return None;
+    }
+    let network = to_u32(subnet.address()) & netmask(subnet.prefix_len());
+    // Host parts without the network and broadcast address
+    let hosts = (1u64 << (32 - subnet.prefix_len())) - 2;
+    let start = u64::from(random) % hosts;
+    (0..hosts.min(1024))
+        .map(|i| from_u32(network + 1 + ((start + i) % hosts) as u32))
+        .find(|x| !taken.contains(x))
+}
+
+/// Listen passively for window and collect the addresses in use inside range
+pub fn survey(iface: &mut NetDevice, range: &Ipv4Cidr, window: Duration) -> Survey {
+    let mut survey = Survey::default();
+    let start = Instant::from_millis(system_clock::ms() as i64);
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        if timestamp - start > window {
+            break;
+        }
+        let (rx_token, _) = match iface.receive() {
+            None => continue,
+            Some(tokens) => tokens,
+        };
+        rx_token
+            .consume(timestamp, |frame| {
+                survey.process_frame(&frame, range);
+                Ok(())
+            })
+            .unwrap_or(());
+    }
+    survey
+}
+
+/// Learn the subnet and gateway used inside range and claim a free address in it
+/// Returns None if no traffic was observed inside range, a link-local address should be
+/// claimed instead
+pub fn choose_address(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    rng: &mut random::Rng,
+    range: &Ipv4Cidr,
+) -> Result<Option<Selection>, String> {
+    let survey = survey(iface, range, SURVEY_WINDOW);
+    let subnet = match survey.subnet(range) {
+        Some(x) => x,
+        // Nobody to talk to in range, only directly connected hosts can be reached
+        None => return Ok(None),
+    };
+    let gateway = survey.gateway(&subnet);
+
+    let mut taken: BTreeSet<Ipv4Address> = survey.hosts.keys().cloned().collect();
+    taken.extend(gateway);
+    for _ in 0..acd::MAX_CONFLICTS {
+        let random = rng.poll_and_get().expect("RNG Failed");
+        let addr = candidate(&subnet, &taken, random).ok_or("No free address in subnet")?;
+        if acd::probe(iface, eth_addr, rng, addr)? {
+            acd::announce(iface, eth_addr, addr)?;
+            return Ok(Some(Selection {
+                cidr: Ipv4Cidr::new(addr, subnet.prefix_len()),
+                gateway,
+                hosts: survey.hosts,
+            }));
+        }
+        taken.insert(addr);
+    }
+    Err(String::from("Too many address conflicts"))
+}
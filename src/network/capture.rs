@@ -0,0 +1,150 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use cortex_m::peripheral::DCB;
+use cortex_m_semihosting::{hio, nr};
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::socket::*;
+use smoltcp::time::{Duration, Instant};
+use stm32f7_discovery::system_clock;
+
+use super::pcapfile::CaptureSink;
+
+/// Port a host-side receiver connects to, e.g. with `nc <board> 5555 > capture.pcap`
+pub const CAPTURE_PORT: u16 = 5555;
+/// File the capture is written to on the debugging host
+pub const CAPTURE_FILE: &str = "capture.pcap";
+const ACCEPT_TIMEOUT: Duration = Duration { millis: 30_000 };
+const SEND_TIMEOUT: Duration = Duration { millis: 5_000 };
+const CLOSE_TIMEOUT: Duration = Duration { millis: 2_000 };
+
+/// Check whether a debugger is attached, semihosting calls halt the board otherwise
+pub fn debugger_attached() -> bool {
+    // C_DEBUGEN of the Debug Halting Control and Status Register is only set by a debugger
+    unsafe { (*DCB::ptr()).dhcsr.read() & 1 != 0 }
+}
+
+/// Writes to a file on the host through semihosting
+/// Only usable while a debugger is attached, semihosting calls halt the board otherwise
+pub struct SemihostingSink {
+    file: hio::HStdout,
+}
+
+impl SemihostingSink {
+    /// Create or truncate the file at path on the host
+    pub fn create(path: &str) -> Result<Self, String> {
+        if !debugger_attached() {
+            return Err(String::from("No debugger attached to write files to"));
+        }
+        // Semihosting expects a nul terminated path
+        let mut name = String::from(path);
+        name.push('\0');
+        hio::open(&name, nr::open::W_TRUNC_BINARY)
+            .map(|file| SemihostingSink { file })
+            .map_err(|_| format!("Unable to open {} on the host", path))
+    }
+}
+
+impl CaptureSink for SemihostingSink {
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.file
+            .write_all(data)
+            .map_err(|_| String::from("Semihosting write failed"))
+    }
+}
+
+/// Streams to a receiver connected over TCP
+pub struct TcpSink<'a, 'b, 'c, 'e, DeviceT>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    iface: &'a mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    sockets: SocketSet<'static, 'static, 'static>,
+    handle: SocketHandle,
+}
+
+impl<'a, 'b, 'c, 'e, DeviceT> TcpSink<'a, 'b, 'c, 'e, DeviceT>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    /// Listen on port and wait for a receiver to connect
+    pub fn accept(
+        iface: &'a mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+        port: u16,
+    ) -> Result<Self, String> {
+        let mut sockets = SocketSet::new(Vec::new());
+        let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; 64]);
+        let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; 2048]);
+        let handle = sockets.add(TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer));
+        sockets
+            .get::<TcpSocket>(handle)
+            .listen(port)
+            .map_err(|_| String::from("Unable to listen for a receiver"))?;
+
+        let mut sink = TcpSink {
+            iface,
+            sockets,
+            handle,
+        };
+        let start = Instant::from_millis(system_clock::ms() as i64);
+        while !sink.sockets.get::<TcpSocket>(handle).may_send() {
+            if sink.poll() - start > ACCEPT_TIMEOUT {
+                return Err(String::from("No receiver connected"));
+            }
+        }
+        Ok(sink)
+    }
+
+    fn poll(&mut self) -> Instant {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        match self.iface.poll(&mut self.sockets, timestamp) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+        timestamp
+    }
+
+    /// Send the remaining data and close the connection
+    pub fn close(mut self) {
+        self.sockets.get::<TcpSocket>(self.handle).close();
+        let start = Instant::from_millis(system_clock::ms() as i64);
+        while self.sockets.get::<TcpSocket>(self.handle).is_active() {
+            if self.poll() - start > CLOSE_TIMEOUT {
+                self.sockets.get::<TcpSocket>(self.handle).abort();
+                self.poll();
+                break;
+            }
+        }
+    }
+}
+
+impl<'a, 'b, 'c, 'e, DeviceT> CaptureSink for TcpSink<'a, 'b, 'c, 'e, DeviceT>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut sent = 0;
+        let mut last_progress = Instant::from_millis(system_clock::ms() as i64);
+        while sent < data.len() {
+            {
+                let mut socket = self.sockets.get::<TcpSocket>(self.handle);
+                if !socket.may_send() {
+                    return Err(String::from("Receiver closed the connection"));
+                }
+                if socket.can_send() {
+                    let count = socket
+                        .send_slice(&data[sent..])
+                        .map_err(|_| String::from("Sending to the receiver failed"))?;
+                    if count > 0 {
+                        sent += count;
+                        last_progress = Instant::from_millis(system_clock::ms() as i64);
+                    }
+                }
+            }
+            if self.poll() - last_progress > SEND_TIMEOUT {
+                return Err(String::from("Receiver stopped accepting data"));
+            }
+        }
+        Ok(())
+    }
+}
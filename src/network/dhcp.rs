@@ -0,0 +1,86 @@
+use alloc::string::{String, ToString};
+use smoltcp::phy::{Device, RxToken};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::*;
+use stm32f7_discovery::{random, system_clock};
+
+use super::eth::{dispatch_ethernet, NetDevice};
+
+pub use netscan::dhcp::*;
+
+/// Broadcast a DHCPDISCOVER and collect every DHCPOFFER received within window
+pub fn audit(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    rng: &mut random::Rng,
+    window: Duration,
+) -> Result<DhcpOffers, String> {
+    let mut offers = DhcpOffers::new();
+    let xid = rng.poll_and_get().expect("RNG Failed");
+    let checksum_caps = iface.capabilities().checksum;
+
+    let payload = emit_discover(xid, eth_addr);
+    let udp_repr = UdpRepr {
+        src_port: DHCP_CLIENT_PORT,
+        dst_port: DHCP_SERVER_PORT,
+        payload: &payload,
+    };
+    let ip_repr = Ipv4Repr {
+        src_addr: Ipv4Address::UNSPECIFIED,
+        dst_addr: Ipv4Address::BROADCAST,
+        protocol: IpProtocol::Udp,
+        payload_len: udp_repr.buffer_len(),
+        hop_limit: 64,
+    };
+
+    let tx_token = match iface.transmit() {
+        Some(x) => x,
+        None => return Err(String::from("No tx descriptor available")),
+    };
+    match dispatch_ethernet(
+        eth_addr,
+        tx_token,
+        Instant::from_millis(system_clock::ms() as i64),
+        ip_repr.buffer_len() + ip_repr.payload_len,
+        |mut frame| {
+            frame.set_dst_addr(EthernetAddress::BROADCAST);
+            frame.set_ethertype(EthernetProtocol::Ipv4);
+
+            let mut ip_packet = Ipv4Packet::new_unchecked(frame.payload_mut());
+            ip_repr.emit(&mut ip_packet, &checksum_caps);
+            let mut udp_packet = UdpPacket::new_unchecked(ip_packet.payload_mut());
+            udp_repr.emit(
+                &mut udp_packet,
+                &ip_repr.src_addr.into(),
+                &ip_repr.dst_addr.into(),
+                &checksum_caps,
+            );
+        },
+    ) {
+        Ok(_) => {}
+        Err(x) => return Err(x.to_string()),
+    };
+
+    let start = Instant::from_millis(system_clock::ms() as i64);
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        if timestamp - window > start {
+            break;
+        }
+        let (rx_token, _) = match iface.receive() {
+            None => continue,
+            Some(tokens) => tokens,
+        };
+        if let Ok(offer) = rx_token.consume(timestamp, |frame| process_offer(eth_addr, xid, &frame))
+        {
+            // Servers may retransmit their offer, only keep the first one
+            if !offers
+                .iter()
+                .any(|x| x.server == offer.server && x.mac == offer.mac)
+            {
+                offers.push(offer);
+            }
+        }
+    }
+    Ok(offers)
+}
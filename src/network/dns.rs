@@ -0,0 +1,282 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::socket::*;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use stm32f7_discovery::{random, system_clock};
+
+use super::arp::ArpResponses;
+
+pub use netscan::dns::*;
+
+/// Queries are sent from a random port of the dynamic range, 49152 to 65535
+const DYNAMIC_PORTS_START: u16 = 49152;
+const DYNAMIC_PORTS: u32 = 16384;
+/// Time to wait for a response before asking the next server
+const QUERY_TIMEOUT: Duration = Duration { millis: 1000 };
+/// Number of queries sent for each question before giving up
+const QUERY_ATTEMPTS: usize = 3;
+
+/// Send all questions to the DNS servers and collect the responses
+/// A question that is not answered in time is sent to the next server, the responses
+/// are returned in the order of the questions
+/// Source port and ids are random and a response is only accepted from a server the question
+/// was sent to, so spoofed responses have to guess both
+pub fn query<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    rng: &mut random::Rng,
+    servers: &[Ipv4Address],
+    questions: &[(String, u16)],
+) -> Vec<Option<Message>>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut responses: Vec<Option<Message>> = questions.iter().map(|_| None).collect();
+    if servers.is_empty() {
+        return responses;
+    }
+    // Time of the last query and number of queries sent for each question
+    let mut attempts: Vec<(Option<Instant>, usize)> = vec![(None, 0); questions.len()];
+    let mut ids: Vec<u16> = Vec::new();
+    while ids.len() < questions.len() {
+        let id = rng.poll_and_get().expect("RNG Failed") as u16;
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    let local_port =
+        DYNAMIC_PORTS_START + (rng.poll_and_get().expect("RNG Failed") % DYNAMIC_PORTS) as u16;
+
+    let mut sockets = SocketSet::new(Vec::new());
+    let udp_rx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 4], vec![0; 2048]);
+    let udp_tx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 4], vec![0; 1024]);
+    let udp_handle = sockets.add(UdpSocket::new(udp_rx_buffer, udp_tx_buffer));
+    sockets
+        .get::<UdpSocket>(udp_handle)
+        .bind(local_port)
+        .unwrap();
+
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        match iface.poll(&mut sockets, timestamp) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+
+        let mut pending = false;
+        {
+            let mut socket = sockets.get::<UdpSocket>(udp_handle);
+            while socket.can_recv() {
+                let (payload, endpoint) = socket.recv().unwrap();
+                let msg = match parse_message(payload) {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                let index = match ids.iter().position(|x| *x == msg.id) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                // Attempt n of a question is sent to server n
+                let asked = &servers[..attempts[index].1.min(servers.len())];
+                let from_server = match endpoint.addr {
+                    IpAddress::Ipv4(addr) => endpoint.port == DNS_PORT && asked.contains(&addr),
+                    _ => false,
+                };
+                let (ref name, qtype) = questions[index];
+                if from_server && responses[index].is_none() && msg.answers(ids[index], name, qtype)
+                {
+                    responses[index] = Some(msg);
+                }
+            }
+
+            for (index, question) in questions.iter().enumerate() {
+                if responses[index].is_some() {
+                    continue;
+                }
+                match attempts[index] {
+                    (Some(sent_at), _) if timestamp - sent_at < QUERY_TIMEOUT => pending = true,
+                    (_, count) if count >= QUERY_ATTEMPTS => {}
+                    (_, count) => {
+                        pending = true;
+                        if !socket.can_send() {
+                            continue;
+                        }
+                        let server = servers[count % servers.len()];
+                        match emit_query(ids[index], &question.0, question.1) {
+                            Ok(packet) => {
+                                socket
+                                    .send_slice(&packet, IpEndpoint::new(server.into(), DNS_PORT))
+                                    .unwrap_or(());
+                                attempts[index] = (Some(timestamp), count + 1);
+                            }
+                            // Invalid names are never sent
+                            Err(_) => attempts[index] = (None, QUERY_ATTEMPTS),
+                        }
+                    }
+                }
+            }
+        }
+
+        if !pending {
+            break;
+        }
+        system_clock::wait_ms(10);
+    }
+    responses
+}
+
+/// Look up the hostname of every address in addrs that is not cached yet
+pub fn resolve_ptr<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    rng: &mut random::Rng,
+    cache: &mut DnsCache,
+    servers: &[Option<Ipv4Address>],
+    addrs: &ArpResponses,
+) where
+    DeviceT: for<'d> Device<'d>,
+{
+    let addrs: Vec<Ipv4Address> = addrs.keys().cloned().collect();
+    resolve_ptr_addrs(iface, rng, cache, servers, &addrs);
+}
+
+/// Look up the hostname of every address in addrs that is not cached yet
+pub fn resolve_ptr_addrs<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    rng: &mut random::Rng,
+    cache: &mut DnsCache,
+    servers: &[Option<Ipv4Address>],
+    addrs: &[Ipv4Address],
+) where
+    DeviceT: for<'d> Device<'d>,
+{
+    let timestamp = Instant::from_millis(system_clock::ms() as i64);
+    let servers: Vec<Ipv4Address> = servers.iter().filter_map(|x| *x).collect();
+    let addrs: Vec<Ipv4Address> = addrs
+        .iter()
+        .filter(|x| cache.lookup_ptr(**x, timestamp).is_none())
+        .cloned()
+        .collect();
+    let questions: Vec<(String, u16)> = addrs.iter().map(|x| (ptr_name(*x), TYPE_PTR)).collect();
+
+    let responses = query(iface, rng, &servers, &questions);
+
+    let timestamp = Instant::from_millis(system_clock::ms() as i64);
+    for (addr, response) in addrs.into_iter().zip(responses.into_iter()) {
+        let answer = response.and_then(|msg| {
+            msg.answers.into_iter().find_map(|record| match record {
+                Record {
+                    rtype: TYPE_PTR,
+                    ttl,
+                    data: RecordData::Name(name),
+                    ..
+                } => Some((name, ttl)),
+                _ => None,
+            })
+        });
+        match answer {
+            Some((name, ttl)) => cache.insert_ptr(addr, Some(name), ttl, timestamp),
+            None => cache.insert_ptr(addr, None, NEGATIVE_TTL, timestamp),
+        }
+    }
+}
+
+/// Resolve name to its IPv4 and IPv6 addresses
+/// CNAME records are followed until addresses are found, names that do not exist are cached
+/// as well
+pub fn resolve<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    rng: &mut random::Rng,
+    cache: &mut DnsCache,
+    servers: &[Option<Ipv4Address>],
+    name: &str,
+) -> Result<Vec<IpAddress>, String>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let key = name.trim_end_matches('.').to_ascii_lowercase();
+    let timestamp = Instant::from_millis(system_clock::ms() as i64);
+    match cache.lookup_host(&key, timestamp) {
+        Some(Some(addrs)) => return Ok(addrs.clone()),
+        Some(None) => return Err(format!("{}: host not found", name)),
+        None => {}
+    }
+
+    let servers: Vec<Ipv4Address> = servers.iter().filter_map(|x| *x).collect();
+    if servers.is_empty() {
+        return Err(String::from("No DNS server configured"));
+    }
+
+    let mut target = key.clone();
+    for _ in 0..MAX_CNAME_DEPTH {
+        let questions = vec![(target.clone(), TYPE_A), (target.clone(), TYPE_AAAA)];
+        let messages: Vec<Message> = query(iface, rng, &servers, &questions)
+            .into_iter()
+            .flatten()
+            .filter(|x| x.rcode() == RCODE_NO_ERROR || x.rcode() == RCODE_NAME_ERROR)
+            .collect();
+        if messages.is_empty() {
+            // Timeouts and server failures are not cached
+            return Err(String::from("No valid response from DNS servers"));
+        }
+
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        let mut addrs = Vec::new();
+        let mut ttl = MAX_TTL;
+        let mut next = None;
+        for msg in messages.iter() {
+            let (last, found, chain_ttl) = follow_answers(msg, &target);
+            if found.is_empty() {
+                if last != target {
+                    // The server only returned the CNAME, ask for its target
+                    next = Some(last);
+                }
+            } else {
+                addrs.extend(found);
+                ttl = ttl.min(chain_ttl);
+            }
+        }
+
+        if !addrs.is_empty() {
+            cache.insert_host(key, Some(addrs.clone()), ttl, timestamp);
+            return Ok(addrs);
+        }
+        match next {
+            Some(x) => target = x.to_ascii_lowercase(),
+            None => {
+                let ttl = messages
+                    .iter()
+                    .map(negative_ttl)
+                    .min()
+                    .unwrap_or(NEGATIVE_TTL);
+                cache.insert_host(key, None, ttl, timestamp);
+                return Err(format!("{}: host not found", name));
+            }
+        }
+    }
+    Err(format!("{}: CNAME chain too long", name))
+}
+
+/// Resolve a scan target given as IPv4 address or hostname to an IPv4 address
+pub fn resolve_target<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    rng: &mut random::Rng,
+    cache: &mut DnsCache,
+    servers: &[Option<Ipv4Address>],
+    target: &str,
+) -> Result<Ipv4Address, String>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    if let Ok(addr) = target.parse::<Ipv4Address>() {
+        return Ok(addr);
+    }
+    resolve(iface, rng, cache, servers, target)?
+        .into_iter()
+        .find_map(|x| match x {
+            IpAddress::Ipv4(addr) => Some(addr),
+            _ => None,
+        })
+        .ok_or_else(|| format!("{}: no IPv4 address", target))
+}
@@ -0,0 +1,215 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use smoltcp::time::Instant;
+use smoltcp::wire::*;
+
+use super::dns::{self, Message};
+use super::ipv6;
+use super::Stringable;
+
+/// Maximum number of clients logged, the least recently active one is evicted beyond that
+pub const MAX_CLIENTS: usize = 8;
+/// Lookups kept per client, older ones are dropped
+pub const MAX_LOOKUPS: usize = 16;
+
+/// A name looked up by a client and the answer it got
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    pub timestamp: Instant,
+    /// Transaction id, matches a response to its query
+    pub id: u16,
+    pub name: String,
+    pub qtype: u16,
+    /// Summary of the answer, None while no response was seen
+    pub answer: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ClientLog {
+    pub lookups: VecDeque<Lookup>,
+    /// Number of queries seen in total, including dropped ones
+    pub queries: usize,
+    pub last_seen: Option<Instant>,
+}
+
+impl ClientLog {
+    fn push(&mut self, lookup: Lookup) {
+        if self.lookups.len() >= MAX_LOOKUPS {
+            self.lookups.pop_front();
+        }
+        self.last_seen = Some(lookup.timestamp);
+        self.lookups.push_back(lookup);
+    }
+}
+
+impl super::StringableVec for ClientLog {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for lookup in self.lookups.iter() {
+            let millis = lookup.timestamp.total_millis();
+            ret.push(format!(
+                "{}.{:03} {} {}",
+                millis / 1000,
+                millis % 1000,
+                dns::type_name(lookup.qtype),
+                lookup.name
+            ));
+            match lookup.answer {
+                Some(ref x) => ret.push(format!("    {}", x)),
+                None => ret.push(String::from("    no response")),
+            }
+        }
+        ret
+    }
+}
+
+/// DNS lookups observed on the segment per client
+#[derive(Debug, Default)]
+pub struct DnsLog {
+    pub clients: BTreeMap<IpAddress, ClientLog>,
+}
+
+impl DnsLog {
+    fn client(&mut self, addr: IpAddress, timestamp: Instant) -> &mut ClientLog {
+        if !self.clients.contains_key(&addr) && self.clients.len() >= MAX_CLIENTS {
+            let oldest = self
+                .clients
+                .iter()
+                .min_by_key(|(_, x)| x.last_seen)
+                .map(|(addr, _)| *addr);
+            if let Some(oldest) = oldest {
+                self.clients.remove(&oldest);
+            }
+        }
+        let client = self.clients.entry(addr).or_insert_with(ClientLog::default);
+        client.last_seen = Some(timestamp);
+        client
+    }
+
+    /// Log the DNS query or response carried by an untagged frame
+    pub fn process_frame(&mut self, frame: &[u8], timestamp: Instant) {
+        let (src, dst, msg) = match parse_frame(frame) {
+            Some(x) => x,
+            None => return,
+        };
+        let question = match msg.questions.first() {
+            Some(x) => x,
+            None => return,
+        };
+
+        if !msg.is_response() {
+            let client = self.client(src, timestamp);
+            client.queries += 1;
+            client.push(Lookup {
+                timestamp,
+                id: msg.id,
+                name: question.name.clone(),
+                qtype: question.qtype,
+                answer: None,
+            });
+            return;
+        }
+
+        let answer = answer_summary(&msg, question.qtype);
+        let client = self.client(dst, timestamp);
+        let pending = client.lookups.iter_mut().rev().find(|x| {
+            x.answer.is_none()
+                && x.id == msg.id
+                && x.qtype == question.qtype
+                && x.name.eq_ignore_ascii_case(&question.name)
+        });
+        match pending {
+            Some(lookup) => lookup.answer = Some(answer),
+            // The query was missed, e.g. because listening started in between
+            None => client.push(Lookup {
+                timestamp,
+                id: msg.id,
+                name: question.name.clone(),
+                qtype: question.qtype,
+                answer: Some(answer),
+            }),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.clients.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+}
+
+impl super::StringableVec for DnsLog {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        for (addr, client) in self.clients.iter() {
+            ret.push(format!("{}: {} queries", addr, client.queries));
+            if let Some(lookup) = client.lookups.back() {
+                ret.push(format!("    last: {}", lookup.name));
+            }
+        }
+        ret
+    }
+}
+
+/// Return source, destination and the DNS message of an untagged frame
+fn parse_frame(frame: &[u8]) -> Option<(IpAddress, IpAddress, Message)> {
+    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+    let (src, dst, protocol, payload) = match eth_frame.ethertype() {
+        EthernetProtocol::Ipv4 => {
+            let packet = Ipv4Packet::new_checked(eth_frame.payload()).ok()?;
+            (
+                IpAddress::Ipv4(packet.src_addr()),
+                IpAddress::Ipv4(packet.dst_addr()),
+                packet.protocol(),
+                packet.payload(),
+            )
+        }
+        EthernetProtocol::Ipv6 => {
+            let packet = Ipv6Packet::new_checked(eth_frame.payload()).ok()?;
+            let upper = ipv6::upper_layer(packet.next_header(), packet.payload()).ok()?;
+            (
+                IpAddress::Ipv6(packet.src_addr()),
+                IpAddress::Ipv6(packet.dst_addr()),
+                upper.protocol,
+                upper.payload?,
+            )
+        }
+        _ => return None,
+    };
+    if protocol != IpProtocol::Udp {
+        return None;
+    }
+    let udp_packet = UdpPacket::new_checked(payload).ok()?;
+    if udp_packet.src_port() != dns::DNS_PORT && udp_packet.dst_port() != dns::DNS_PORT {
+        return None;
+    }
+    let msg = dns::parse_message(udp_packet.payload()).ok()?;
+    Some((src, dst, msg))
+}
+
+/// Summarize the answers of a response to a query of type qtype
+fn answer_summary(msg: &Message, qtype: u16) -> String {
+    match msg.rcode() {
+        dns::RCODE_NO_ERROR => {}
+        dns::RCODE_NAME_ERROR => return String::from("NXDOMAIN"),
+        x => return format!("error {}", x),
+    }
+    let answers: Vec<String> = msg
+        .answers
+        .iter()
+        .filter(|x| x.rtype == qtype)
+        .map(|x| x.data.to_string())
+        .collect();
+    match answers.len() {
+        // Only a CNAME without the records it points to
+        0 => match msg.answers.first() {
+            Some(x) => format!("{} {}", dns::type_name(x.rtype), x.data.to_string()),
+            None => String::from("no answer"),
+        },
+        1 => answers[0].clone(),
+        n => format!("{} (+{} more)", answers[0], n - 1),
+    }
+}
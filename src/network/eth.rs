@@ -1,48 +1,239 @@
+use alloc::borrow::Cow;
 use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::iface::{EthernetInterface, EthernetInterfaceBuilder, NeighborCache, Routes};
 use smoltcp::phy::{Device, DeviceCapabilities, RxToken, TxToken};
 use smoltcp::time::Instant;
 use smoltcp::wire::*;
 use stm32f7_discovery::{ethernet::EthernetDevice, system_clock};
 
 use super::arp::ArpResponses;
+use super::dissect::LiveView;
+use super::dnslog::DnsLog;
+use super::filter::Filter;
+use super::flows::{FlowKey, FlowTable};
+use super::ipv6::{self, Ipv6Neighbors};
+use super::lldp::LinkNeighbors;
+use super::pcap::Capture;
+use super::stats::{self, ProtocolMix, StatsResponses};
 
-pub type StatsResponses = BTreeMap<Ipv4Address, (usize, usize, Instant)>;
-
-impl super::StringableVec for StatsResponses {
-    fn to_string_vec(&self) -> Vec<String> {
-        let mut ret: Vec<String> = Vec::new();
-        let now_s = Instant::from_millis(system_clock::ms() as i64).secs();
-        for i in self.iter() {
-            ret.push(format!("{}:", i.0));
-            let (count, bytes, ts) = i.1;
-            ret.push(format!("    {} packets", count));
-            ret.push(format!("    {} bytes", bytes));
-            if now_s > ts.secs() {
-                ret.push(format!(
-                    "    {} bytes / second",
-                    (*bytes as i64) / (now_s - ts.secs())
-                ));
-            }
-            ret.push(String::from(""));
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const VLAN_TAG_LEN: usize = 4;
+const VLAN_ID_MASK: u16 = 0x0fff;
+
+/// Ethernet device of the board, sending and receiving on the selected VLAN
+pub type NetDevice = VlanDevice<EthernetDevice>;
+
+/// Device sending and receiving all frames on the selected VLAN
+/// Frames of the interface sockets and raw frames are tagged alike
+pub struct VlanDevice<D> {
+    pub inner: D,
+    /// VLAN id of all frames, None sends and receives them untagged
+    pub vlan: Option<u16>,
+}
+
+impl<D> VlanDevice<D> {
+    pub fn new(inner: D) -> VlanDevice<D> {
+        VlanDevice { inner, vlan: None }
+    }
+
+    /// Select the VLAN frames are sent and received on, None uses untagged frames
+    pub fn set_vlan(&mut self, id: Option<u16>) {
+        self.vlan = id.map(|x| x & VLAN_ID_MASK);
+    }
+}
+
+impl<'a, D> VlanDevice<D>
+where
+    D: Device<'a>,
+{
+    /// Receive a frame as seen on the wire, including its tag
+    /// Frames sent with the returned token are still tagged with the selected VLAN
+    pub fn receive_tagged(&'a mut self) -> Option<(D::RxToken, VlanTxToken<D::TxToken>)> {
+        let vlan = self.vlan;
+        self.inner
+            .receive()
+            .map(|(rx_token, tx_token)| (rx_token, VlanTxToken::new(tx_token, vlan)))
+    }
+}
+
+impl<'a, D> Device<'a> for VlanDevice<D>
+where
+    D: Device<'a>,
+{
+    type RxToken = VlanRxToken<D::RxToken>;
+    type TxToken = VlanTxToken<D::TxToken>;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        let vlan = self.vlan;
+        self.inner.receive().map(|(rx_token, tx_token)| {
+            (
+                VlanRxToken {
+                    inner: rx_token,
+                    vlan,
+                },
+                VlanTxToken::new(tx_token, vlan),
+            )
+        })
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        let vlan = self.vlan;
+        self.inner
+            .transmit()
+            .map(|tx_token| VlanTxToken::new(tx_token, vlan))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        // The tag takes away from the frame size of the inner device
+        let mut caps = self.inner.capabilities();
+        if self.vlan.is_some() {
+            caps.max_transmission_unit -= VLAN_TAG_LEN;
+        }
+        caps
+    }
+}
+
+/// Receive token removing the tag of the selected VLAN, frames of other VLANs are dropped
+pub struct VlanRxToken<T> {
+    inner: T,
+    vlan: Option<u16>,
+}
+
+impl<T: RxToken> RxToken for VlanRxToken<T> {
+    fn consume<R, F>(self, timestamp: Instant, f: F) -> Result<R, smoltcp::Error>
+    where
+        F: FnOnce(&[u8]) -> Result<R, smoltcp::Error>,
+    {
+        let vlan = self.vlan;
+        self.inner
+            .consume(timestamp, |frame| f(&untag_frame(frame, vlan)?))
+    }
+}
+
+/// Transmit token inserting the tag of the selected VLAN
+pub struct VlanTxToken<T> {
+    inner: T,
+    vlan: Option<u16>,
+}
+
+impl<T> VlanTxToken<T> {
+    fn new(inner: T, vlan: Option<u16>) -> VlanTxToken<T> {
+        VlanTxToken { inner, vlan }
+    }
+}
+
+impl<T: TxToken> TxToken for VlanTxToken<T> {
+    fn consume<R, F>(self, timestamp: Instant, len: usize, f: F) -> Result<R, smoltcp::Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<R, smoltcp::Error>,
+    {
+        let id = match self.vlan {
+            Some(x) => x,
+            None => return self.inner.consume(timestamp, len, f),
+        };
+        self.inner
+            .consume(timestamp, len + VLAN_TAG_LEN, |tx_buffer| {
+                // Build the untagged frame behind the space of the tag
+                let result = f(&mut tx_buffer[VLAN_TAG_LEN..])?;
+
+                // Move the addresses to the front and insert the tag before the ethertype
+                let mut addrs = [0; 12];
+                addrs.copy_from_slice(&tx_buffer[VLAN_TAG_LEN..VLAN_TAG_LEN + 12]);
+                tx_buffer[..12].copy_from_slice(&addrs);
+                NetworkEndian::write_u16(&mut tx_buffer[12..14], ETHERTYPE_VLAN);
+                NetworkEndian::write_u16(&mut tx_buffer[14..16], id);
+                Ok(result)
+            })
+    }
+}
+
+/// Build the interface on top of device, the sockets use the VLAN selected on the device
+pub fn into_interface<'a>(
+    device: EthernetDevice,
+    eth_addr: EthernetAddress,
+) -> EthernetInterface<'a, 'a, 'a, NetDevice> {
+    EthernetInterfaceBuilder::new(VlanDevice::new(device))
+        .ethernet_addr(eth_addr)
+        .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+        .ip_addrs(vec![IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)])
+        .routes(Routes::new(BTreeMap::new()))
+        .finalize()
+}
+
+/// Number of frames seen per VLAN id
+pub type VlanCounts = BTreeMap<u16, usize>;
+
+/// Format the frames seen per VLAN id and mark the selected VLAN
+pub fn vlan_lines(counts: &VlanCounts, selected: Option<u16>) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    for (id, count) in counts.iter() {
+        if selected == Some(*id) {
+            ret.push(format!("VLAN {}: {} frames (selected)", id, count));
+        } else {
+            ret.push(format!("VLAN {}: {} frames", id, count));
         }
-        ret
+    }
+    ret
+}
+
+/// Everything collected while listening to the traffic on the link
+#[derive(Default)]
+pub struct Monitor {
+    pub stats: StatsResponses,
+    pub totals: ProtocolMix,
+    pub flows: FlowTable,
+    pub capture: Capture,
+    pub dns_log: DnsLog,
+    /// Frames not matching the filter are neither counted nor captured
+    pub filter: Option<Filter>,
+    pub link: LinkNeighbors,
+    pub ipv6_neighbors: Ipv6Neighbors,
+    pub vlans: VlanCounts,
+    /// Frames are only dissected while the live view is shown
+    pub live: Option<LiveView>,
+}
+
+/// Return the VLAN id of a 802.1Q tagged frame
+pub fn frame_vlan(frame: &[u8]) -> Option<u16> {
+    if frame.len() >= 14 + VLAN_TAG_LEN && NetworkEndian::read_u16(&frame[12..14]) == ETHERTYPE_VLAN
+    {
+        Some(NetworkEndian::read_u16(&frame[14..16]) & VLAN_ID_MASK)
+    } else {
+        None
+    }
+}
+
+/// Remove the tag of vlan from frame
+/// Frames of other VLANs are dropped, untagged frames only pass if vlan is None
+pub fn untag_frame(frame: &[u8], vlan: Option<u16>) -> Result<Cow<[u8]>, smoltcp::Error> {
+    match (vlan, frame_vlan(frame)) {
+        (None, None) => Ok(Cow::Borrowed(frame)),
+        (Some(x), Some(y)) if x == y => {
+            let mut untagged = Vec::with_capacity(frame.len() - VLAN_TAG_LEN);
+            untagged.extend_from_slice(&frame[..12]);
+            untagged.extend_from_slice(&frame[12 + VLAN_TAG_LEN..]);
+            Ok(Cow::Owned(untagged))
+        }
+        _ => Err(smoltcp::Error::Dropped),
     }
 }
 
 pub fn listen(
-    stats: &mut StatsResponses,
-    iface: &mut EthernetDevice,
+    monitor: &mut Monitor,
+    iface: &mut NetDevice,
     eth_addr: EthernetAddress,
     neighbors: &ArpResponses,
     gw: Option<Ipv4Address>,
 ) -> Result<(), String> {
     let mut tries = 0;
     let caps = iface.capabilities();
+    let vlan = iface.vlan;
     loop {
-        // Try to receive all packets currently in the buffer
-        let (rx_token, tx_token) = match iface.receive() {
+        // Try to receive all packets currently in the buffer, still tagged to count all VLANs
+        let (rx_token, tx_token) = match iface.receive_tagged() {
             None => {
                 if tries > 100 {
                     break;
@@ -55,50 +246,83 @@ pub fn listen(
         rx_token
             .consume(Instant::from_millis(system_clock::ms() as i64), |frame| {
                 let timestamp = Instant::from_millis(system_clock::ms() as i64);
+                let wanted = match monitor.filter {
+                    Some(ref filter) => filter.matches_frame(&frame),
+                    None => true,
+                };
+                // Frames are captured as seen on the wire, including their VLAN tag
+                if wanted {
+                    monitor.capture.record(&frame, timestamp);
+                    if let Some(ref mut live) = monitor.live {
+                        live.add(&frame, timestamp);
+                    }
+                }
+                if let Some(id) = frame_vlan(&frame) {
+                    *monitor.vlans.entry(id).or_insert(0) += 1;
+                }
+                // Switch announcements are only recorded, never forwarded
+                if let Some((src, neighbor)) = super::lldp::process_frame(&frame, timestamp) {
+                    monitor.link.insert(src, neighbor);
+                    return Ok(());
+                }
+                // Frames of other VLANs are neither counted nor forwarded
+                let frame = match untag_frame(&frame, vlan) {
+                    Ok(x) => x,
+                    Err(_) => return Ok(()),
+                };
+                // Neighbor discovery tells which IPv6 hosts are on the link
+                if let Some((src, src_mac, message)) = ipv6::frame_ndp(&frame) {
+                    monitor
+                        .ipv6_neighbors
+                        .learn(src, src_mac, &message, timestamp);
+                }
+                // Collect statistics on all received packets
+                if wanted {
+                    if let Ok(observation) = stats::classify(&frame, neighbors) {
+                        stats::record(
+                            &mut monitor.stats,
+                            &mut monitor.totals,
+                            &observation,
+                            timestamp,
+                        );
+                    }
+                    if let Some(key) = FlowKey::parse(&frame) {
+                        monitor.flows.record(key, frame.len(), timestamp);
+                    }
+                    monitor.dns_log.process_frame(&frame, timestamp);
+                }
                 // Parse the raw ethernet frame and return the info necessary for a
                 // response/forward
-                process_eth(gw, &neighbors, eth_addr, &frame, &caps).and_then(
-                    |(x, (addr, bytes))| {
-                        // Collect statistics on received packets
-                        stats
-                            .entry(addr)
-                            .and_modify(|(count, total_bytes, _)| {
-                                *count += 1;
-                                *total_bytes += bytes
-                            })
-                            .or_insert((1, bytes, timestamp));
-                        if let Some((ethertype, dst, payload, len)) = x {
-                            // Dispatch returned packet
-                            dispatch_ethernet(eth_addr, tx_token, timestamp, len, |mut frame| {
-                                frame.set_dst_addr(dst);
-                                // frame.set_src_addr(eth_addr);
-                                frame.set_ethertype(ethertype);
-                                frame.payload_mut().copy_from_slice(&payload);
-                            })
-                        } else {
-                            Ok(())
-                        }
-                    },
-                )
+                process_eth(gw, &neighbors, eth_addr, &frame, &caps).and_then(|x| {
+                    if let Some((ethertype, dst, payload, len)) = x {
+                        // Dispatch returned packet
+                        dispatch_ethernet(eth_addr, tx_token, timestamp, len, |mut frame| {
+                            frame.set_dst_addr(dst);
+                            // frame.set_src_addr(eth_addr);
+                            frame.set_ethertype(ethertype);
+                            frame.payload_mut().copy_from_slice(&payload);
+                        })
+                    } else {
+                        Ok(())
+                    }
+                })
             })
             .or_else(|x| Err(x.to_string()))?;
     }
+    monitor
+        .flows
+        .expire(Instant::from_millis(system_clock::ms() as i64));
     Ok(())
 }
 
-fn process_eth<'a, T: AsRef<[u8]>>(
+/// Parse an untagged frame and return the packet to forward or answer with
+fn process_eth(
     gw: Option<Ipv4Address>,
     neighbors: &ArpResponses,
     eth_addr: EthernetAddress,
-    frame: &'a T,
+    frame: &[u8],
     caps: &DeviceCapabilities,
-) -> Result<
-    (
-        Option<(EthernetProtocol, EthernetAddress, Vec<u8>, usize)>,
-        (Ipv4Address, usize),
-    ),
-    smoltcp::Error,
-> {
+) -> Result<Option<(EthernetProtocol, EthernetAddress, Vec<u8>, usize)>, smoltcp::Error> {
     // Ignore any packets not directed to our hardware address or any of the multicast groups.
     let eth_frame = EthernetFrame::new_checked(frame)?;
 
@@ -109,8 +333,6 @@ fn process_eth<'a, T: AsRef<[u8]>>(
         return Err(smoltcp::Error::Dropped);
     }
 
-    let packet_len = eth_frame.payload().len() + EthernetFrame::<&T>::header_len();
-
     match eth_frame.ethertype() {
         EthernetProtocol::Ipv4 => {
             let ipv4_packet = Ipv4Packet::new_checked(eth_frame.payload())?;
@@ -128,17 +350,14 @@ fn process_eth<'a, T: AsRef<[u8]>>(
             };
 
             if let Some(dst) = dst_addr {
-                Ok((
-                    Some((
-                        EthernetProtocol::Ipv4,
-                        *dst,
-                        Vec::from(eth_frame.payload()),
-                        eth_frame.payload().len(),
-                    )),
-                    (ipv4_repr.src_addr, packet_len),
-                ))
+                Ok(Some((
+                    EthernetProtocol::Ipv4,
+                    *dst,
+                    Vec::from(eth_frame.payload()),
+                    eth_frame.payload().len(),
+                )))
             } else {
-                Ok((None, (ipv4_repr.src_addr, packet_len)))
+                Ok(None)
             }
         }
         EthernetProtocol::Arp => {
@@ -164,23 +383,25 @@ fn process_eth<'a, T: AsRef<[u8]>>(
                     };
                     let mut pack = ArpPacket::new_unchecked(vec![0; arp.buffer_len()]);
                     arp.emit(&mut pack);
-                    Ok((
-                        Some((
-                            EthernetProtocol::Arp,
-                            source_hardware_addr,
-                            pack.into_inner(),
-                            arp.buffer_len(),
-                        )),
-                        (source_protocol_addr, packet_len),
-                    ))
+                    Ok(Some((
+                        EthernetProtocol::Arp,
+                        source_hardware_addr,
+                        pack.into_inner(),
+                        arp.buffer_len(),
+                    )))
                 } else {
-                    Ok((None, (source_protocol_addr, packet_len)))
+                    Ok(None)
                 }
             } else {
                 Err(::smoltcp::Error::Unrecognized)
             }
         }
-        // Ignore non ipv4 traffic
+        // IPv6 is only counted, never answered or forwarded
+        EthernetProtocol::Ipv6 => {
+            Ipv6Packet::new_checked(eth_frame.payload())?;
+            Ok(None)
+        }
+        // Ignore all other traffic
         _ => Err(::smoltcp::Error::Unrecognized),
     }
 }
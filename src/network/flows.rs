@@ -0,0 +1,229 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::*;
+use stm32f7_discovery::system_clock;
+
+use super::ipv6;
+
+/// Maximum number of flows kept, the least recently seen flow is evicted beyond that
+pub const MAX_FLOWS: usize = 64;
+/// Flows without packets for this long are removed
+const IDLE_TIMEOUT: Duration = Duration { millis: 60_000 };
+/// Number of flows shown in the top talkers view
+const TOP_TALKERS: usize = 20;
+
+/// Identifies a unidirectional flow, ports are 0 for protocols without ports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FlowKey {
+    pub protocol: IpProtocol,
+    pub src_addr: IpAddress,
+    pub src_port: u16,
+    pub dst_addr: IpAddress,
+    pub dst_port: u16,
+}
+
+impl FlowKey {
+    /// Extract the 5-tuple of an untagged frame carrying an IPv4 or IPv6 packet
+    pub fn parse(frame: &[u8]) -> Option<FlowKey> {
+        let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+        let (protocol, src_addr, dst_addr, payload) = match eth_frame.ethertype() {
+            EthernetProtocol::Ipv4 => {
+                let packet = Ipv4Packet::new_checked(eth_frame.payload()).ok()?;
+                (
+                    packet.protocol(),
+                    IpAddress::Ipv4(packet.src_addr()),
+                    IpAddress::Ipv4(packet.dst_addr()),
+                    Some(packet.payload()),
+                )
+            }
+            EthernetProtocol::Ipv6 => {
+                let packet = Ipv6Packet::new_checked(eth_frame.payload()).ok()?;
+                let upper = ipv6::upper_layer(packet.next_header(), packet.payload()).ok()?;
+                (
+                    upper.protocol,
+                    IpAddress::Ipv6(packet.src_addr()),
+                    IpAddress::Ipv6(packet.dst_addr()),
+                    upper.payload,
+                )
+            }
+            _ => return None,
+        };
+        let (src_port, dst_port) = match (protocol, payload) {
+            (IpProtocol::Tcp, Some(payload)) => {
+                let tcp_packet = TcpPacket::new_checked(payload).ok()?;
+                (tcp_packet.src_port(), tcp_packet.dst_port())
+            }
+            (IpProtocol::Udp, Some(payload)) => {
+                let udp_packet = UdpPacket::new_checked(payload).ok()?;
+                (udp_packet.src_port(), udp_packet.dst_port())
+            }
+            _ => (0, 0),
+        };
+        Some(FlowKey {
+            protocol,
+            src_addr,
+            src_port,
+            dst_addr,
+            dst_port,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Flow {
+    pub packets: usize,
+    pub bytes: usize,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+    /// Second the bytes of current_bytes were seen in
+    current_sec: i64,
+    current_bytes: usize,
+    /// Bytes of the second before current_sec
+    previous_bytes: usize,
+}
+
+impl Flow {
+    fn new(timestamp: Instant) -> Self {
+        Flow {
+            packets: 0,
+            bytes: 0,
+            first_seen: timestamp,
+            last_seen: timestamp,
+            current_sec: timestamp.secs(),
+            current_bytes: 0,
+            previous_bytes: 0,
+        }
+    }
+
+    fn add(&mut self, bytes: usize, timestamp: Instant) {
+        let sec = timestamp.secs();
+        if sec != self.current_sec {
+            self.previous_bytes = if sec == self.current_sec + 1 {
+                self.current_bytes
+            } else {
+                0
+            };
+            self.current_sec = sec;
+            self.current_bytes = 0;
+        }
+        self.packets += 1;
+        self.bytes += bytes;
+        self.current_bytes += bytes;
+        self.last_seen = timestamp;
+    }
+
+    /// Bytes per second during the last complete second before now
+    pub fn rate(&self, now: Instant) -> usize {
+        let sec = now.secs();
+        if sec == self.current_sec {
+            self.previous_bytes
+        } else if sec == self.current_sec + 1 {
+            self.current_bytes
+        } else {
+            0
+        }
+    }
+}
+
+/// All active flows, bounded to MAX_FLOWS entries
+#[derive(Debug, Default)]
+pub struct FlowTable {
+    flows: BTreeMap<FlowKey, Flow>,
+}
+
+impl FlowTable {
+    /// Count a packet of len bytes for the flow key
+    pub fn record(&mut self, key: FlowKey, len: usize, timestamp: Instant) {
+        if !self.flows.contains_key(&key) && self.flows.len() >= MAX_FLOWS {
+            self.expire(timestamp);
+            if self.flows.len() >= MAX_FLOWS {
+                let oldest = self
+                    .flows
+                    .iter()
+                    .min_by_key(|(_, flow)| flow.last_seen)
+                    .map(|(key, _)| *key);
+                if let Some(oldest) = oldest {
+                    self.flows.remove(&oldest);
+                }
+            }
+        }
+        self.flows
+            .entry(key)
+            .or_insert_with(|| Flow::new(timestamp))
+            .add(len, timestamp);
+    }
+
+    /// Remove all flows idle for longer than IDLE_TIMEOUT
+    pub fn expire(&mut self, now: Instant) {
+        let idle: Vec<FlowKey> = self
+            .flows
+            .iter()
+            .filter(|(_, flow)| now - flow.last_seen > IDLE_TIMEOUT)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in idle {
+            self.flows.remove(&key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.flows.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Return up to n flows sorted by their current throughput, then by their total bytes
+    pub fn top_talkers(&self, now: Instant, n: usize) -> Vec<(&FlowKey, &Flow)> {
+        let mut flows: Vec<(&FlowKey, &Flow)> = self.flows.iter().collect();
+        flows.sort_by(|a, b| (b.1.rate(now), b.1.bytes).cmp(&(a.1.rate(now), a.1.bytes)));
+        flows.truncate(n);
+        flows
+    }
+}
+
+/// Format an address and port, IPv6 addresses are put in brackets to set the port apart
+fn endpoint(addr: IpAddress, port: u16) -> String {
+    match addr {
+        IpAddress::Ipv6(x) => format!("[{}]:{}", x, port),
+        x => format!("{}:{}", x, port),
+    }
+}
+
+impl super::StringableVec for FlowTable {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        let now = Instant::from_millis(system_clock::ms() as i64);
+        ret.push(format!("Top talkers of {} flows:", self.len()));
+        for (key, flow) in self.top_talkers(now, TOP_TALKERS) {
+            if key.src_port == 0 && key.dst_port == 0 {
+                ret.push(format!(
+                    "{} {} > {}",
+                    key.protocol, key.src_addr, key.dst_addr
+                ));
+            } else {
+                ret.push(format!(
+                    "{} {} > {}",
+                    key.protocol,
+                    endpoint(key.src_addr, key.src_port),
+                    endpoint(key.dst_addr, key.dst_port)
+                ));
+            }
+            ret.push(format!(
+                "    {} bytes / second, {} packets, {} bytes, {}s",
+                flow.rate(now),
+                flow.packets,
+                flow.bytes,
+                (flow.last_seen - flow.first_seen).secs()
+            ));
+        }
+        ret
+    }
+}
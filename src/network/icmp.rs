@@ -2,27 +2,22 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use byteorder::{ByteOrder, NetworkEndian};
 use smoltcp::iface::EthernetInterface;
-use smoltcp::phy::{Device, DeviceCapabilities};
+use smoltcp::phy::{Device, DeviceCapabilities, RxToken};
 use smoltcp::socket::*;
 use smoltcp::time::{Duration, Instant};
-use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr, IpAddress, Ipv4Address};
+use smoltcp::wire::*;
 use stm32f7_discovery::{ethernet::MTU, random, system_clock};
 
 use super::arp::ArpResponses;
+use super::eth::{dispatch_ethernet, NetDevice};
 
-#[derive(Debug)]
-pub struct IcmpResponse(pub Ipv4Address, pub Duration);
-pub type IcmpResponses = Vec<IcmpResponse>;
+pub use netscan::icmp::*;
 
-impl super::StringableVec for IcmpResponses {
-    fn to_string_vec(&self) -> Vec<String> {
-        let mut ret: Vec<String> = Vec::new();
-        for i in self.iter() {
-            ret.push(format!("{} ({})", i.0, i.1));
-        }
-        ret
-    }
-}
+/// Hops probed before giving up on reaching the destination
+pub const MAX_HOPS: u8 = 16;
+/// Time to wait for the answer to a single probe
+const HOP_TIMEOUT: Duration = Duration { millis: 500 };
+const PROBE_PAYLOAD_LEN: usize = 32;
 
 /// Iterate over all addresses in addrs and send probe them via ICMP
 pub fn scan_v4<'b, 'c, 'e, DeviceT>(
@@ -136,6 +131,86 @@ where
     }
 }
 
+/// Trace the route to dst with echo requests of increasing TTL sent to next_hop
+/// The trace ends when dst answers, reports it unreachable or after MAX_HOPS
+pub fn traceroute(
+    iface: &mut NetDevice,
+    eth_addr: EthernetAddress,
+    src: Ipv4Address,
+    next_hop: EthernetAddress,
+    dst: Ipv4Address,
+    rng: &mut random::Rng,
+) -> Result<Trace, String> {
+    let ident = rng.poll_and_get().expect("RNG Failed") as u16;
+    let payload = [0xffu8; PROBE_PAYLOAD_LEN];
+    let checksum = capabilities().checksum;
+    let mut trace = Trace::new();
+
+    for ttl in 1..=MAX_HOPS {
+        let seq_no = u16::from(ttl);
+        let icmp_repr = Icmpv4Repr::EchoRequest {
+            ident,
+            seq_no,
+            data: &payload,
+        };
+        let ip_repr = Ipv4Repr {
+            src_addr: src,
+            dst_addr: dst,
+            protocol: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: ttl,
+        };
+
+        let tx_token = match iface.transmit() {
+            Some(x) => x,
+            None => return Err(String::from("No tx descriptor available")),
+        };
+        let sent_at = Instant::from_millis(system_clock::ms() as i64);
+        dispatch_ethernet(
+            eth_addr,
+            tx_token,
+            sent_at,
+            ip_repr.buffer_len() + icmp_repr.buffer_len(),
+            |mut frame| {
+                frame.set_dst_addr(next_hop);
+                frame.set_ethertype(EthernetProtocol::Ipv4);
+
+                let mut packet = Ipv4Packet::new_unchecked(frame.payload_mut());
+                ip_repr.emit(&mut packet, &checksum);
+                let mut icmp_packet = Icmpv4Packet::new_unchecked(packet.payload_mut());
+                icmp_repr.emit(&mut icmp_packet, &checksum);
+            },
+        )
+        .map_err(|x| format!("{}", x))?;
+
+        let mut reply = None;
+        loop {
+            let timestamp = Instant::from_millis(system_clock::ms() as i64);
+            if timestamp - sent_at > HOP_TIMEOUT {
+                break;
+            }
+            let (rx_token, _) = match iface.receive() {
+                Some(tokens) => tokens,
+                None => continue,
+            };
+            let answer = rx_token.consume(timestamp, |frame| {
+                Ok(parse_hop_reply(frame, dst, ident, seq_no))
+            });
+            if let Ok(Some((addr, kind))) = answer {
+                reply = Some((addr, kind, timestamp - sent_at));
+                break;
+            }
+        }
+
+        trace.push(Hop { ttl, reply });
+        match reply {
+            Some((_, HopReply::TimeExceeded, _)) | None => {}
+            Some(_) => break,
+        }
+    }
+    Ok(trace)
+}
+
 fn capabilities() -> DeviceCapabilities {
     let mut capabilities = DeviceCapabilities::default();
     capabilities.max_transmission_unit = MTU;
@@ -0,0 +1,92 @@
+use alloc::string::String;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, Ipv4Address};
+use stm32f7_discovery::{random, system_clock};
+
+use super::acd;
+use super::eth::NetDevice;
+
+pub const LINK_LOCAL_PREFIX_LEN: u8 = 16;
+/// Addresses are chosen from 169.254.1.0 to 169.254.254.255, the first and last 256 are reserved
+const FIRST_HOST: u32 = 0x0100;
+const HOST_COUNT: u32 = 0xfe00;
+/// Minimum time between attempts once MAX_CONFLICTS was reached
+pub const RATE_LIMIT_INTERVAL: Duration = Duration { millis: 60_000 };
+
+/// Return the n-th candidate address derived from seed
+/// The first candidate only depends on the hardware address, so a host tends to get the same
+/// address after a restart
+pub fn candidate(eth_addr: EthernetAddress, seed: u32, n: u32) -> Ipv4Address {
+    let mut state = eth_addr.as_bytes().iter().fold(0x811c_9dc5u32, |hash, x| {
+        (hash ^ u32::from(*x)).wrapping_mul(0x0100_0193)
+    });
+    if n > 0 {
+        state ^= seed.wrapping_add(n);
+        // xorshift to spread the seed over all bits
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+    }
+    let host = FIRST_HOST + state % HOST_COUNT;
+    Ipv4Address::new(169, 254, (host >> 8) as u8, host as u8)
+}
+
+/// Claim of a link-local address as described in RFC 3927
+/// After MAX_CONFLICTS conflicts only one address is probed per RATE_LIMIT_INTERVAL, the claim
+/// is continued by calling poll until it succeeds
+pub struct Claim {
+    seed: u32,
+    /// Number of candidates chosen so far
+    attempts: u32,
+    conflicts: usize,
+    /// Address that was just given up because of a conflict, it is not chosen again
+    pub lost: Option<Ipv4Address>,
+    /// Earliest time of the next probe once rate limited
+    pub next_attempt: Option<Instant>,
+}
+
+impl Claim {
+    pub fn new(rng: &mut random::Rng, lost: Option<Ipv4Address>) -> Self {
+        Claim {
+            seed: rng.poll_and_get().expect("RNG Failed"),
+            attempts: 0,
+            conflicts: 0,
+            lost,
+            next_attempt: None,
+        }
+    }
+
+    /// Whether poll would probe an address at timestamp
+    pub fn is_due(&self, timestamp: Instant) -> bool {
+        self.next_attempt.map_or(true, |x| timestamp >= x)
+    }
+
+    /// Probe candidates until one is free or the probes are rate limited
+    /// Returns the claimed address, or None if the caller has to wait for next_attempt
+    pub fn poll(
+        &mut self,
+        iface: &mut NetDevice,
+        eth_addr: EthernetAddress,
+        rng: &mut random::Rng,
+    ) -> Result<Option<Ipv4Address>, String> {
+        loop {
+            if !self.is_due(Instant::from_millis(system_clock::ms() as i64)) {
+                return Ok(None);
+            }
+            let addr = candidate(eth_addr, self.seed, self.attempts);
+            self.attempts += 1;
+            if Some(addr) == self.lost {
+                continue;
+            }
+            if acd::probe(iface, eth_addr, rng, addr)? {
+                acd::announce(iface, eth_addr, addr)?;
+                return Ok(Some(addr));
+            }
+            self.conflicts += 1;
+            if self.conflicts >= acd::MAX_CONFLICTS {
+                let timestamp = Instant::from_millis(system_clock::ms() as i64);
+                self.next_attempt = Some(timestamp + RATE_LIMIT_INTERVAL);
+            }
+        }
+    }
+}
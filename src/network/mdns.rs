@@ -0,0 +1,202 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::socket::*;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use stm32f7_discovery::system_clock;
+
+use super::dns::{self, Record, RecordData, CLASS_IN, TYPE_PTR, TYPE_SRV, TYPE_TXT};
+
+pub use netscan::mdns::*;
+
+pub const MDNS_ADDR: Ipv4Address = Ipv4Address([224, 0, 0, 251]);
+pub const MDNS_PORT: u16 = 5353;
+const MDNS_LOCAL_PORT: u16 = 49154;
+const SERVICES_NAME: &str = "_services._dns-sd._udp.local";
+/// Ask responders to answer via unicast, responders do this for queries from a port other
+/// than 5353 as well
+const CLASS_UNICAST_RESPONSE: u16 = 0x8000;
+/// Time to collect responses to a query
+const QUERY_WINDOW: Duration = Duration { millis: 1000 };
+
+/// Build a multicast DNS query containing all questions
+pub fn emit_query(questions: &[(String, u16)]) -> Result<Vec<u8>, &'static str> {
+    let mut buffer = vec![0; 12];
+    NetworkEndian::write_u16(&mut buffer[4..6], questions.len() as u16);
+    for (name, qtype) in questions.iter() {
+        dns::emit_question(&mut buffer, name, *qtype, CLASS_IN | CLASS_UNICAST_RESPONSE)?;
+    }
+    Ok(buffer)
+}
+
+/// Browse all service types announced via DNS-SD and resolve their instances
+pub fn discover<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+) -> Result<MdnsServices, String>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut sockets = SocketSet::new(Vec::new());
+    let udp_rx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 8], vec![0; 4096]);
+    let udp_tx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 2], vec![0; 1500]);
+    let udp_handle = sockets.add(UdpSocket::new(udp_rx_buffer, udp_tx_buffer));
+    sockets
+        .get::<UdpSocket>(udp_handle)
+        .bind(MDNS_LOCAL_PORT)
+        .unwrap();
+
+    let mut records = Vec::<(Ipv4Address, Record)>::new();
+
+    exchange(
+        iface,
+        &mut sockets,
+        udp_handle,
+        &[(String::from(SERVICES_NAME), TYPE_PTR)],
+        &mut records,
+    )?;
+    let service_types = ptr_targets(&records, SERVICES_NAME);
+    if service_types.is_empty() {
+        return Ok(MdnsServices::new());
+    }
+
+    let questions: Vec<(String, u16)> = service_types
+        .iter()
+        .map(|x| (x.clone(), TYPE_PTR))
+        .collect();
+    exchange(iface, &mut sockets, udp_handle, &questions, &mut records)?;
+
+    // Ask for the records responders did not include as additional records
+    let mut questions = Vec::new();
+    for service_type in service_types.iter() {
+        for instance in ptr_targets(&records, service_type) {
+            if find_srv(&records, &instance).is_none() {
+                questions.push((instance.clone(), TYPE_SRV));
+                questions.push((instance, TYPE_TXT));
+            }
+        }
+    }
+    if !questions.is_empty() {
+        exchange(iface, &mut sockets, udp_handle, &questions, &mut records)?;
+    }
+
+    let mut services = MdnsServices::new();
+    for service_type in service_types.iter() {
+        for instance in ptr_targets(&records, service_type) {
+            let (src, port, target) = match find_srv(&records, &instance) {
+                Some(x) => x,
+                None => continue,
+            };
+            let addr = records
+                .iter()
+                .find_map(|(_, record)| match record.data {
+                    RecordData::A(addr) if record.name.eq_ignore_ascii_case(&target) => Some(addr),
+                    _ => None,
+                })
+                .unwrap_or(src);
+            let txt = records
+                .iter()
+                .find_map(|(_, record)| match record.data {
+                    RecordData::Txt(ref txt) if record.name.eq_ignore_ascii_case(&instance) => {
+                        Some(txt.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            let host_services = services.entry(addr).or_insert_with(Vec::new);
+            if !host_services.iter().any(|x| x.instance == instance) {
+                host_services.push(MdnsService {
+                    instance,
+                    service_type: service_type.clone(),
+                    target,
+                    port,
+                    txt,
+                });
+            }
+        }
+    }
+    Ok(services)
+}
+
+/// Send a query to the mDNS group and collect the records of all responses
+fn exchange<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    sockets: &mut SocketSet,
+    udp_handle: SocketHandle,
+    questions: &[(String, u16)],
+    records: &mut Vec<(Ipv4Address, Record)>,
+) -> Result<(), String>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let packet = emit_query(questions).map_err(String::from)?;
+    let start = Instant::from_millis(system_clock::ms() as i64);
+    let mut sent = false;
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        match iface.poll(sockets, timestamp) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+
+        {
+            let mut socket = sockets.get::<UdpSocket>(udp_handle);
+            if !sent && socket.can_send() {
+                socket
+                    .send_slice(&packet, IpEndpoint::new(MDNS_ADDR.into(), MDNS_PORT))
+                    .map_err(|x| x.to_string())?;
+                sent = true;
+            }
+            while socket.can_recv() {
+                let (payload, endpoint) = socket.recv().unwrap();
+                if let (Ok(msg), IpAddress::Ipv4(src)) =
+                    (dns::parse_message(payload), endpoint.addr)
+                {
+                    if msg.is_response() {
+                        for record in msg.answers.into_iter().chain(msg.additionals.into_iter()) {
+                            records.push((src, record));
+                        }
+                    }
+                }
+            }
+        }
+
+        if timestamp - start > QUERY_WINDOW {
+            break;
+        }
+        system_clock::wait_ms(10);
+    }
+    Ok(())
+}
+
+/// Return the distinct targets of all PTR records for name
+fn ptr_targets(records: &[(Ipv4Address, Record)], name: &str) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    for (_, record) in records.iter() {
+        if let RecordData::Name(ref target) = record.data {
+            if record.rtype == TYPE_PTR
+                && record.name.eq_ignore_ascii_case(name)
+                && !ret.contains(target)
+            {
+                ret.push(target.clone());
+            }
+        }
+    }
+    ret
+}
+
+/// Return the responder, port and target host of the SRV record of instance
+fn find_srv(
+    records: &[(Ipv4Address, Record)],
+    instance: &str,
+) -> Option<(Ipv4Address, u16, String)> {
+    records.iter().find_map(|(src, record)| match record.data {
+        RecordData::Srv {
+            port, ref target, ..
+        } if record.name.eq_ignore_ascii_case(instance) => Some((*src, port, target.clone())),
+        _ => None,
+    })
+}
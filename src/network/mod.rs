@@ -1,67 +1,25 @@
-use alloc::string::String;
-use alloc::vec::Vec;
-use managed::ManagedSlice;
-use smoltcp::iface::EthernetInterface;
-use smoltcp::phy::Device;
-use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address};
-
-use services::Service;
+pub use netscan::{
+    baseline, cidr, clear_ip4_address, config, dissect, export, filter, host_label, inventory,
+    ipv6, line_address, lldp, oui, pcap, pcapfile, printable, printable_lines, services,
+    set_ip4_address, HostNames, NamedStringableVec, PortScan, PortScans, Stringable, StringableVec,
+};
 
+pub mod acd;
 pub mod arp;
-pub mod cidr;
+pub mod arpwatch;
+pub mod autoconf;
+pub mod capture;
+pub mod dhcp;
+pub mod dns;
+pub mod dnslog;
 pub mod eth;
+pub mod flows;
 pub mod icmp;
-pub mod services;
+pub mod ipv4ll;
+pub mod mdns;
+pub mod netbios;
+pub mod snmp;
+pub mod ssdp;
+pub mod stats;
 pub mod tcp;
 pub mod udp;
-
-pub trait StringableVec {
-    fn to_string_vec(&self) -> Vec<String>;
-}
-
-pub trait Stringable {
-    fn to_string(&self) -> String;
-}
-
-#[derive(Debug)]
-pub struct PortScan(pub Ipv4Address, pub Vec<&'static Service>);
-pub type PortScans = Vec<PortScan>;
-
-impl super::StringableVec for Vec<&Service> {
-    fn to_string_vec(&self) -> Vec<String> {
-        let mut ret: Vec<String> = Vec::new();
-        for i in self.iter() {
-            ret.push(format!("    {} ({})", i.0, i.1));
-        }
-        ret
-    }
-}
-
-impl super::StringableVec for PortScans {
-    fn to_string_vec(&self) -> Vec<String> {
-        let mut ret: Vec<String> = Vec::new();
-        for i in self.iter() {
-            ret.push(format!("{}:", i.0));
-            if i.1.is_empty() {
-                ret.push(String::from("    No open ports found"));
-            } else {
-                ret.extend(i.1.to_string_vec());
-            }
-        }
-        ret
-    }
-}
-
-/// Set device IPv4 Address
-pub fn set_ip4_address<'b, 'c, 'e, DeviceT>(
-    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
-    addr: Ipv4Address,
-    netmask: u8,
-) where
-    DeviceT: for<'d> Device<'d>,
-{
-    iface.update_ip_addrs(|addrs| {
-        let addr = IpAddress::from(addr);
-        *addrs = ManagedSlice::from(vec![IpCidr::new(addr, netmask); 1]);
-    });
-}
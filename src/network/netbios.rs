@@ -0,0 +1,84 @@
+use alloc::vec::Vec;
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::socket::*;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use stm32f7_discovery::system_clock;
+
+use super::arp::ArpResponses;
+
+pub use netscan::netbios::*;
+
+const NETBIOS_LOCAL_PORT: u16 = 49155;
+/// Time to wait for the node status responses
+const QUERY_WINDOW: Duration = Duration { millis: 1500 };
+/// Number of queries that are sent at once
+const QUERY_BATCH: usize = 4;
+
+/// Send a node status request to every address in addrs and collect the responses
+pub fn query_nodes<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    addrs: &ArpResponses,
+) -> NetbiosInfos
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut infos = NetbiosInfos::new();
+    let targets: Vec<Ipv4Address> = addrs.keys().cloned().collect();
+    let id_base = system_clock::ms() as u16;
+
+    let mut sockets = SocketSet::new(Vec::new());
+    let udp_rx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 4], vec![0; 2048]);
+    let udp_tx_buffer = UdpSocketBuffer::new(
+        vec![UdpPacketMetadata::EMPTY; QUERY_BATCH],
+        vec![0; 64 * QUERY_BATCH],
+    );
+    let udp_handle = sockets.add(UdpSocket::new(udp_rx_buffer, udp_tx_buffer));
+    sockets
+        .get::<UdpSocket>(udp_handle)
+        .bind(NETBIOS_LOCAL_PORT)
+        .unwrap();
+
+    let mut next = 0;
+    let mut last_sent = Instant::from_millis(system_clock::ms() as i64);
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        match iface.poll(&mut sockets, timestamp) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+
+        {
+            let mut socket = sockets.get::<UdpSocket>(udp_handle);
+            while next < targets.len() && socket.can_send() {
+                let packet = emit_status_request(id_base.wrapping_add(next as u16));
+                socket
+                    .send_slice(
+                        &packet,
+                        IpEndpoint::new(targets[next].into(), NETBIOS_NS_PORT),
+                    )
+                    .unwrap_or(());
+                next += 1;
+                last_sent = timestamp;
+            }
+            while socket.can_recv() {
+                let (payload, endpoint) = socket.recv().unwrap();
+                if let (Ok((id, info)), IpAddress::Ipv4(src)) =
+                    (parse_status_response(payload), endpoint.addr)
+                {
+                    let index = id.wrapping_sub(id_base) as usize;
+                    if index < targets.len() && targets[index] == src {
+                        infos.insert(src, info);
+                    }
+                }
+            }
+        }
+
+        if next >= targets.len() && timestamp - last_sent > QUERY_WINDOW {
+            break;
+        }
+        system_clock::wait_ms(10);
+    }
+    infos
+}
@@ -0,0 +1,141 @@
+use alloc::vec::Vec;
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::socket::*;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use stm32f7_discovery::system_clock;
+
+use super::arp::ArpResponses;
+
+pub use netscan::snmp::*;
+
+const SNMP_LOCAL_PORT: u16 = 49157;
+/// Time to wait for the responses of a round of requests
+const QUERY_WINDOW: Duration = Duration { millis: 1500 };
+/// Number of requests that are sent at once
+const QUERY_BATCH: usize = 4;
+
+/// Send a request built by emit to every address in targets and collect the responses
+/// in the order of targets
+fn exchange<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    sockets: &mut SocketSet,
+    udp_handle: SocketHandle,
+    targets: &[Ipv4Address],
+    emit: impl Fn(i32) -> Vec<u8>,
+) -> Vec<Option<Pdu>>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut responses: Vec<Option<Pdu>> = targets.iter().map(|_| None).collect();
+    let id_base = (system_clock::ms() & 0x3fff_ffff) as i32;
+
+    let mut next = 0;
+    let mut last_sent = Instant::from_millis(system_clock::ms() as i64);
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        match iface.poll(sockets, timestamp) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+
+        {
+            let mut socket = sockets.get::<UdpSocket>(udp_handle);
+            while next < targets.len() && socket.can_send() {
+                let packet = emit(id_base + next as i32);
+                socket
+                    .send_slice(&packet, IpEndpoint::new(targets[next].into(), SNMP_PORT))
+                    .unwrap_or(());
+                next += 1;
+                last_sent = timestamp;
+            }
+            while socket.can_recv() {
+                let (payload, endpoint) = socket.recv().unwrap();
+                if let (Ok(pdu), IpAddress::Ipv4(src)) = (parse_response(payload), endpoint.addr) {
+                    let index = pdu.request_id.wrapping_sub(id_base) as usize;
+                    if index < targets.len() && targets[index] == src {
+                        responses[index] = Some(pdu);
+                    }
+                }
+            }
+        }
+
+        if next >= targets.len() && timestamp - last_sent > QUERY_WINDOW {
+            break;
+        }
+        system_clock::wait_ms(10);
+    }
+    responses
+}
+
+/// Ask every address in addrs for its system description, name, uptime and contact
+/// Agents that reject the GetRequest, e.g. because an object has no instance, are asked
+/// again with a GetNextRequest for the objects without instance suffix
+pub fn query_system<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    addrs: &ArpResponses,
+    community: &str,
+    version: Version,
+) -> SnmpInfos
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut infos = SnmpInfos::new();
+    let targets: Vec<Ipv4Address> = addrs.keys().cloned().collect();
+
+    let mut sockets = SocketSet::new(Vec::new());
+    let udp_rx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 4], vec![0; 4096]);
+    let udp_tx_buffer = UdpSocketBuffer::new(
+        vec![UdpPacketMetadata::EMPTY; QUERY_BATCH],
+        vec![0; 128 * QUERY_BATCH],
+    );
+    let udp_handle = sockets.add(UdpSocket::new(udp_rx_buffer, udp_tx_buffer));
+    sockets
+        .get::<UdpSocket>(udp_handle)
+        .bind(SNMP_LOCAL_PORT)
+        .unwrap();
+
+    let instances: Vec<Vec<u32>> = SYSTEM_OBJECTS
+        .iter()
+        .map(|x| {
+            let mut oid = Vec::from(*x);
+            oid.push(0);
+            oid
+        })
+        .collect();
+    let instances: Vec<&[u32]> = instances.iter().map(|x| x.as_slice()).collect();
+    let responses = exchange(iface, &mut sockets, udp_handle, &targets, |id| {
+        emit_request(version, community, PDU_GET_REQUEST, id, &instances)
+    });
+
+    let mut retry = Vec::new();
+    for (addr, response) in targets.iter().zip(responses.into_iter()) {
+        if let Some(pdu) = response {
+            let info = infos.entry(*addr).or_insert_with(SystemInfo::default);
+            if pdu.error_status != 0 || !process_bindings(&pdu.bindings, info) {
+                retry.push(*addr);
+            }
+        }
+    }
+
+    if !retry.is_empty() {
+        let responses = exchange(iface, &mut sockets, udp_handle, &retry, |id| {
+            emit_request(
+                version,
+                community,
+                PDU_GET_NEXT_REQUEST,
+                id,
+                &SYSTEM_OBJECTS,
+            )
+        });
+        for (addr, response) in retry.iter().zip(responses.into_iter()) {
+            if let (Some(pdu), Some(info)) = (response, infos.get_mut(addr)) {
+                if pdu.error_status == 0 {
+                    process_bindings(&pdu.bindings, info);
+                }
+            }
+        }
+    }
+    infos
+}
@@ -0,0 +1,170 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use smoltcp::iface::EthernetInterface;
+use smoltcp::phy::Device;
+use smoltcp::socket::*;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use stm32f7_discovery::system_clock;
+
+pub use netscan::ssdp::*;
+
+pub const SSDP_ADDR: Ipv4Address = Ipv4Address([239, 255, 255, 250]);
+pub const SSDP_PORT: u16 = 1900;
+const SSDP_LOCAL_PORT: u16 = 49156;
+const HTTP_LOCAL_PORT: u16 = 49200;
+const M_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+                        HOST: 239.255.255.250:1900\r\n\
+                        MAN: \"ssdp:discover\"\r\n\
+                        MX: 1\r\n\
+                        ST: ssdp:all\r\n\r\n";
+/// Time to collect responses, devices delay their response by up to MX seconds
+const SEARCH_WINDOW: Duration = Duration { millis: 2000 };
+const FETCH_TIMEOUT: Duration = Duration { millis: 2000 };
+/// Only the beginning of a device description is read, it contains the root device
+const MAX_DESCRIPTION_LEN: usize = 4096;
+
+/// Send a M-SEARCH request for all devices and collect the responses per device
+/// If fetch_descriptions is set, the device description behind the first location of every
+/// device is fetched as well
+pub fn search<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    fetch_descriptions: bool,
+) -> Result<SsdpDevices, String>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut devices = SsdpDevices::new();
+    {
+        let mut sockets = SocketSet::new(Vec::new());
+        let udp_rx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 8], vec![0; 4096]);
+        let udp_tx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 1], vec![0; 256]);
+        let udp_handle = sockets.add(UdpSocket::new(udp_rx_buffer, udp_tx_buffer));
+        sockets
+            .get::<UdpSocket>(udp_handle)
+            .bind(SSDP_LOCAL_PORT)
+            .unwrap();
+
+        let start = Instant::from_millis(system_clock::ms() as i64);
+        let mut sent = false;
+        loop {
+            let timestamp = Instant::from_millis(system_clock::ms() as i64);
+            match iface.poll(&mut sockets, timestamp) {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+
+            {
+                let mut socket = sockets.get::<UdpSocket>(udp_handle);
+                if !sent && socket.can_send() {
+                    socket
+                        .send_slice(
+                            M_SEARCH.as_bytes(),
+                            IpEndpoint::new(SSDP_ADDR.into(), SSDP_PORT),
+                        )
+                        .map_err(|x| x.to_string())?;
+                    sent = true;
+                }
+                while socket.can_recv() {
+                    let (payload, endpoint) = socket.recv().unwrap();
+                    if let (Some(response), IpAddress::Ipv4(src)) =
+                        (parse_response(payload), endpoint.addr)
+                    {
+                        let device = devices.entry(src).or_insert_with(SsdpDevice::default);
+                        // Devices repeat their responses, keep one per unique service name
+                        if response.usn.is_none()
+                            || !device.responses.iter().any(|x| x.usn == response.usn)
+                        {
+                            device.responses.push(response);
+                        }
+                    }
+                }
+            }
+
+            if timestamp - start > SEARCH_WINDOW {
+                break;
+            }
+            system_clock::wait_ms(10);
+        }
+    }
+
+    if fetch_descriptions {
+        for (index, device) in devices.values_mut().enumerate() {
+            let url = match device.responses.iter().find_map(|x| x.location.as_ref()) {
+                Some(x) => x.clone(),
+                None => continue,
+            };
+            if let Some((addr, port, path)) = parse_url(&url) {
+                let local_port = HTTP_LOCAL_PORT + index as u16;
+                device.description = fetch_description(iface, addr, port, path, local_port).ok();
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// Fetch a device description via HTTP and parse it
+fn fetch_description<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    addr: Ipv4Address,
+    port: u16,
+    path: &str,
+    local_port: u16,
+) -> Result<DeviceDescription, String>
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut sockets = SocketSet::new(Vec::new());
+    let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; 1024]);
+    let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; 512]);
+    let tcp_handle = sockets.add(TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer));
+    sockets
+        .get::<TcpSocket>(tcp_handle)
+        .connect((addr, port), local_port)
+        .map_err(|x| x.to_string())?;
+
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}:{}\r\nConnection: close\r\n\r\n",
+        path, addr, port
+    );
+    let mut document = Vec::new();
+    let mut sent = false;
+    let start = Instant::from_millis(system_clock::ms() as i64);
+    loop {
+        let timestamp = Instant::from_millis(system_clock::ms() as i64);
+        match iface.poll(&mut sockets, timestamp) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+
+        let mut socket = sockets.get::<TcpSocket>(tcp_handle);
+        if !sent && socket.can_send() {
+            socket
+                .send_slice(request.as_bytes())
+                .map_err(|x| x.to_string())?;
+            sent = true;
+        }
+        if socket.can_recv() {
+            socket
+                .recv(|data| {
+                    let len = data.len().min(MAX_DESCRIPTION_LEN - document.len());
+                    document.extend_from_slice(&data[..len]);
+                    (data.len(), ())
+                })
+                .map_err(|x| x.to_string())?;
+        }
+
+        if document.len() >= MAX_DESCRIPTION_LEN || (sent && !socket.may_recv()) {
+            socket.abort();
+            break;
+        }
+        if timestamp - start > FETCH_TIMEOUT {
+            socket.abort();
+            if document.is_empty() {
+                return Err(String::from("Device description request timed out"));
+            }
+            break;
+        }
+    }
+    Ok(parse_description(&super::printable_lines(&document)))
+}
@@ -0,0 +1,463 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use smoltcp::time::Instant;
+use smoltcp::wire::*;
+use stm32f7_discovery::system_clock;
+
+use super::arp::ArpResponses;
+use super::ipv6;
+
+/// Protocol classes the traffic is counted in, TCP and UDP cover both IP versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    Arp,
+    Icmp,
+    /// ICMPv6 except neighbor discovery
+    Icmpv6,
+    Ndp,
+    Tcp,
+    Udp,
+    /// IPv4 packets of any other IP protocol
+    Ipv4,
+    /// IPv6 packets of any other next header
+    Ipv6,
+    /// All other ethertypes
+    Other,
+}
+
+const PROTOCOL_COUNT: usize = 9;
+pub const PROTOCOLS: [Protocol; PROTOCOL_COUNT] = [
+    Protocol::Arp,
+    Protocol::Icmp,
+    Protocol::Icmpv6,
+    Protocol::Ndp,
+    Protocol::Tcp,
+    Protocol::Udp,
+    Protocol::Ipv4,
+    Protocol::Ipv6,
+    Protocol::Other,
+];
+
+impl Protocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            Protocol::Arp => "ARP",
+            Protocol::Icmp => "ICMP",
+            Protocol::Icmpv6 => "ICMPv6",
+            Protocol::Ndp => "NDP",
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+            Protocol::Ipv4 => "IPv4 other",
+            Protocol::Ipv6 => "IPv6 other",
+            Protocol::Other => "Other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Counter {
+    pub packets: usize,
+    pub bytes: usize,
+}
+
+impl Counter {
+    pub fn add(&mut self, bytes: usize) {
+        self.packets += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Sum up a set of counters
+fn sum<'a, I: Iterator<Item = &'a Counter>>(counters: I) -> Counter {
+    counters.fold(Counter::default(), |acc, x| Counter {
+        packets: acc.packets + x.packets,
+        bytes: acc.bytes + x.bytes,
+    })
+}
+
+const SECOND_BUCKETS: usize = 10;
+const TEN_SECOND_BUCKETS: usize = 6;
+
+/// Bytes per second over sliding windows of the recent past
+/// Single seconds are kept for the last 10 s and sums of 10 s for the last minute, which keeps
+/// the meter small enough for every host
+#[derive(Debug, Default, Clone)]
+pub struct RateMeter {
+    seconds: [u32; SECOND_BUCKETS],
+    ten_seconds: [u32; TEN_SECOND_BUCKETS],
+    /// Second of the most recent bytes
+    current_sec: i64,
+    /// Highest number of bytes seen in a complete second
+    peak: u32,
+}
+
+impl RateMeter {
+    /// Move the ring buffers forward to sec and clear the buckets of the skipped seconds
+    fn advance(&mut self, sec: i64) {
+        if sec <= self.current_sec {
+            return;
+        }
+        self.peak = self
+            .peak
+            .max(self.seconds[self.current_sec as usize % SECOND_BUCKETS]);
+        for s in (self.current_sec + 1).max(sec - SECOND_BUCKETS as i64 + 1)..=sec {
+            self.seconds[s as usize % SECOND_BUCKETS] = 0;
+        }
+        let (current_ten, ten) = (self.current_sec / 10, sec / 10);
+        for t in (current_ten + 1).max(ten - TEN_SECOND_BUCKETS as i64 + 1)..=ten {
+            self.ten_seconds[t as usize % TEN_SECOND_BUCKETS] = 0;
+        }
+        self.current_sec = sec;
+    }
+
+    pub fn add(&mut self, bytes: usize, timestamp: Instant) {
+        let sec = timestamp.secs();
+        self.advance(sec);
+        let bytes = bytes as u32;
+        let second = &mut self.seconds[sec as usize % SECOND_BUCKETS];
+        *second = second.saturating_add(bytes);
+        let ten_seconds = &mut self.ten_seconds[(sec / 10) as usize % TEN_SECOND_BUCKETS];
+        *ten_seconds = ten_seconds.saturating_add(bytes);
+    }
+
+    /// Bytes of second sec, if it is still kept
+    fn second(&self, sec: i64) -> u32 {
+        if sec < 0 || sec > self.current_sec || sec <= self.current_sec - SECOND_BUCKETS as i64 {
+            0
+        } else {
+            self.seconds[sec as usize % SECOND_BUCKETS]
+        }
+    }
+
+    /// Bytes per second during the last complete second before now
+    pub fn current(&self, now: Instant) -> usize {
+        self.second(now.secs() - 1) as usize
+    }
+
+    /// Average bytes per second during the last 10 complete seconds
+    pub fn average_10s(&self, now: Instant) -> usize {
+        let now_s = now.secs();
+        let total: u64 = (now_s - SECOND_BUCKETS as i64..now_s)
+            .map(|s| u64::from(self.second(s)))
+            .sum();
+        (total / SECOND_BUCKETS as u64) as usize
+    }
+
+    /// Average bytes per second during the last 6 complete 10 s intervals
+    pub fn average_60s(&self, now: Instant) -> usize {
+        let now_ten = now.secs() / 10;
+        let current_ten = self.current_sec / 10;
+        let total: u64 = (now_ten - TEN_SECOND_BUCKETS as i64..now_ten)
+            .filter(|t| {
+                *t >= 0 && *t <= current_ten && *t > current_ten - TEN_SECOND_BUCKETS as i64
+            })
+            .map(|t| u64::from(self.ten_seconds[t as usize % TEN_SECOND_BUCKETS]))
+            .sum();
+        (total / (SECOND_BUCKETS * TEN_SECOND_BUCKETS) as u64) as usize
+    }
+
+    /// Highest number of bytes seen in a complete second
+    pub fn peak(&self, now: Instant) -> usize {
+        if now.secs() > self.current_sec {
+            self.peak.max(self.second(self.current_sec)) as usize
+        } else {
+            self.peak as usize
+        }
+    }
+
+    /// Format the rates for the scroll views
+    pub fn format_rates(&self, now: Instant) -> Vec<String> {
+        vec![
+            format!(
+                "    {} / {} / {} bytes / second (1s/10s/60s)",
+                self.current(now),
+                self.average_10s(now),
+                self.average_60s(now)
+            ),
+            format!("    peak {} bytes / second", self.peak(now)),
+        ]
+    }
+}
+
+/// Maximum number of hosts traffic is counted for
+pub const MAX_HOSTS: usize = 64;
+
+/// Traffic of a single host per protocol and direction
+#[derive(Debug, Clone)]
+pub struct HostStats {
+    sent: [Counter; PROTOCOL_COUNT],
+    received: [Counter; PROTOCOL_COUNT],
+    pub rate: RateMeter,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+}
+
+impl HostStats {
+    pub fn new(first_seen: Instant) -> Self {
+        HostStats {
+            sent: Default::default(),
+            received: Default::default(),
+            rate: RateMeter::default(),
+            first_seen,
+            last_seen: first_seen,
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        protocol: Protocol,
+        direction: Direction,
+        bytes: usize,
+        timestamp: Instant,
+    ) {
+        match direction {
+            Direction::Sent => self.sent[protocol as usize].add(bytes),
+            Direction::Received => self.received[protocol as usize].add(bytes),
+        }
+        self.rate.add(bytes, timestamp);
+        self.last_seen = timestamp;
+    }
+
+    pub fn get(&self, protocol: Protocol, direction: Direction) -> Counter {
+        match direction {
+            Direction::Sent => self.sent[protocol as usize],
+            Direction::Received => self.received[protocol as usize],
+        }
+    }
+
+    /// Traffic of all protocols in direction
+    pub fn total(&self, direction: Direction) -> Counter {
+        match direction {
+            Direction::Sent => sum(self.sent.iter()),
+            Direction::Received => sum(self.received.iter()),
+        }
+    }
+}
+
+/// Traffic per host, keyed by IPv4 and IPv6 addresses alike, bounded to MAX_HOSTS entries
+pub type StatsResponses = BTreeMap<IpAddress, HostStats>;
+
+/// Traffic of every host followed by its rates
+pub fn host_lines(stats: &StatsResponses) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    let now = Instant::from_millis(system_clock::ms() as i64);
+    for (addr, host) in stats.iter() {
+        ret.push(format!("{}:", addr));
+        let sent = host.total(Direction::Sent);
+        let received = host.total(Direction::Received);
+        ret.push(format!(
+            "    sent {} packets, {} bytes",
+            sent.packets, sent.bytes
+        ));
+        ret.push(format!(
+            "    received {} packets, {} bytes",
+            received.packets, received.bytes
+        ));
+        // Breakdown as sent/received
+        for protocol in PROTOCOLS.iter() {
+            let sent = host.get(*protocol, Direction::Sent);
+            let received = host.get(*protocol, Direction::Received);
+            if sent.packets + received.packets > 0 {
+                ret.push(format!(
+                    "    {}: {}/{} packets, {}/{} bytes",
+                    protocol.name(),
+                    sent.packets,
+                    received.packets,
+                    sent.bytes,
+                    received.bytes
+                ));
+            }
+        }
+        ret.extend(host.rate.format_rates(now));
+        ret.push(String::from(""));
+    }
+    ret
+}
+
+/// Traffic of the whole segment per protocol, every frame is counted once
+#[derive(Debug, Default, Clone)]
+pub struct ProtocolMix {
+    counters: [Counter; PROTOCOL_COUNT],
+    pub rate: RateMeter,
+}
+
+impl ProtocolMix {
+    pub fn add(&mut self, protocol: Protocol, bytes: usize, timestamp: Instant) {
+        self.counters[protocol as usize].add(bytes);
+        self.rate.add(bytes, timestamp);
+    }
+
+    pub fn get(&self, protocol: Protocol) -> Counter {
+        self.counters[protocol as usize]
+    }
+
+    pub fn total(&self) -> Counter {
+        sum(self.counters.iter())
+    }
+}
+
+impl super::StringableVec for ProtocolMix {
+    fn to_string_vec(&self) -> Vec<String> {
+        let mut ret: Vec<String> = Vec::new();
+        let total = self.total();
+        ret.push(String::from("Overall:"));
+        ret.push(format!("    {} packets", total.packets));
+        ret.push(format!("    {} bytes", total.bytes));
+        ret.extend(
+            self.rate
+                .format_rates(Instant::from_millis(system_clock::ms() as i64)),
+        );
+        if total.bytes == 0 {
+            return ret;
+        }
+        ret.push(String::from("Protocol mix:"));
+        for protocol in PROTOCOLS.iter() {
+            let counter = self.get(*protocol);
+            if counter.packets > 0 {
+                ret.push(format!(
+                    "    {}: {} packets, {} bytes ({}%)",
+                    protocol.name(),
+                    counter.packets,
+                    counter.bytes,
+                    counter.bytes * 100 / total.bytes
+                ));
+            }
+        }
+        ret
+    }
+}
+
+/// Protocol and hosts of a received frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Observation {
+    pub protocol: Protocol,
+    pub src: Option<IpAddress>,
+    pub dst: Option<IpAddress>,
+    pub len: usize,
+}
+
+/// Look up the address of a known neighbor by its hardware address
+fn neighbor_addr(neighbors: &ArpResponses, eth_addr: EthernetAddress) -> Option<Ipv4Address> {
+    neighbors
+        .iter()
+        .find(|(_, x)| **x == eth_addr)
+        .map(|(addr, _)| *addr)
+}
+
+/// Classify an untagged ethernet frame
+/// Frames without IP addresses are attributed to the neighbors owning the hardware addresses
+pub fn classify(frame: &[u8], neighbors: &ArpResponses) -> Result<Observation, smoltcp::Error> {
+    let eth_frame = EthernetFrame::new_checked(frame)?;
+    let (protocol, src, dst) = match eth_frame.ethertype() {
+        EthernetProtocol::Ipv4 => {
+            let packet = Ipv4Packet::new_checked(eth_frame.payload())?;
+            let protocol = match packet.protocol() {
+                IpProtocol::Icmp => Protocol::Icmp,
+                IpProtocol::Tcp => Protocol::Tcp,
+                IpProtocol::Udp => Protocol::Udp,
+                _ => Protocol::Ipv4,
+            };
+            (
+                protocol,
+                Some(IpAddress::Ipv4(packet.src_addr())),
+                Some(IpAddress::Ipv4(packet.dst_addr())),
+            )
+        }
+        EthernetProtocol::Ipv6 => {
+            let packet = Ipv6Packet::new_checked(eth_frame.payload())?;
+            let upper = ipv6::upper_layer(packet.next_header(), packet.payload())?;
+            let protocol = match upper.protocol {
+                IpProtocol::Icmpv6 => match upper.payload.and_then(ipv6::parse_ndp) {
+                    Some(_) => Protocol::Ndp,
+                    None => Protocol::Icmpv6,
+                },
+                IpProtocol::Tcp => Protocol::Tcp,
+                IpProtocol::Udp => Protocol::Udp,
+                _ => Protocol::Ipv6,
+            };
+            (
+                protocol,
+                Some(IpAddress::Ipv6(packet.src_addr())),
+                Some(IpAddress::Ipv6(packet.dst_addr())),
+            )
+        }
+        EthernetProtocol::Arp => {
+            let arp_packet = ArpPacket::new_checked(eth_frame.payload())?;
+            match ArpRepr::parse(&arp_packet)? {
+                ArpRepr::EthernetIpv4 {
+                    source_protocol_addr,
+                    target_protocol_addr,
+                    ..
+                } => (
+                    Protocol::Arp,
+                    Some(IpAddress::Ipv4(source_protocol_addr)),
+                    Some(IpAddress::Ipv4(target_protocol_addr)),
+                ),
+                _ => return Err(smoltcp::Error::Unrecognized),
+            }
+        }
+        _ => (
+            Protocol::Other,
+            neighbor_addr(neighbors, eth_frame.src_addr()).map(IpAddress::Ipv4),
+            neighbor_addr(neighbors, eth_frame.dst_addr()).map(IpAddress::Ipv4),
+        ),
+    };
+    // Directed broadcasts like 192.168.1.255 only show in the hardware address
+    let dst = if eth_frame.dst_addr().is_unicast() {
+        dst
+    } else {
+        None
+    };
+    Ok(Observation {
+        protocol,
+        src,
+        dst,
+        len: frame.len(),
+    })
+}
+
+/// Remove the host seen least recently to make room for another one
+fn evict_oldest(stats: &mut StatsResponses) {
+    let oldest = stats
+        .iter()
+        .min_by_key(|(_, host)| host.last_seen)
+        .map(|(addr, _)| *addr);
+    if let Some(oldest) = oldest {
+        stats.remove(&oldest);
+    }
+}
+
+/// Count an observed frame for the segment and both of its hosts
+/// Broadcast and multicast addresses are no hosts and only count for the segment
+pub fn record(
+    stats: &mut StatsResponses,
+    mix: &mut ProtocolMix,
+    observation: &Observation,
+    timestamp: Instant,
+) {
+    mix.add(observation.protocol, observation.len, timestamp);
+    let hosts = [
+        (observation.src, Direction::Sent),
+        (observation.dst, Direction::Received),
+    ];
+    for (addr, direction) in hosts.iter() {
+        let addr = match addr {
+            Some(x) if x.is_unicast() => *x,
+            _ => continue,
+        };
+        if !stats.contains_key(&addr) && stats.len() >= MAX_HOSTS {
+            evict_oldest(stats);
+        }
+        stats
+            .entry(addr)
+            .or_insert_with(|| HostStats::new(timestamp))
+            .add(observation.protocol, *direction, observation.len, timestamp);
+    }
+}
@@ -1,14 +1,34 @@
+use alloc::string::String;
 use alloc::vec::Vec;
 use smoltcp::iface::EthernetInterface;
 use smoltcp::phy::Device;
 use smoltcp::socket::*;
 use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::Ipv4Address;
 use stm32f7_discovery::system_clock;
 
 use super::arp::ArpResponses;
 use super::services::{Service, TCP_SERVICES};
 use super::{PortScan, PortScans};
 
+/// Time to wait for a service greeting first after a connection is established
+const BANNER_TIMEOUT: Duration = Duration { millis: 50 };
+
+/// Start of a connection attempt, its socket, the probed service and when it was established
+type Connection = (Instant, SocketHandle, &'static Service, Option<Instant>);
+
+/// Return the first line of data sent by a service, None if it is blank
+fn banner_line(data: &[u8]) -> Option<String> {
+    let end = data.iter().position(|&c| c == b'\n').unwrap_or(data.len());
+    let line = super::printable(&data[..end]);
+    let line = line.trim_end_matches('?').trim();
+    if line.is_empty() {
+        None
+    } else {
+        Some(String::from(line))
+    }
+}
+
 /// Starts a tcp port scan for every address in addrs
 pub fn probe_addresses<'b, 'c, 'e, DeviceT>(
     iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
@@ -19,81 +39,103 @@ where
 {
     let mut ports = Vec::<PortScan>::new();
     for addr in addrs {
-        let mut serv = Vec::<&Service>::new();
-        let mut local_port = 49152;
-        let mut handles: [(bool, Option<(Instant, SocketHandle, &Service)>); 10] =
-            [(false, None); 10];
-        let mut socket_count = 0;
+        ports.push(probe_address(iface, *addr.0));
+    }
+    ports
+}
+
+/// Starts a tcp port scan of a single address
+pub fn probe_address<'b, 'c, 'e, DeviceT>(
+    iface: &mut EthernetInterface<'b, 'c, 'e, DeviceT>,
+    addr: Ipv4Address,
+) -> PortScan
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let mut serv = Vec::<&Service>::new();
+    let mut banners = Vec::<(u16, String)>::new();
+    let mut local_port = 49152;
+    let mut handles: [(bool, Option<Connection>); 10] = [(false, None); 10];
+    let mut socket_count = 0;
 
-        let mut serv_iter = TCP_SERVICES.iter();
-        let mut iter_done = false;
-        while !iter_done {
-            let mut sockets = SocketSet::new(Vec::new());
-            // Limit amount of sockets to open simultaneously to prevent OOM
-            for i in 0..10 {
-                let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; 64]);
-                let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; 128]);
-                let tcp_socket = TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer);
+    let mut serv_iter = TCP_SERVICES.iter();
+    let mut iter_done = false;
+    while !iter_done {
+        let mut sockets = SocketSet::new(Vec::new());
+        // Limit amount of sockets to open simultaneously to prevent OOM
+        for i in 0..10 {
+            let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; 64]);
+            let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; 128]);
+            let tcp_socket = TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer);
 
-                let tcp_handle = sockets.add(tcp_socket);
-                let port = match serv_iter.next() {
-                    Some(x) => x,
-                    None => {
-                        iter_done = true;
-                        break;
-                    }
-                };
-                {
-                    let mut socket = sockets.get::<TcpSocket>(tcp_handle);
-                    socket.connect((*addr.0, port.0), local_port).unwrap();
-                    local_port += 1;
+            let tcp_handle = sockets.add(tcp_socket);
+            let port = match serv_iter.next() {
+                Some(x) => x,
+                None => {
+                    iter_done = true;
+                    break;
                 }
-                handles[i] = (
-                    false,
-                    Some((
-                        Instant::from_millis(system_clock::ms() as i64),
-                        tcp_handle,
-                        port,
-                    )),
-                );
-                socket_count += 1;
+            };
+            {
+                let mut socket = sockets.get::<TcpSocket>(tcp_handle);
+                socket.connect((addr, port.0), local_port).unwrap();
+                local_port += 1;
             }
-            // Poll sockets until connection is established or they time out
-            while socket_count > 0 {
-                let timestamp = Instant::from_millis(system_clock::ms() as i64);
-                match iface.poll(&mut sockets, timestamp) {
-                    Ok(_) => {}
-                    Err(_) => {}
+            handles[i] = (
+                false,
+                Some((
+                    Instant::from_millis(system_clock::ms() as i64),
+                    tcp_handle,
+                    port,
+                    None,
+                )),
+            );
+            socket_count += 1;
+        }
+        // Poll sockets until connection is established or they time out
+        while socket_count > 0 {
+            let timestamp = Instant::from_millis(system_clock::ms() as i64);
+            match iface.poll(&mut sockets, timestamp) {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+            for (done, opt) in handles.iter_mut() {
+                if *done {
+                    continue;
                 }
-                for (done, opt) in handles.iter_mut() {
-                    if *done {
-                        continue;
+                if let Some(x) = opt {
+                    let (stamp, handle, port, established) = x;
+                    let mut socket = sockets.get::<TcpSocket>(*handle);
+                    if established.is_none() && socket.state() == TcpState::Established {
+                        serv.push(port);
+                        *established = Some(timestamp);
                     }
-                    if let Some(x) = opt {
-                        let (stamp, handle, port) = x;
-                        let mut socket = sockets.get::<TcpSocket>(*handle);
-                        if socket.state() == TcpState::Established {
-                            serv.push(port);
-                            if socket.can_send() {
-                                socket.close();
-                            } else {
-                                socket.abort();
+                    let finished = match *established {
+                        Some(_) if socket.can_recv() => {
+                            if let Ok(Some(banner)) =
+                                socket.recv(|data| (data.len(), banner_line(data)))
+                            {
+                                banners.push((port.0, banner));
                             }
-                            socket_count -= 1;
-                            *done = true;
-                            *opt = None;
-                        } else if timestamp - Duration::from_millis(100) > *stamp {
+                            true
+                        }
+                        Some(at) => timestamp - BANNER_TIMEOUT > at || !socket.may_recv(),
+                        None => timestamp - Duration::from_millis(100) > *stamp,
+                    };
+                    if finished {
+                        if socket.can_send() {
+                            socket.close();
+                        } else {
                             socket.abort();
-                            socket_count -= 1;
-                            *done = true;
-                            *opt = None;
                         }
+                        socket_count -= 1;
+                        *done = true;
+                        *opt = None;
                     }
                 }
             }
-            // sockets.prune();
         }
-        ports.push(PortScan(*addr.0, serv));
+        // sockets.prune();
     }
-    ports
+    PortScan(addr, serv, banners)
 }